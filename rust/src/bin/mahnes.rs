@@ -0,0 +1,413 @@
+extern crate mahnes_rs;
+
+use mahnes_rs::{Core, PowerOnRam, RomINES, Movie, RomDisassembly, GdbStub, Debugger, Breakpoint, AddressSpace, WATCH_EXECUTE, write_png};
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+use std::path::Path;
+use std::process;
+
+
+const USAGE: &str = "usage: mahnes <rom.nes> [options]
+  --frames N            run N frames (default 60)
+  --until EXPR          stop once the debugger expression EXPR holds, e.g. \"[$6000] == 0\"
+  --ram-init KIND       power-on RAM contents: zero, ff, pattern or random[:SEED]
+  --input FILE          scripted controller input, lines of \"<frame> <buttons>\"
+  --movie FILE          play back an FCEUX .fm2 movie from power-on
+  --record FILE         record controller input to an .fm2 movie
+  --screenshot N        save frame N as a PNG (repeatable)
+  --screenshot-dir DIR  directory for screenshots (default .)
+  --wav FILE            record audio as a WAV file (not available: no APU is emulated yet)
+  --dump-ram FILE       write internal RAM on exit
+  --dump-vram FILE      write nametable RAM on exit
+  --cdl FILE            write an FCEUX code/data log on exit
+  --disassemble FILE    write a ca65 disassembly of the mapped PRG ROM on exit
+  --xrefs FILE          write the disassembly's cross-reference list
+  --gdb PORT            wait for a GDB remote connection on localhost before running
+  --cheat CODE          apply a Game Genie or raw \"AAAA:VV\" cheat (repeatable)
+  --cheats FILE         load cheats from an FCEUX .cht file
+  --symbols FILE        load labels from a .dbg, .mlb or .nl file (repeatable)
+  --info                print the iNES header";
+
+
+struct Options
+{
+	rom_path: String,
+	frames: usize,
+	until: Option<String>,
+	power_on_ram: PowerOnRam,
+	input_path: Option<String>,
+	movie_path: Option<String>,
+	record_path: Option<String>,
+	screenshot_frames: Vec<usize>,
+	screenshot_dir: String,
+	wav: Option<String>,
+	dump_ram: Option<String>,
+	dump_vram: Option<String>,
+	cdl: Option<String>,
+	disassemble: Option<String>,
+	xrefs: Option<String>,
+	symbol_paths: Vec<String>,
+	cheat_codes: Vec<String>,
+	cheats_path: Option<String>,
+	gdb_port: Option<u16>,
+	info: bool
+}
+
+
+fn main()
+{
+	let options = match parse_args(env::args().skip(1).collect())
+	{
+		Ok(options) => options,
+		Err(err) =>
+		{
+			eprintln!("error: {}\n\n{}", err, USAGE);
+			process::exit(2);
+		}
+	};
+	
+	if let Err(err) = run(&options)
+	{
+		eprintln!("error: {}", err);
+		process::exit(1);
+	}
+}
+
+
+fn parse_args(args: Vec<String>) -> Result<Options, String>
+{
+	let mut options = Options
+	{
+		rom_path: String::new(),
+		frames: 60,
+		until: None,
+		power_on_ram: PowerOnRam::Zero,
+		input_path: None,
+		movie_path: None,
+		record_path: None,
+		screenshot_frames: Vec::new(),
+		screenshot_dir: ".".to_string(),
+		wav: None,
+		dump_ram: None,
+		dump_vram: None,
+		cdl: None,
+		disassemble: None,
+		xrefs: None,
+		symbol_paths: Vec::new(),
+		cheat_codes: Vec::new(),
+		cheats_path: None,
+		gdb_port: None,
+		info: false
+	};
+	
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next()
+	{
+		let mut value = |name: &str| args.next().ok_or(format!("missing value for {}", name));
+		
+		match arg.as_ref()
+		{
+			"--frames" => options.frames = parse_number(&value("--frames")?)?,
+			"--until" => options.until = Some(value("--until")?),
+			"--ram-init" => options.power_on_ram = parse_power_on_ram(&value("--ram-init")?)?,
+			"--input" => options.input_path = Some(value("--input")?),
+			"--movie" => options.movie_path = Some(value("--movie")?),
+			"--record" => options.record_path = Some(value("--record")?),
+			"--screenshot" => options.screenshot_frames.push(parse_number(&value("--screenshot")?)?),
+			"--screenshot-dir" => options.screenshot_dir = value("--screenshot-dir")?,
+			"--wav" => options.wav = Some(value("--wav")?),
+			"--dump-ram" => options.dump_ram = Some(value("--dump-ram")?),
+			"--dump-vram" => options.dump_vram = Some(value("--dump-vram")?),
+			"--cdl" => options.cdl = Some(value("--cdl")?),
+			"--disassemble" => options.disassemble = Some(value("--disassemble")?),
+			"--xrefs" => options.xrefs = Some(value("--xrefs")?),
+			"--gdb" => options.gdb_port = Some(value("--gdb")?.parse().map_err(|_| "--gdb: invalid port".to_string())?),
+			"--symbols" => options.symbol_paths.push(value("--symbols")?),
+			"--cheat" => options.cheat_codes.push(value("--cheat")?),
+			"--cheats" => options.cheats_path = Some(value("--cheats")?),
+			"--info" => options.info = true,
+			
+			"-h" | "--help" =>
+			{
+				println!("{}", USAGE);
+				process::exit(0);
+			}
+			
+			_ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+			_ if options.rom_path.is_empty() => options.rom_path = arg,
+			_ => return Err(format!("unexpected argument {}", arg))
+		}
+	}
+	
+	if options.rom_path.is_empty()
+		{ return Err("no ROM given".to_string()); }
+		
+	Ok(options)
+}
+
+
+fn parse_number(text: &str) -> Result<usize, String>
+{
+	text.parse().map_err(|_| format!("invalid number `{}`", text))
+}
+
+
+fn parse_power_on_ram(text: &str) -> Result<PowerOnRam, String>
+{
+	let mut parts = text.splitn(2, ':');
+	
+	match (parts.next().unwrap(), parts.next())
+	{
+		("zero", None) => Ok(PowerOnRam::Zero),
+		("ff", None) => Ok(PowerOnRam::Ones),
+		("pattern", None) => Ok(PowerOnRam::Pattern),
+		("random", None) =>
+		{
+			// Without the seed a failure seen this way couldn't be reproduced
+			let seed = process::id();
+			eprintln!("power-on RAM seed: {} (repeat with --ram-init random:{})", seed, seed);
+			Ok(PowerOnRam::Random(seed))
+		}
+		
+		("random", Some(seed)) => Ok(PowerOnRam::Random(seed.parse().map_err(|_| format!("invalid seed `{}`", seed))?)),
+		_ => Err(format!("unknown RAM initialisation `{}`", text))
+	}
+}
+
+
+fn run(options: &Options) -> Result<(), String>
+{
+	if let Some(ref path) = options.wav
+		{ return Err(format!("{}: can't record audio, no APU is emulated yet", path)); }
+		
+	let buffer = fs::read(&options.rom_path).map_err(|err| format!("{}: {}", options.rom_path, err))?;
+	
+	if buffer.len() < 16 || &buffer[0..4] != b"NES\x1a"
+		{ return Err(format!("{}: not an iNES file", options.rom_path)); }
+		
+	if buffer.len() < 16 + buffer[4] as usize * 0x4000 + buffer[5] as usize * 0x2000
+		{ return Err(format!("{}: file is truncated", options.rom_path)); }
+		
+	let ines = RomINES::new(&buffer);
+	
+	if options.info
+		{ print_header(&ines); }
+		
+	let mapper_id = ines.mapper_id;
+	let cartridge = ines.make_cartridge().ok_or(format!("unsupported mapper {}", mapper_id))?;
+	
+	let inputs = match options.input_path
+	{
+		Some(ref path) => parse_input_script(path)?,
+		None => Vec::new()
+	};
+	
+	let mut core = Core::new(Box::new(cartridge));
+	core.power_on_ram = options.power_on_ram;
+	core.reset();
+	// The code/data log also guides the disassembler
+	core.set_cdl(options.cdl.is_some() || options.disassemble.is_some() || options.xrefs.is_some());
+	
+	for path in &options.symbol_paths
+		{ load_symbols(&mut core, path)?; }
+		
+	if let Some(ref path) = options.cheats_path
+	{
+		let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+		core.load_cheats(&text).map_err(|err| format!("{}: {}", path, err))?;
+	}
+	
+	for code in &options.cheat_codes
+		{ core.add_cheat(code).map_err(|err| format!("--cheat: {}", err))?; }
+		
+	if let Some(ref path) = options.movie_path
+	{
+		let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+		core.play_movie(Movie::parse_fm2(&text).map_err(|err| format!("{}: {}", path, err))?);
+	}
+	else if options.record_path.is_some()
+	{
+		let mut movie = Movie::new();
+		let rom_name = Path::new(&options.rom_path).file_stem().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+		
+		for (key, value) in &mut movie.header
+		{
+			if key == "romFilename"
+				{ *value = rom_name.clone(); }
+		}
+		
+		core.record_movie(movie);
+	}
+	
+	if let Some(ref condition) = options.until
+	{
+		let mut debugger = Debugger::new();
+		debugger.add(Breakpoint
+		{
+			space: AddressSpace::Cpu,
+			addr_start: 0x0000,
+			addr_end: 0xffff,
+			flags: WATCH_EXECUTE,
+			enabled: true,
+			condition: Some(core.parse_expression(condition).map_err(|err| format!("--until: {}", err))?)
+		});
+		
+		core.debugger = Some(Box::new(debugger));
+	}
+	
+	if let Some(port) = options.gdb_port
+	{
+		let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|err| format!("--gdb: {}", err))?;
+		println!("waiting for GDB on 127.0.0.1:{}", port);
+		
+		let (stream, _) = listener.accept().map_err(|err| format!("--gdb: {}", err))?;
+		GdbStub::new(stream).run(&mut core).map_err(|err| format!("--gdb: {}", err))?;
+	}
+	
+	let mut next_input = 0;
+	let mut frame = 0;
+	
+	while frame < options.frames
+	{
+		while next_input < inputs.len() && inputs[next_input].0 <= frame
+		{
+			core.controller1 = inputs[next_input].1;
+			next_input += 1;
+		}
+		
+		let stop = core.run_frame();
+		
+		if let Some(reason) = stop
+		{
+			println!("stopped during frame {} at ${:04x}: {:?}", frame + 1, core.cpu.reg_pc.wrapping_sub(1), reason);
+			break;
+		}
+		
+		frame += 1;
+		
+		if options.screenshot_frames.contains(&frame)
+		{
+			let path = Path::new(&options.screenshot_dir).join(format!("frame_{}.png", frame));
+			let mut file = fs::File::create(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+			write_png(&mut file, 256, 240, &core.screen).map_err(|err| format!("{}: {}", path.display(), err))?;
+		}
+	}
+	
+	println!("ran {} frames ({} lag), screen hash {:08x}", frame, core.lag_frames, core.get_screen_hash());
+	
+	if let (Some(path), Some(movie)) = (options.record_path.as_ref(), core.stop_movie())
+		{ fs::write(path, movie.to_fm2()).map_err(|err| format!("{}: {}", path, err))?; }
+		
+	if let Some(ref path) = options.dump_ram
+		{ fs::write(path, &core.ram[..]).map_err(|err| format!("{}: {}", path, err))?; }
+		
+	if let Some(ref path) = options.dump_vram
+		{ fs::write(path, &core.vram[..]).map_err(|err| format!("{}: {}", path, err))?; }
+		
+	if let (Some(path), Some(cdl)) = (options.cdl.as_ref(), core.cdl.as_ref())
+		{ fs::write(path, cdl.to_cdl()).map_err(|err| format!("{}: {}", path, err))?; }
+		
+	if options.disassemble.is_some() || options.xrefs.is_some()
+	{
+		let dis = disassemble_prg(&core);
+		let labels = |addr| core.symbols.find_label(&core, addr);
+		
+		if let Some(ref path) = options.disassemble
+			{ fs::write(path, dis.to_ca65(&labels)).map_err(|err| format!("{}: {}", path, err))?; }
+			
+		if let Some(ref path) = options.xrefs
+			{ fs::write(path, dis.format_xrefs(&labels)).map_err(|err| format!("{}: {}", path, err))?; }
+	}
+	
+	Ok(())
+}
+
+
+// Disassembles the PRG ROM as currently mapped, skipping the mirror of a 16 KB ROM
+fn disassemble_prg(core: &Core) -> RomDisassembly
+{
+	let base: u16 = if core.cartridge.get_prg_rom_size() <= 0x4000 { 0xc000 } else { 0x8000 };
+	let prg: Vec<u8> = (base..=0xffff).map(|addr| core.peek_cpu(addr)).collect();
+	
+	let cdl: Option<Vec<u8>> = core.cdl.as_ref().map(|cdl|
+		(base..=0xffff).map(|addr| core.cartridge.cpu_addr_to_prg_offset(addr).and_then(|offset| cdl.prg.get(offset).cloned()).unwrap_or(0)).collect());
+		
+	RomDisassembly::new(&prg, base, cdl.as_ref().map(|cdl| &cdl[..]))
+}
+
+
+fn print_header(ines: &RomINES)
+{
+	println!("mapper:     {}", ines.mapper_id);
+	println!("PRG ROM:    {} KB", ines.prg_byte_num / 1024);
+	println!("CHR ROM:    {} KB{}", ines.chr_byte_num / 1024, if ines.chr_byte_num == 0 { " (CHR RAM)" } else { "" });
+	println!("mirroring:  {}", if ines.mirroring { "horizontal" } else { "vertical" });
+	println!("battery:    {}", if ines.has_sram { "yes" } else { "no" });
+	println!("trainer:    {}", if ines.has_trainer { "yes" } else { "no" });
+	println!("region:     {}", if (ines.region & 1) != 0 { "PAL" } else { "NTSC" });
+}
+
+
+// FCEUX names its files "<rom>.ram.nl" and "<rom>.<bank>.nl"
+fn load_symbols(core: &mut Core, path: &str) -> Result<(), String>
+{
+	let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+	
+	if path.ends_with(".dbg")
+		{ core.symbols.load_dbg(&text); }
+	else if path.ends_with(".mlb")
+		{ core.symbols.load_mlb(&text); }
+	else if path.ends_with(".nl")
+	{
+		let bank = path.trim_end_matches(".nl").rsplit('.').next().and_then(|bank| usize::from_str_radix(bank, 16).ok());
+		core.symbols.load_nl(&text, bank);
+	}
+	else
+		{ return Err(format!("{}: unknown symbol file type", path)); }
+		
+	Ok(())
+}
+
+
+// Each line holds "<frame> <buttons>", and the buttons stay held until the next line
+fn parse_input_script(path: &str) -> Result<Vec<(usize, u8)>, String>
+{
+	let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+	let mut inputs = Vec::new();
+	
+	for (line_index, line) in text.lines().enumerate()
+	{
+		let line = line.split('#').next().unwrap().trim();
+		if line.is_empty()
+			{ continue; }
+			
+		let error = |msg: String| format!("{}:{}: {}", path, line_index + 1, msg);
+		
+		let mut words = line.split_whitespace();
+		let frame = parse_number(words.next().unwrap()).map_err(&error)?;
+		
+		let mut buttons = 0;
+		for word in words.flat_map(|word| word.split('+'))
+		{
+			buttons |= match word.to_ascii_uppercase().as_ref()
+			{
+				"A" => 0x01,
+				"B" => 0x02,
+				"SELECT" => 0x04,
+				"START" => 0x08,
+				"UP" => 0x10,
+				"DOWN" => 0x20,
+				"LEFT" => 0x40,
+				"RIGHT" => 0x80,
+				"-" | "NONE" => 0x00,
+				_ => return Err(error(format!("unknown button `{}`", word)))
+			};
+		}
+		
+		inputs.push((frame, buttons));
+	}
+	
+	inputs.sort_by_key(|&(frame, _)| frame);
+	Ok(inputs)
+}
\ No newline at end of file
@@ -0,0 +1,134 @@
+use Core;
+use RomINES;
+use std::panic;
+
+
+const STATUS_ADDR: u16 = 0x6000;
+const SIGNATURE_ADDR: u16 = 0x6001;
+const TEXT_ADDR: u16 = 0x6004;
+
+const SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET_REQUESTED: u8 = 0x81;
+
+// The protocol asks for at least 100ms before pressing reset
+const RESET_DELAY_FRAMES: usize = 6;
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlarggResult
+{
+	Passed(String),
+	Failed(u8, String),
+	Timeout(String),
+	Crashed(String),
+	Unsupported
+}
+
+
+impl BlarggResult
+{
+	pub fn is_pass(&self) -> bool
+	{
+		matches!(*self, BlarggResult::Passed(_))
+	}
+}
+
+
+pub fn run_blargg_rom(rom: &[u8], max_frames: usize) -> BlarggResult
+{
+	let result = panic::catch_unwind(panic::AssertUnwindSafe(||
+	{
+		let ines = RomINES::new(rom);
+		let cartridge = match ines.make_cartridge()
+		{
+			Some(cartridge) => cartridge,
+			None => return BlarggResult::Unsupported
+		};
+		
+		let mut core = Core::new(Box::new(cartridge));
+		core.reset();
+		
+		run_blargg_core(&mut core, max_frames)
+	}));
+	
+	match result
+	{
+		Ok(result) => result,
+		Err(err) =>
+		{
+			let message = match err.downcast_ref::<&str>()
+			{
+				Some(message) => message.to_string(),
+				None => match err.downcast_ref::<String>()
+				{
+					Some(message) => message.clone(),
+					None => "unknown panic".to_string()
+				}
+			};
+			
+			BlarggResult::Crashed(message)
+		}
+	}
+}
+
+
+fn run_blargg_core(core: &mut Core, max_frames: usize) -> BlarggResult
+{
+	let mut reset_countdown = None;
+	let mut reset_done = false;
+	
+	for _ in 0..max_frames
+	{
+		core.run_frame();
+		
+		let signature = [core.peek_cpu(SIGNATURE_ADDR), core.peek_cpu(SIGNATURE_ADDR + 1), core.peek_cpu(SIGNATURE_ADDR + 2)];
+		if signature != SIGNATURE
+			{ continue; }
+			
+		match core.peek_cpu(STATUS_ADDR)
+		{
+			STATUS_RUNNING => reset_done = false,
+			
+			STATUS_RESET_REQUESTED =>
+			{
+				// Keep reporting $81 after the reset until the ROM restarts
+				if reset_done
+					{ continue; }
+					
+				let countdown = reset_countdown.unwrap_or(RESET_DELAY_FRAMES);
+				if countdown == 0
+				{
+					core.soft_reset();
+					reset_countdown = None;
+					reset_done = true;
+				}
+				else
+					{ reset_countdown = Some(countdown - 1); }
+			}
+			
+			0 => return BlarggResult::Passed(read_blargg_text(core)),
+			status => return BlarggResult::Failed(status, read_blargg_text(core))
+		}
+	}
+	
+	BlarggResult::Timeout(read_blargg_text(core))
+}
+
+
+fn read_blargg_text(core: &Core) -> String
+{
+	let mut text = String::new();
+	
+	for i in 0..0x1000
+	{
+		let c = core.peek_cpu(TEXT_ADDR + i);
+		if c == 0
+			{ break; }
+			
+		text.push(c as char);
+	}
+	
+	text.trim().to_string()
+}
\ No newline at end of file
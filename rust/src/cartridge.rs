@@ -12,18 +12,62 @@ pub trait Cartridge
 	}
 	
 	
-	fn cpu_read(&mut self, _addr: u16) -> u8
+	fn get_prg_rom_size(&self) -> usize
 	{
 		0
 	}
 	
 	
+	fn get_chr_rom_size(&self) -> usize
+	{
+		0
+	}
+	
+	
+	// PRG RAM mapped at $6000
+	fn get_prg_ram_size(&self) -> usize
+	{
+		0
+	}
+	
+	
+	fn cpu_addr_to_prg_offset(&self, _addr: u16) -> Option<usize>
+	{
+		None
+	}
+	
+	
+	fn ppu_addr_to_chr_offset(&self, _addr: u16) -> Option<usize>
+	{
+		None
+	}
+	
+	
+	fn cpu_read(&mut self, _addr: u16) -> Option<u8>
+	{
+		None
+	}
+	
+	
 	fn cpu_write(&mut self, _addr: u16, _val: u8)
 	{
 	
 	}
 	
 	
+	fn cpu_peek(&self, _addr: u16) -> Option<u8>
+	{
+		None
+	}
+	
+	
+	// Changes PRG RAM or ROM contents directly, bypassing mapper registers
+	fn cpu_poke(&mut self, _addr: u16, _val: u8)
+	{
+	
+	}
+	
+	
 	fn ppu_read(&mut self, _addr: u16) -> u8
 	{
 		0
@@ -36,6 +80,18 @@ pub trait Cartridge
 	}
 	
 	
+	fn ppu_peek(&self, _addr: u16) -> u8
+	{
+		0
+	}
+	
+	
+	fn ppu_poke(&mut self, _addr: u16, _val: u8)
+	{
+	
+	}
+	
+	
 	fn ppu_ciram_enable(&self, _addr: u16) -> bool
 	{
 		false
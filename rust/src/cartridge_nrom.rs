@@ -7,6 +7,11 @@ pub struct CartridgeNROM
 	prg_rom: Vec<u8>,
 	chr_rom: Vec<u8>,
 	chr_ram: [u8; 0x2000],
+	
+	// Family BASIC boards have 8 KB at $6000, and iNES headers rarely say so, so it's
+	// always mapped like FCEUX does. Test ROMs report their results through it.
+	prg_ram: [u8; 0x2000],
+	
 	mirroring: bool
 }
 
@@ -20,6 +25,7 @@ impl CartridgeNROM
 			prg_rom: ines.prg_rom,
 			chr_rom: ines.chr_rom,
 			chr_ram: [0; 0x2000],
+			prg_ram: [0; 0x2000],
 			mirroring: ines.mirroring
 		}
 	}
@@ -40,28 +46,108 @@ impl Cartridge for CartridgeNROM
 	}
 	
 	
-	fn cpu_read(&mut self, addr: u16) -> u8
+	fn get_prg_rom_size(&self) -> usize
+	{
+		self.prg_rom.len()
+	}
+	
+	
+	fn get_chr_rom_size(&self) -> usize
+	{
+		self.chr_rom.len()
+	}
+	
+	
+	fn get_prg_ram_size(&self) -> usize
+	{
+		self.prg_ram.len()
+	}
+	
+	
+	fn cpu_addr_to_prg_offset(&self, addr: u16) -> Option<usize>
 	{
-		if self.prg_rom.len() < 0x8000
-			{ self.prg_rom[(addr & 0x3fff) as usize] }
+		if addr < 0x8000 || self.prg_rom.is_empty()
+			{ None }
 		else
-			{ self.prg_rom[(addr & 0x7fff) as usize] }
+			{ Some((addr as usize & 0x7fff) % self.prg_rom.len()) }
+	}
+	
+	
+	fn ppu_addr_to_chr_offset(&self, addr: u16) -> Option<usize>
+	{
+		if addr < 0x2000 && !self.chr_rom.is_empty()
+			{ Some(addr as usize % self.chr_rom.len()) }
+		else
+			{ None }
+	}
+	
+	
+	fn cpu_read(&mut self, addr: u16) -> Option<u8>
+	{
+		self.cpu_peek(addr)
+	}
+	
+	
+	fn cpu_write(&mut self, addr: u16, val: u8)
+	{
+		if addr >= 0x6000 && addr < 0x8000
+			{ self.prg_ram[(addr & 0x1fff) as usize] = val; }
+	}
+	
+	
+	fn cpu_peek(&self, addr: u16) -> Option<u8>
+	{
+		if addr < 0x6000
+			{ None }
+		else if addr < 0x8000
+			{ Some(self.prg_ram[(addr & 0x1fff) as usize]) }
+		else if self.prg_rom.len() < 0x8000
+			{ Some(self.prg_rom[(addr & 0x3fff) as usize]) }
+		else
+			{ Some(self.prg_rom[(addr & 0x7fff) as usize]) }
+	}
+	
+	
+	fn cpu_poke(&mut self, addr: u16, val: u8)
+	{
+		if addr >= 0x6000 && addr < 0x8000
+			{ self.prg_ram[(addr & 0x1fff) as usize] = val; }
+		else if addr >= 0x8000 && !self.prg_rom.is_empty()
+		{
+			let len = self.prg_rom.len();
+			self.prg_rom[(addr as usize & 0x7fff) % len] = val;
+		}
 	}
 	
 	
 	fn ppu_read(&mut self, addr: u16) -> u8
 	{
-		if self.chr_rom.len() == 0
+		self.ppu_peek(addr)
+	}
+	
+	
+	fn ppu_write(&mut self, addr: u16, val: u8)
+	{
+		if self.chr_rom.is_empty() && addr < 0x2000
+			{ self.chr_ram[addr as usize] = val; }
+	}
+	
+	
+	fn ppu_peek(&self, addr: u16) -> u8
+	{
+		if self.chr_rom.is_empty()
 			{ self.chr_ram[(addr & 0x1fff) as usize] }
 		else
 			{ self.chr_rom[(addr & 0x1fff) as usize] }
 	}
 	
 	
-	fn ppu_write(&mut self, addr: u16, val: u8)
+	fn ppu_poke(&mut self, addr: u16, val: u8)
 	{
-		if self.chr_rom.len() == 0 && addr < 0x2000
-			{ self.chr_ram[addr as usize] = val; }
+		if self.chr_rom.is_empty()
+			{ self.chr_ram[(addr & 0x1fff) as usize] = val; }
+		else
+			{ self.chr_rom[(addr & 0x1fff) as usize] = val; }
 	}
 	
 	
@@ -0,0 +1,162 @@
+use Core;
+use cpu_dis;
+use cpu_dis::AddressingMode;
+
+
+// FCEUX .cdl flags for PRG bytes; bits 2-3 hold the 8KB CPU bank the byte was seen in
+pub const CDL_PRG_CODE: u8 = 0x01;
+pub const CDL_PRG_DATA: u8 = 0x02;
+pub const CDL_PRG_INDIRECT_CODE: u8 = 0x10;
+pub const CDL_PRG_INDIRECT_DATA: u8 = 0x20;
+
+// FCEUX .cdl flags for CHR bytes
+pub const CDL_CHR_RENDERED: u8 = 0x01;
+pub const CDL_CHR_READ: u8 = 0x02;
+
+
+pub struct CodeDataLogger
+{
+	pub prg: Vec<u8>,
+	pub chr: Vec<u8>,
+	
+	// Set by JMP ($nnnn), so the instruction it lands on is marked as indirect code
+	indirect_jump_pending: bool
+}
+
+
+impl CodeDataLogger
+{
+	pub fn new(prg_size: usize, chr_size: usize) -> CodeDataLogger
+	{
+		CodeDataLogger
+		{
+			prg: vec![0; prg_size],
+			chr: vec![0; chr_size],
+			indirect_jump_pending: false
+		}
+	}
+	
+	
+	pub fn from_cdl(buffer: &[u8], prg_size: usize, chr_size: usize) -> Option<CodeDataLogger>
+	{
+		if buffer.len() != prg_size + chr_size
+			{ return None; }
+			
+		Some(CodeDataLogger
+		{
+			prg: buffer[0..prg_size].to_vec(),
+			chr: buffer[prg_size..].to_vec(),
+			indirect_jump_pending: false
+		})
+	}
+	
+	
+	pub fn to_cdl(&self) -> Vec<u8>
+	{
+		let mut buffer = Vec::with_capacity(self.prg.len() + self.chr.len());
+		buffer.extend_from_slice(&self.prg);
+		buffer.extend_from_slice(&self.chr);
+		buffer
+	}
+	
+	
+	pub fn log_instruction(&mut self, core: &Core, addr: u16)
+	{
+		let opcode = core.peek_cpu(addr);
+		let byte2 = core.peek_cpu(addr.wrapping_add(1));
+		let byte3 = core.peek_cpu(addr.wrapping_add(2));
+		
+		if self.indirect_jump_pending
+		{
+			self.indirect_jump_pending = false;
+			self.log_prg(core, addr, CDL_PRG_INDIRECT_CODE);
+		}
+		
+		let (mnemonic, mode) = match cpu_dis::decode_opcode_variant(opcode, core.cpu.variant)
+		{
+			Some(decoded) => decoded,
+			None =>
+			{
+				self.log_prg(core, addr, CDL_PRG_CODE);
+				return;
+			}
+		};
+		
+		for i in 0..mode.get_length()
+			{ self.log_prg(core, addr.wrapping_add(i as u16), CDL_PRG_CODE); }
+			
+		let abs = (byte2 as u16) | ((byte3 as u16) << 8);
+		let reads_data = !matches!(mnemonic, "sta" | "stx" | "sty" | "stz" | "jmp" | "jsr");
+		
+		match mode
+		{
+			AddressingMode::Ind =>
+			{
+				self.log_prg(core, abs, CDL_PRG_DATA);
+				self.log_prg(core, cpu_dis::get_pointer_high_addr(abs, core.cpu.variant), CDL_PRG_DATA);
+				self.indirect_jump_pending = true;
+			}
+			
+			AddressingMode::Iax =>
+			{
+				let pointer = abs.wrapping_add(core.cpu.reg_x as u16);
+				self.log_prg(core, pointer, CDL_PRG_DATA);
+				self.log_prg(core, pointer.wrapping_add(1), CDL_PRG_DATA);
+				self.indirect_jump_pending = true;
+			}
+			
+			AddressingMode::Abs if reads_data =>
+				self.log_prg(core, abs, CDL_PRG_DATA),
+				
+			AddressingMode::Abx if reads_data =>
+				self.log_prg(core, abs.wrapping_add(core.cpu.reg_x as u16), CDL_PRG_DATA),
+				
+			AddressingMode::Aby if reads_data =>
+				self.log_prg(core, abs.wrapping_add(core.cpu.reg_y as u16), CDL_PRG_DATA),
+				
+			AddressingMode::Ptx if reads_data =>
+			{
+				let pointer = byte2.wrapping_add(core.cpu.reg_x);
+				let effective = (core.peek_cpu(pointer as u16) as u16) | ((core.peek_cpu(pointer.wrapping_add(1) as u16) as u16) << 8);
+				self.log_prg(core, effective, CDL_PRG_DATA | CDL_PRG_INDIRECT_DATA);
+			}
+			
+			AddressingMode::Ptr if reads_data =>
+			{
+				let effective = (core.peek_cpu(byte2 as u16) as u16) | ((core.peek_cpu(byte2.wrapping_add(1) as u16) as u16) << 8);
+				self.log_prg(core, effective, CDL_PRG_DATA | CDL_PRG_INDIRECT_DATA);
+			}
+			
+			AddressingMode::Pty if reads_data =>
+			{
+				let base = (core.peek_cpu(byte2 as u16) as u16) | ((core.peek_cpu(byte2.wrapping_add(1) as u16) as u16) << 8);
+				self.log_prg(core, base.wrapping_add(core.cpu.reg_y as u16), CDL_PRG_DATA | CDL_PRG_INDIRECT_DATA);
+			}
+			
+			_ => { }
+		}
+	}
+	
+	
+	pub fn log_prg(&mut self, core: &Core, addr: u16, flags: u8)
+	{
+		if let Some(offset) = core.cartridge.cpu_addr_to_prg_offset(addr)
+		{
+			if let Some(byte) = self.prg.get_mut(offset)
+				{ *byte |= flags | ((((addr >> 13) & 0x3) as u8) << 2); }
+		}
+	}
+	
+	
+	pub fn log_chr(&mut self, offset: usize, flags: u8)
+	{
+		if let Some(byte) = self.chr.get_mut(offset)
+			{ *byte |= flags; }
+	}
+	
+	
+	pub fn count_logged_prg(&self) -> usize
+	{
+		self.prg.iter().filter(|&&byte| (byte & (CDL_PRG_CODE | CDL_PRG_DATA)) != 0).count()
+	}
+}
\ No newline at end of file
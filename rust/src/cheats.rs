@@ -0,0 +1,220 @@
+use Core;
+
+
+const GAME_GENIE_LETTERS: &[u8; 16] = b"APZLGITYEOXUKSVN";
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheatKind
+{
+	// Written to memory once per frame
+	Freeze,
+	
+	// Replaces what the CPU reads from the cartridge, like a Game Genie
+	Substitute
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cheat
+{
+	pub kind: CheatKind,
+	pub addr: u16,
+	pub value: u8,
+	pub compare: Option<u8>,
+	pub enabled: bool,
+	pub name: String
+}
+
+
+impl Cheat
+{
+	pub fn new(kind: CheatKind, addr: u16, value: u8, compare: Option<u8>) -> Cheat
+	{
+		Cheat
+		{
+			kind,
+			addr,
+			value,
+			compare,
+			enabled: true,
+			name: String::new()
+		}
+	}
+	
+	
+	// Accepts Game Genie codes and raw "AAAA:VV" or "AAAA?CC:VV" codes
+	pub fn parse(code: &str) -> Result<Cheat, String>
+	{
+		let code = code.trim();
+		
+		if !code.contains(':')
+			{ return Cheat::from_game_genie(code); }
+			
+		let mut parts = code.splitn(2, ':');
+		let target = parts.next().unwrap();
+		let value = parse_hex_u8(parts.next().unwrap()).ok_or(format!("invalid value in `{}`", code))?;
+		
+		let mut target = target.splitn(2, '?');
+		let addr = parse_hex_u16(target.next().unwrap()).ok_or(format!("invalid address in `{}`", code))?;
+		let compare = match target.next()
+		{
+			Some(compare) => Some(parse_hex_u8(compare).ok_or(format!("invalid compare value in `{}`", code))?),
+			None => None
+		};
+		
+		Ok(Cheat::new(get_cheat_kind(addr, false), addr, value, compare))
+	}
+	
+	
+	pub fn from_game_genie(code: &str) -> Result<Cheat, String>
+	{
+		let mut n = Vec::with_capacity(8);
+		for c in code.bytes()
+		{
+			match GAME_GENIE_LETTERS.iter().position(|&letter| letter == c.to_ascii_uppercase())
+			{
+				Some(nibble) => n.push(nibble as u16),
+				None => return Err(format!("invalid Game Genie letter `{}`", c as char))
+			}
+		}
+		
+		if n.len() != 6 && n.len() != 8
+			{ return Err(format!("Game Genie codes have 6 or 8 letters, not {}", n.len())); }
+			
+		let addr = 0x8000 |
+			((n[3] & 7) << 12) | ((n[5] & 7) << 8) | ((n[4] & 8) << 8) |
+			((n[2] & 7) << 4) | ((n[1] & 8) << 4) | (n[4] & 7) | (n[3] & 8);
+			
+		let last = n[n.len() - 1];
+		let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7) | (last & 8);
+		
+		let compare = if n.len() == 8
+			{ Some((((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8)) as u8) }
+		else
+			{ None };
+			
+		let mut cheat = Cheat::new(CheatKind::Substitute, addr, value as u8, compare);
+		cheat.name = code.to_ascii_uppercase();
+		Ok(cheat)
+	}
+	
+	
+	// FCEUX .cht: "[S][C][:]AAAA:VV[:CC]:Name", where S substitutes reads, C adds a compare
+	// value and a leading colon marks the cheat as disabled
+	pub fn parse_cht(text: &str) -> Result<Vec<Cheat>, String>
+	{
+		let mut cheats = Vec::new();
+		
+		for (line_index, line) in text.lines().enumerate()
+		{
+			let mut line = line.trim();
+			if line.is_empty()
+				{ continue; }
+				
+			let error = || format!("line {}: invalid cheat", line_index + 1);
+			
+			let substitute = line.starts_with('S');
+			if substitute
+				{ line = &line[1..]; }
+				
+			let has_compare = line.starts_with('C');
+			if has_compare
+				{ line = &line[1..]; }
+				
+			let enabled = !line.starts_with(':');
+			if !enabled
+				{ line = &line[1..]; }
+				
+			let mut fields = line.splitn(if has_compare { 4 } else { 3 }, ':');
+			
+			let addr = fields.next().and_then(parse_hex_u16).ok_or_else(error)?;
+			let value = fields.next().and_then(parse_hex_u8).ok_or_else(error)?;
+			let compare = if has_compare { Some(fields.next().and_then(parse_hex_u8).ok_or_else(error)?) } else { None };
+			
+			let mut cheat = Cheat::new(get_cheat_kind(addr, substitute), addr, value, compare);
+			cheat.enabled = enabled;
+			cheat.name = fields.next().unwrap_or("").to_string();
+			cheats.push(cheat);
+		}
+		
+		Ok(cheats)
+	}
+	
+	
+	pub fn to_cht(cheats: &[Cheat]) -> String
+	{
+		let mut text = String::new();
+		
+		for cheat in cheats
+		{
+			if cheat.kind == CheatKind::Substitute
+				{ text.push('S'); }
+				
+			if cheat.compare.is_some()
+				{ text.push('C'); }
+				
+			if !cheat.enabled
+				{ text.push(':'); }
+				
+			text.push_str(&format!("{:04X}:{:02X}", cheat.addr, cheat.value));
+			
+			if let Some(compare) = cheat.compare
+				{ text.push_str(&format!(":{:02X}", compare)); }
+				
+			text.push_str(&format!(":{}\n", cheat.name));
+		}
+		
+		text
+	}
+}
+
+
+impl Core
+{
+	pub fn add_cheat(&mut self, code: &str) -> Result<usize, String>
+	{
+		self.cheats.push(Cheat::parse(code)?);
+		Ok(self.cheats.len() - 1)
+	}
+	
+	
+	pub fn load_cheats(&mut self, text: &str) -> Result<usize, String>
+	{
+		let cheats = Cheat::parse_cht(text)?;
+		let count = cheats.len();
+		
+		self.cheats.extend(cheats);
+		Ok(count)
+	}
+	
+	
+	pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool)
+	{
+		if let Some(cheat) = self.cheats.get_mut(index)
+			{ cheat.enabled = enabled; }
+	}
+}
+
+
+// ROM can only be patched on the way to the CPU, and only reads from the cartridge can be
+// substituted, so anything else is frozen instead
+fn get_cheat_kind(addr: u16, substitute: bool) -> CheatKind
+{
+	if addr >= 0x8000 || (substitute && addr >= 0x4020)
+		{ CheatKind::Substitute }
+	else
+		{ CheatKind::Freeze }
+}
+
+
+fn parse_hex_u16(text: &str) -> Option<u16>
+{
+	u16::from_str_radix(text.trim().trim_start_matches('$'), 16).ok()
+}
+
+
+fn parse_hex_u8(text: &str) -> Option<u8>
+{
+	u8::from_str_radix(text.trim().trim_start_matches('$'), 16).ok()
+}
\ No newline at end of file
@@ -1,6 +1,34 @@
 use Cpu;
 use Ppu;
 use Cartridge;
+use Palette;
+use NtscFilter;
+use PaletteRam;
+use Debugger;
+use TraceLogger;
+use CodeDataLogger;
+use SymbolTable;
+use Movie;
+use Cheat;
+use cheats::CheatKind;
+use movie::{MOVIE_SOFT_RESET, MOVIE_POWER};
+use cdl::{CDL_CHR_RENDERED, CDL_CHR_READ};
+use cpu::{CpuEvent, CpuState};
+use png;
+use debugger::{AddressSpace, StopReason};
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PowerOnRam
+{
+	Zero,
+	Ones,
+	
+	// Alternating runs of four $00 and four $FF bytes, as many consoles come up
+	Pattern,
+	
+	Random(u32)
+}
 
 
 pub struct Core
@@ -14,13 +42,30 @@ pub struct Core
 	pub controller_shiftreg: u8,
 	pub controller_strobe: u8,
 	
+	pub cpu_open_bus: u8,
+	
 	pub ram: [u8; 0x800],
 	pub vram: [u8; 0x800],
-	pub palram: [u8; 0x20],
+	pub palram: PaletteRam,
+	pub power_on_ram: PowerOnRam,
 	
 	pub screen: [u8; 256 * 240 * 4],
+	pub palette: Palette,
+	pub ntsc_filter: Option<NtscFilter>,
 	
-	pub controller1: u8
+	pub debugger: Option<Box<Debugger>>,
+	pub trace_logger: Option<Box<TraceLogger>>,
+	pub cdl: Option<Box<CodeDataLogger>>,
+	pub symbols: SymbolTable,
+	pub movie: Option<Box<Movie>>,
+	pub cheats: Vec<Cheat>,
+	
+	pub controller1: u8,
+	
+	// Reads of $4016 and $4017 during the frame in progress and the last complete one
+	pub input_polls: [usize; 2],
+	pub last_input_polls: [usize; 2],
+	pub lag_frames: usize
 }
 
 
@@ -39,13 +84,29 @@ impl Core
 			controller_shiftreg: 0,
 			controller_strobe: 0,
 			
+			cpu_open_bus: 0,
+			
 			ram: [0; 0x800],
 			vram: [0; 0x800],
-			palram: [0; 0x20],
+			palram: PaletteRam::new(),
+			power_on_ram: PowerOnRam::Zero,
 			
 			screen: [0; 256 * 240 * 4],
+			palette: Palette::new(),
+			ntsc_filter: None,
 			
-			controller1: 0
+			debugger: None,
+			trace_logger: None,
+			cdl: None,
+			symbols: SymbolTable::new(),
+			movie: None,
+			cheats: Vec::new(),
+			
+			controller1: 0,
+			
+			input_polls: [0; 2],
+			last_input_polls: [0; 2],
+			lag_frames: 0
 		});
 		
 		let core_ptr = (&mut *core) as *mut Core;
@@ -62,11 +123,16 @@ impl Core
 	}
 	
 	
+	// A power cycle: memory comes up in the state chosen by `power_on_ram`
 	pub fn reset(&mut self)
 	{
-		self.ram = [0; 0x800];
-		self.vram = [0; 0x800];
-		self.palram = [0; 0x20];
+		if let Some(ref mut movie) = self.movie
+			{ movie.record_command(MOVIE_POWER); }
+			
+		fill_power_on_ram(&mut self.ram, self.power_on_ram);
+		fill_power_on_ram(&mut self.vram, self.power_on_ram);
+		self.palram.reset();
+		self.cpu_open_bus = 0;
 		
 		self.cpu.reset();
 		self.ppu.reset();
@@ -75,65 +141,376 @@ impl Core
 	}
 	
 	
-	pub fn run(&mut self)
+	// The reset button restarts the CPU and part of the PPU, but leaves memory alone
+	pub fn soft_reset(&mut self)
+	{
+		if let Some(ref mut movie) = self.movie
+			{ movie.record_command(MOVIE_SOFT_RESET); }
+			
+		self.cpu.soft_reset();
+		self.ppu.soft_reset();
+	}
+	
+	
+	pub fn set_palette(&mut self, buffer: &[u8]) -> bool
+	{
+		match Palette::from_pal(buffer)
+		{
+			Some(palette) =>
+			{
+				self.palette = palette;
+				true
+			}
+			
+			None => false
+		}
+	}
+	
+	
+	pub fn set_ntsc_filter(&mut self, enabled: bool)
+	{
+		if !enabled
+			{ self.ntsc_filter = None; }
+		else if self.ntsc_filter.is_none()
+			{ self.ntsc_filter = Some(NtscFilter::new()); }
+	}
+	
+	
+	pub fn set_cdl(&mut self, enabled: bool)
+	{
+		if !enabled
+			{ self.cdl = None; }
+		else if self.cdl.is_none()
+			{ self.cdl = Some(Box::new(CodeDataLogger::new(self.cartridge.get_prg_rom_size(), self.cartridge.get_chr_rom_size()))); }
+	}
+	
+	
+	pub fn get_screen_hash(&self) -> u32
+	{
+		png::crc32(&self.screen)
+	}
+	
+	
+	pub fn peek_cpu(&self, addr: u16) -> u8
+	{
+		let open_bus = self.cpu_open_bus;
+		
+		if addr < 0x2000
+			{ self.ram[(addr & 0x7ff) as usize] }
+		else if addr < 0x4000
+			{ self.ppu.peek_reg(addr) }
+		else if addr == 0x4015
+			{ open_bus & 0x20 }
+		else if addr == 0x4016
+			{ (self.controller_shiftreg & 1) | (open_bus & 0xe0) }
+		else if addr == 0x4017
+			{ open_bus & 0xe0 }
+		else if addr < 0x4020
+			{ open_bus }
+		else
+			{ self.cartridge.cpu_peek(addr).unwrap_or(open_bus) }
+	}
+	
+	
+	pub fn peek_ppu(&self, addr: u16) -> u8
+	{
+		let addr = addr & 0x3fff;
+		
+		if addr < 0x2000
+			{ self.cartridge.ppu_peek(addr) }
+		else if addr < 0x3f00
+			{ self.vram[self.get_vram_index(addr)] }
+		else
+			{ self.palram.read(addr) }
+	}
+	
+	
+	// Registers are left alone, since writing them always has side effects
+	pub fn poke_cpu(&mut self, addr: u16, val: u8)
 	{
+		if addr < 0x2000
+			{ self.ram[(addr & 0x7ff) as usize] = val; }
+		else if addr >= 0x4020
+			{ self.cartridge.cpu_poke(addr, val); }
+	}
+	
+	
+	pub fn poke_ppu(&mut self, addr: u16, val: u8)
+	{
+		let addr = addr & 0x3fff;
+		
+		if addr < 0x2000
+			{ self.cartridge.ppu_poke(addr, val); }
+		else if addr < 0x3f00
+		{
+			let index = self.get_vram_index(addr);
+			self.vram[index] = val;
+		}
+		else
+			{ self.palram.write(addr, val); }
+	}
+	
+	
+	// A playing movie replaces the live input right where the game latches it
+	fn get_controller1_input(&self) -> u8
+	{
+		self.movie.as_ref().and_then(|movie| movie.get_input()).unwrap_or(self.controller1)
+	}
+	
+	
+	// Movie resets happen on frame boundaries, before anything else runs
+	fn apply_movie_commands(&mut self)
+	{
+		let commands = match self.movie
+		{
+			Some(ref mut movie) => movie.take_commands(),
+			None => return
+		};
+		
+		if (commands & MOVIE_POWER) != 0
+			{ self.reset(); }
+		else if (commands & MOVIE_SOFT_RESET) != 0
+			{ self.soft_reset(); }
+	}
+	
+	
+	fn get_vram_index(&self, addr: u16) -> usize
+	{
+		let mirror = if self.cartridge.ppu_ciram_mirror(addr | 0x8000) { 0x400 } else { 0 };
+		(((addr & 0x3ff) | mirror) & 0x7ff) as usize
+	}
+	
+	
+	pub fn run(&mut self) -> Option<StopReason>
+	{
+		self.apply_movie_commands();
+		let frame = self.ppu.get_frame();
+		
 		self.cpu.clock();
-		self.ppu.clock();
-		self.ppu.clock();
-		self.ppu.clock();
+		self.log_instruction();
+		
+		let mut stop = self.check_debugger(Debugger::check_cpu);
+		
+		for _ in 0..3
+		{
+			self.ppu.clock();
+			let ppu_stop = self.check_debugger(Debugger::check_ppu);
+			stop = stop.or(ppu_stop);
+		}
 		
 		self.clocks = self.clocks.wrapping_add(3);
+		
+		if self.ppu.get_frame() != frame
+			{ self.end_frame(); }
+			
+		stop
+	}
+	
+	
+	// Runs until the next opcode fetch, keeping the first stop reason seen on the way
+	pub fn step_instruction(&mut self) -> Option<StopReason>
+	{
+		let mut stop = None;
+		
+		loop
+		{
+			let reason = self.run();
+			stop = stop.or(reason);
+			
+			if let CpuEvent::FetchOpcode(_) = self.cpu.last_event
+				{ return stop; }
+		}
+	}
+	
+	
+	pub fn get_cpu_state(&self) -> CpuState
+	{
+		self.cpu.get_state(&|addr| self.peek_cpu(addr))
+	}
+	
+	
+	// A frame in which the game never looked at the controllers is a lag frame
+	pub fn is_lag_frame(&self) -> bool
+	{
+		self.last_input_polls == [0; 2]
+	}
+	
+	
+	pub fn reset_lag_count(&mut self)
+	{
+		self.lag_frames = 0;
+	}
+	
+	
+	fn end_frame(&mut self)
+	{
+		self.last_input_polls = self.input_polls;
+		self.input_polls = [0; 2];
+		
+		if self.is_lag_frame()
+			{ self.lag_frames += 1; }
+			
+		if let Some(ref mut movie) = self.movie
+			{ movie.end_frame(self.controller1); }
+			
+		self.apply_cheat_freezes();
+	}
+	
+	
+	fn apply_cheat_reads(&self, addr: u16, val: u8) -> u8
+	{
+		self.cheats.iter()
+			.filter(|cheat| cheat.enabled && cheat.kind == CheatKind::Substitute && cheat.addr == addr)
+			.find(|cheat| cheat.compare.map_or(true, |compare| compare == val))
+			.map_or(val, |cheat| cheat.value)
+	}
+	
+	
+	fn apply_cheat_freezes(&mut self)
+	{
+		let writes: Vec<(u16, u8)> = self.cheats.iter()
+			.filter(|cheat| cheat.enabled && cheat.kind == CheatKind::Freeze && cheat.addr < 0x8000)
+			.filter(|cheat| cheat.compare.map_or(true, |compare| compare == self.peek_cpu(cheat.addr)))
+			.map(|cheat| (cheat.addr, cheat.value))
+			.collect();
+			
+		for (addr, value) in writes
+			{ self.poke_cpu(addr, value); }
+	}
+	
+	
+	pub fn run_frame(&mut self) -> Option<StopReason>
+	{
+		self.apply_movie_commands();
+		let frame = self.ppu.get_frame();
+		
+		while self.ppu.get_frame() == frame
+		{
+			if let Some(stop) = self.run()
+				{ return Some(stop); }
+		}
+		
+		None
+	}
+	
+	
+	fn log_instruction(&mut self)
+	{
+		if let CpuEvent::FetchOpcode(addr) = self.cpu.last_event
+		{
+			if let Some(mut trace_logger) = self.trace_logger.take()
+			{
+				trace_logger.log(self, addr);
+				self.trace_logger = Some(trace_logger);
+			}
+			
+			if let Some(mut cdl) = self.cdl.take()
+			{
+				cdl.log_instruction(self, addr);
+				self.cdl = Some(cdl);
+			}
+		}
+	}
+	
+	
+	fn check_debugger(&mut self, check: fn(&mut Debugger, &Core) -> Option<StopReason>) -> Option<StopReason>
+	{
+		let mut debugger = self.debugger.take()?;
+		let stop = check(&mut debugger, self);
+		self.debugger = Some(debugger);
+		stop
+	}
+	
+	
+	unsafe fn record_access(core: *mut Core, space: AddressSpace, addr: u16, val: u8, write: bool)
+	{
+		if let Some(ref mut debugger) = (*core).debugger
+			{ debugger.record_access(space, addr, val, write); }
 	}
 	
 	
 	unsafe fn cpu_read(core: *mut Core, addr: u16) -> u8
 	{
 		let cartridge_read = (*core).cartridge.cpu_read(addr);
+		let open_bus = (*core).cpu_open_bus;
 		
-		if addr < 0x2000
+		let val = if addr < 0x2000
 		{
 			(*core).ram[(addr & 0x7ff) as usize]
 		}
 		
-		else if addr < 0x3000
+		else if addr < 0x4000
 		{
 			match addr % 8
 			{
 				2 => (*core).ppu.read_reg_status(),
 				4 => (*core).ppu.read_reg_oamdata(),
-				7 => (*core).ppu.read_reg_data(),
-				_ => 0
+				7 =>
+				{
+					Core::log_chr(core, (*core).ppu.get_scroll_v() & 0x3fff, CDL_CHR_READ);
+					(*core).ppu.read_reg_data()
+				}
+				
+				_ => (*core).ppu.read_reg_open_bus()
 			}
 		}
 		
+		else if addr == 0x4015
+		{
+			// Internal to the CPU, so the external bus keeps its value
+			let val = open_bus & 0x20;
+			Core::record_access(core, AddressSpace::Cpu, addr, val, false);
+			return val;
+		}
+		
 		else if addr == 0x4016
 		{
+			(*core).input_polls[0] += 1;
+			
 			let bit = (*core).controller_shiftreg & 1;
 			(*core).controller_shiftreg >>= 1;
 			(*core).controller_shiftreg |= 0x80;
-			bit
+			bit | (open_bus & 0xe0)
 		}
 		
+		else if addr == 0x4017
+		{
+			(*core).input_polls[1] += 1;
+			open_bus & 0xe0
+		}
+		
+		else if addr < 0x4020
+			{ open_bus }
+		
 		else
-			{ cartridge_read }
+			{ (*core).apply_cheat_reads(addr, cartridge_read.unwrap_or(open_bus)) };
+			
+		(*core).cpu_open_bus = val;
+		
+		Core::record_access(core, AddressSpace::Cpu, addr, val, false);
+		val
 	}
 	
 	
 	unsafe fn cpu_write(core: *mut Core, addr: u16, val: u8)
 	{
 		(*core).cartridge.cpu_write(addr, val);
+		(*core).cpu_open_bus = val;
+		
+		Core::record_access(core, AddressSpace::Cpu, addr, val, true);
 		
 		if addr < 0x2000
 		{
 			(*core).ram[(addr & 0x7ff) as usize] = val;
 		}
 		
-		else if addr < 0x3000
+		else if addr < 0x4000
 		{
 			match addr % 8
 			{
 				0 => (*core).ppu.write_reg_ctrl(val),
 				1 => (*core).ppu.write_reg_mask(val),
+				2 => (*core).ppu.write_reg_status(val),
 				3 => (*core).ppu.write_reg_oamaddr(val),
 				4 => (*core).ppu.write_reg_oamdata(val),
 				5 => (*core).ppu.write_reg_scroll(val),
@@ -157,7 +534,7 @@ impl Core
 			if (val & 1) != 0
 			{
 				(*core).controller_strobe = 0;
-				(*core).controller_shiftreg = (*core).controller1;
+				(*core).controller_shiftreg = (*core).get_controller1_input();
 			}
 			else
 				{ (*core).controller_strobe = 1; }
@@ -165,130 +542,92 @@ impl Core
 	}
 	
 	
+	unsafe fn log_chr(core: *mut Core, addr: u16, flags: u8)
+	{
+		if let Some(ref mut cdl) = (*core).cdl
+		{
+			if let Some(offset) = (*core).cartridge.ppu_addr_to_chr_offset(addr)
+				{ cdl.log_chr(offset, flags); }
+		}
+	}
+	
+	
 	unsafe fn ppu_read(core: *mut Core, addr: u16) -> u8
 	{
+		if (*core).ppu.is_rendering()
+			{ Core::log_chr(core, addr, CDL_CHR_RENDERED); }
+			
 		let cartridge_read = (*core).cartridge.ppu_read(addr);
 		
-		if addr < 0x2000
+		let val = if addr < 0x2000
 			{ cartridge_read }
 		
 		else if addr < 0x3000
-		{
-			let mirror = if (*core).cartridge.ppu_ciram_mirror(addr | 0x8000) { 0x400 } else { 0 };
-			(*core).vram[(((addr & 0x3ff) | mirror) & 0x7ff) as usize]
-		}
+			{ (*core).vram[(*core).get_vram_index(addr)] }
 		
 		else if addr >= 0x3f00 && addr < 0x4000
-			{ (*core).palram[((addr - 0x3f00) & 0x1f) as usize] }
+			{ (*core).palram.read(addr) }
 		
 		else
-			{ 0 }
+			{ 0 };
+			
+		Core::record_access(core, AddressSpace::Ppu, addr, val, false);
+		val
 	}
 	
 	
 	unsafe fn ppu_write(core: *mut Core, addr: u16, val: u8)
 	{
 		(*core).cartridge.ppu_write(addr, val);
+		Core::record_access(core, AddressSpace::Ppu, addr, val, true);
 		
 		if addr >= 0x2000 && addr < 0x3000
 		{
-			let mirror = if (*core).cartridge.ppu_ciram_mirror(addr | 0x8000) { 0x400 } else { 0 };
-			(*core).vram[(((addr & 0x3ff) | mirror) & 0x7ff) as usize] = val;
+			let index = (*core).get_vram_index(addr);
+			(*core).vram[index] = val;
 		}
 		
 		else if addr >= 0x3f00 && addr < 0x4000
-		{
-			if addr & 0xf == 0
-			{
-				for i in 0..8
-					{ (*core).palram[i * 4] = val; }
-			}
-			else
-				{ (*core).palram[((addr - 0x3f00) & 0x1f) as usize] = val; }
-		}
+			{ (*core).palram.write(addr, val); }
 	}
 	
 	
-	unsafe fn ppu_output_dot(core: *mut Core, scanline: usize, dot: usize, color: u8, _mask: u8)
+	unsafe fn ppu_output_dot(core: *mut Core, scanline: usize, dot: usize, color: u8, mask: u8)
 	{
 		let screen_addr = (scanline * 256 + dot) * 4;
-		let palette_addr = color as usize * 4;
+		let rgba = (*core).palette.get_rgba(color, mask);
+		
+		(*core).screen[screen_addr + 0] = rgba[0];
+		(*core).screen[screen_addr + 1] = rgba[1];
+		(*core).screen[screen_addr + 2] = rgba[2];
+		(*core).screen[screen_addr + 3] = rgba[3];
 		
-		(*core).screen[screen_addr + 0] = PALETTE_DEFAULT[palette_addr + 0];
-		(*core).screen[screen_addr + 1] = PALETTE_DEFAULT[palette_addr + 1];
-		(*core).screen[screen_addr + 2] = PALETTE_DEFAULT[palette_addr + 2];
-		(*core).screen[screen_addr + 3] = PALETTE_DEFAULT[palette_addr + 3];
+		if let Some(ref mut ntsc_filter) = (*core).ntsc_filter
+			{ ntsc_filter.output_dot(scanline, dot, color, mask); }
 	}
 }
 
 
-static PALETTE_DEFAULT: [u8; 64 * 4] =
-[
-	0x75, 0x75, 0x75, 0xff,
-	0x27, 0x1b, 0x8f, 0xff,
-	0x00, 0x00, 0xab, 0xff,
-	0x47, 0x00, 0x9f, 0xff,
-	0x8f, 0x00, 0x77, 0xff,
-	0xab, 0x00, 0x13, 0xff,
-	0xa7, 0x00, 0x00, 0xff,
-	0x7f, 0x0b, 0x00, 0xff,
-	0x43, 0x2f, 0x00, 0xff,
-	0x00, 0x47, 0x00, 0xff,
-	0x00, 0x51, 0x00, 0xff,
-	0x00, 0x3f, 0x17, 0xff,
-	0x1b, 0x3f, 0x5f, 0xff,
-	0x00, 0x00, 0x00, 0xff,
-	0x00, 0x00, 0x00, 0xff,
-	0x00, 0x00, 0x00, 0xff,
-
-	0xbc, 0xbc, 0xbc, 0xff,
-	0x00, 0x73, 0xef, 0xff,
-	0x23, 0x3b, 0xef, 0xff,
-	0x83, 0x00, 0xf3, 0xff,
-	0xbf, 0x00, 0xbf, 0xff,
-	0xe7, 0x00, 0x5b, 0xff,
-	0xdb, 0x2b, 0x00, 0xff,
-	0xcb, 0x4f, 0x0f, 0xff,
-	0x8b, 0x73, 0x00, 0xff,
-	0x00, 0x97, 0x00, 0xff,
-	0x00, 0xab, 0x00, 0xff,
-	0x00, 0x93, 0x3b, 0xff,
-	0x00, 0x83, 0x8b, 0xff,
-	0x00, 0x00, 0x00, 0xff,
-	0x00, 0x00, 0x00, 0xff,
-	0x00, 0x00, 0x00, 0xff,
-
-	0xff, 0xff, 0xff, 0xff,
-	0x3f, 0xbf, 0xff, 0xff,
-	0x5f, 0x97, 0xff, 0xff,
-	0xa7, 0x8b, 0xfd, 0xff,
-	0xf7, 0x7b, 0xff, 0xff,
-	0xff, 0x77, 0xb7, 0xff,
-	0xff, 0x77, 0x63, 0xff,
-	0xff, 0x9b, 0x3b, 0xff,
-	0xf3, 0xbf, 0x3f, 0xff,
-	0x83, 0xd3, 0x13, 0xff,
-	0x4f, 0xdf, 0x4b, 0xff,
-	0x58, 0xf8, 0x98, 0xff,
-	0x00, 0xeb, 0xdb, 0xff,
-	0x00, 0x00, 0x00, 0xff,
-	0x00, 0x00, 0x00, 0xff,
-	0x00, 0x00, 0x00, 0xff,
-
-	0xff, 0xff, 0xff, 0xff,
-	0xab, 0xe7, 0xff, 0xff,
-	0xc7, 0xd7, 0xff, 0xff,
-	0xd7, 0xcb, 0xff, 0xff,
-	0xff, 0xc7, 0xff, 0xff,
-	0xff, 0xc7, 0xdb, 0xff,
-	0xff, 0xbf, 0xb3, 0xff,
-	0xff, 0xdb, 0xab, 0xff,
-	0xff, 0xe7, 0xa3, 0xff,
-	0xe3, 0xff, 0xa3, 0xff,
-	0xab, 0xf3, 0xbf, 0xff,
-	0xb3, 0xff, 0xcf, 0xff,
-	0x9f, 0xff, 0xf3, 0xff,
-	0x00, 0x00, 0x00, 0xff,
-	0x00, 0x00, 0x00, 0xff,
-	0x00, 0x00, 0x00, 0xff,
-];
\ No newline at end of file
+fn fill_power_on_ram(ram: &mut [u8], policy: PowerOnRam)
+{
+	match policy
+	{
+		PowerOnRam::Zero => ram.iter_mut().for_each(|byte| *byte = 0x00),
+		PowerOnRam::Ones => ram.iter_mut().for_each(|byte| *byte = 0xff),
+		PowerOnRam::Pattern => ram.iter_mut().enumerate().for_each(|(i, byte)| *byte = if (i & 4) != 0 { 0xff } else { 0x00 }),
+		
+		PowerOnRam::Random(seed) =>
+		{
+			// xorshift32, which must not start from zero
+			let mut state = if seed == 0 { 0x2545f491 } else { seed };
+			
+			for byte in ram.iter_mut()
+			{
+				state ^= state << 13;
+				state ^= state >> 17;
+				state ^= state << 5;
+				*byte = state as u8;
+			}
+		}
+	}
+}
\ No newline at end of file
@@ -1,9 +1,48 @@
 use cpu_opcodes;
+use cpu_dis;
 
 
 type CpuReadFn = Fn(u16) -> u8;
 type CpuWriteFn = Fn(u16, u8);
 type CpuExecuteInstrFn = Fn(&Cpu, u16, u8, u8, u8);
+type CpuOpcodeSteps = [fn(&mut Cpu); 8];
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CpuEvent
+{
+	None,
+	FetchOpcode(u16),
+	Nmi,
+	Irq
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CpuVariant
+{
+	// The NES CPU, which keeps the D flag but has no decimal mode
+	Ricoh2A03,
+	Nmos6502,
+	Wdc65C02
+}
+
+
+// Registers as of the start of the current instruction, plus the instruction itself
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CpuState
+{
+	pub pc: u16,
+	pub a: u8,
+	pub x: u8,
+	pub y: u8,
+	pub s: u8,
+	pub p: u8,
+	pub cycles: usize,
+	
+	pub instruction: [u8; 3],
+	pub instruction_len: usize
+}
 
 
 const FLAG_C : u8 = 0b00000001; // Carry
@@ -15,16 +54,32 @@ const FLAG_U : u8 = 0b00100000; // Unused
 const FLAG_V : u8 = 0b01000000; // Overflow
 const FLAG_N : u8 = 0b10000000; // Negative
 
+const VECTOR_NMI : u16 = 0xfffa;
+const VECTOR_IRQ : u16 = 0xfffe;
+
 
 pub struct Cpu
 {
+	pub variant: CpuVariant,
+	
 	pub clocks: usize,
 	
 	signal_nmi: bool,
 	acknowledge_nmi: bool,
 	
+	// IRQ is level-sensitive, so only the line itself is kept
 	signal_irq: bool,
-	acknowledge_irq: bool,
+	
+	// Interrupts as seen at the end of the last cycle and the one before
+	poll_nmi: bool,
+	poll_prev_nmi: bool,
+	poll_irq: bool,
+	poll_prev_irq: bool,
+	
+	interrupt_vector: u16,
+	
+	// The 65C02 spends an extra cycle fixing up decimal ADC and SBC
+	decimal_cycle: bool,
 	
 	pub opcode: u8,
 	pub opcode_step: u8,
@@ -33,6 +88,8 @@ pub struct Cpu
 	pub routine_nmi: bool,
 	pub routine_irq: bool,
 	
+	pub last_event: CpuEvent,
+	
 	pub reg_pc: u16,
 	pub reg_a: u8,
 	pub reg_x: u8,
@@ -55,13 +112,23 @@ impl Cpu
 	{
 		Cpu
 		{
+			variant: CpuVariant::Ricoh2A03,
+			
 			clocks: 0,
 			
 			signal_nmi: false,
 			acknowledge_nmi: false,
 			
 			signal_irq: false,
-			acknowledge_irq: false,
+			
+			poll_nmi: false,
+			poll_prev_nmi: false,
+			poll_irq: false,
+			poll_prev_irq: false,
+			
+			interrupt_vector: VECTOR_IRQ,
+			
+			decimal_cycle: false,
 			
 			opcode: 0,
 			opcode_step: 0,
@@ -70,6 +137,8 @@ impl Cpu
 			routine_nmi: false,
 			routine_irq: false,
 			
+			last_event: CpuEvent::None,
+			
 			reg_pc: 0,
 			reg_a: 0,
 			reg_x: 0,
@@ -91,6 +160,14 @@ impl Cpu
 	{
 		self.clocks = 0;
 		
+		self.acknowledge_nmi = false;
+		self.poll_nmi = false;
+		self.poll_prev_nmi = false;
+		self.poll_irq = false;
+		self.poll_prev_irq = false;
+		
+		self.decimal_cycle = false;
+		
 		self.opcode = 0;
 		self.opcode_step = 0;
 		
@@ -98,11 +175,14 @@ impl Cpu
 		self.routine_nmi = false;
 		self.routine_irq = false;
 		
+		self.last_event = CpuEvent::None;
+		
+		// The reset sequence takes S down to $FD
 		self.reg_pc = 0;
 		self.reg_a = 0;
 		self.reg_x = 0;
 		self.reg_y = 0;
-		self.reg_s = 0xfd;
+		self.reg_s = 0x00;
 		self.reg_p = 0x24;
 		
 		self.internal_addr = 0;
@@ -110,8 +190,29 @@ impl Cpu
 	}
 	
 	
+	// The reset line restarts the sequence but leaves registers as they are
+	pub fn soft_reset(&mut self)
+	{
+		self.opcode_step = 0;
+		self.decimal_cycle = false;
+		
+		self.routine_reset = true;
+		self.routine_nmi = false;
+		self.routine_irq = false;
+		
+		self.last_event = CpuEvent::None;
+	}
+	
+	
+	// Skipping a pending reset sequence still applies what it does to the registers
 	pub fn set_pc(&mut self, addr: u16)
 	{
+		if self.routine_reset
+		{
+			self.reg_s = self.reg_s.wrapping_sub(3);
+			self.set_interrupt_flags();
+		}
+		
 		self.reg_pc = addr;
 		self.routine_reset = false;
 	}
@@ -128,25 +229,29 @@ impl Cpu
 	
 	pub fn drive_irq(&mut self, active: bool)
 	{
-		if !self.signal_irq && active
-			{ self.acknowledge_irq = true; }
-			
 		self.signal_irq = active;
 	}
 	
 	
 	pub fn clock(&mut self)
 	{
+		// Lines driven since the last clock count as the end of the previous cycle
+		self.poll_prev_nmi = self.poll_nmi;
+		self.poll_nmi = self.acknowledge_nmi;
+		self.poll_prev_irq = self.poll_irq;
+		self.poll_irq = self.signal_irq && (self.reg_p & FLAG_I) == 0;
+		
 		self.opcode_step += 1;
+		self.last_event = CpuEvent::None;
 		
 		if self.routine_reset
 			{ self.run_reset_routine(); }
 			
-		else if self.routine_nmi
-			{ self.run_nmi_routine(); }
-		
-		else if self.routine_irq
-			{ self.run_irq_routine(); }
+		else if self.routine_nmi || self.routine_irq
+			{ self.run_interrupt_routine(); }
+			
+		else if self.decimal_cycle
+			{ self.run_decimal_cycle(); }
 			
 		else
 			{ self.dispatch_opcode(); }
@@ -155,11 +260,67 @@ impl Cpu
 	}
 	
 	
+	// Clocks until the next opcode fetch and returns how many cycles it took
+	pub fn step_instruction(&mut self) -> usize
+	{
+		let clocks = self.clocks;
+		
+		loop
+		{
+			self.clock();
+			
+			if let CpuEvent::FetchOpcode(_) = self.last_event
+				{ return self.clocks - clocks; }
+		}
+	}
+	
+	
+	// Right after a fetch PC has already moved past the opcode
+	pub fn get_instruction_addr(&self) -> u16
+	{
+		match self.last_event
+		{
+			CpuEvent::FetchOpcode(addr) => addr,
+			_ => self.reg_pc
+		}
+	}
+	
+	
+	pub fn get_state(&self, peek: &Fn(u16) -> u8) -> CpuState
+	{
+		let pc = self.get_instruction_addr();
+		let instruction = [peek(pc), peek(pc.wrapping_add(1)), peek(pc.wrapping_add(2))];
+		let instruction_len = cpu_dis::decode_opcode_variant(instruction[0], self.variant).map_or(1, |(_, mode)| mode.get_length());
+		
+		CpuState
+		{
+			pc,
+			a: self.reg_a,
+			x: self.reg_x,
+			y: self.reg_y,
+			s: self.reg_s,
+			p: self.reg_p,
+			cycles: self.clocks,
+			
+			instruction,
+			instruction_len
+		}
+	}
+	
+	
 	fn run_reset_routine(&mut self)
 	{
 		match self.opcode_step
 		{
-			1 | 2 | 3 | 4 => { }
+			1 => { (self.hook_read)(self.reg_pc); }
+			
+			// The pushes of an interrupt, with writes suppressed
+			2 | 3 | 4 =>
+			{
+				(self.hook_read)(0x100 + (self.reg_s as u16));
+				self.reg_s = self.reg_s.wrapping_sub(1);
+				self.set_interrupt_flags();
+			}
 			
 			5 => self.reg_pc = (self.hook_read)(0xfffc) as u16,
 				
@@ -175,30 +336,34 @@ impl Cpu
 	}
 	
 	
-	fn run_nmi_routine(&mut self)
+	// Step 1 is the opcode fetch that noticed the interrupt
+	fn run_interrupt_routine(&mut self)
 	{
 		let reg_pc = self.reg_pc;
 		let reg_p = self.reg_p;
 		
 		match self.opcode_step
 		{
-			1 => { }
+			2 => { (self.hook_read)(reg_pc); } // Dummy read
 				
-			2 => self.push_stack((reg_pc >> 8) as u8),
+			3 => self.push_stack((reg_pc >> 8) as u8),
 				
-			3 => self.push_stack((reg_pc & 0xff) as u8),
-			
-			4 => self.push_stack(reg_p),
+			4 => self.push_stack((reg_pc & 0xff) as u8),
 			
-			5 => { }
+			5 =>
+			{
+				self.push_stack((reg_p & !FLAG_B) | FLAG_U);
+				self.select_interrupt_vector(if self.routine_nmi { VECTOR_NMI } else { VECTOR_IRQ });
+			}
 			
-			6 => self.reg_pc = (self.hook_read)(0xfffa) as u16,
+			6 => self.reg_pc = (self.hook_read)(self.interrupt_vector) as u16,
 				
 			7 =>
 			{
-				self.reg_pc |= ((self.hook_read)(0xfffb) as u16) << 8;
+				self.reg_pc |= ((self.hook_read)(self.interrupt_vector + 1) as u16) << 8;
 				self.routine_nmi = false;
-				self.end_opcode();
+				self.routine_irq = false;
+				self.end_interrupt();
 			}
 			
 			_ => unreachable!()
@@ -206,34 +371,45 @@ impl Cpu
 	}
 	
 	
-	fn run_irq_routine(&mut self)
+	// An NMI showing up while an IRQ or BRK is pushing the return address takes over its vector
+	fn select_interrupt_vector(&mut self, vector: u16)
 	{
-		let reg_pc = self.reg_pc;
-		let reg_p = self.reg_p;
-		
-		match self.opcode_step
+		if vector != VECTOR_NMI && self.poll_nmi
 		{
-			1 => self.reg_p &= !FLAG_I,
-				
-			2 => self.push_stack((reg_pc >> 8) as u8),
-				
-			3 => self.push_stack((reg_pc & 0xff) as u8),
-			
-			4 => self.push_stack(reg_p),
-			
-			5 => { }
-			
-			6 => self.reg_pc = (self.hook_read)(0xfffe) as u16,
-				
-			7 =>
-			{
-				self.reg_pc |= ((self.hook_read)(0xffff) as u16) << 8;
-				self.routine_irq = false;
-				self.end_opcode();
-			}
-			
-			_ => unreachable!()
+			self.acknowledge_nmi = false;
+			self.poll_nmi = false;
+			self.interrupt_vector = VECTOR_NMI;
 		}
+		else
+			{ self.interrupt_vector = vector; }
+			
+		self.set_interrupt_flags();
+	}
+	
+	
+	// The 65C02 also leaves decimal mode when taking an interrupt
+	fn set_interrupt_flags(&mut self)
+	{
+		self.reg_p |= FLAG_I;
+		
+		if self.variant == CpuVariant::Wdc65C02
+			{ self.reg_p &= !FLAG_D; }
+	}
+	
+	
+	fn run_decimal_cycle(&mut self)
+	{
+		(self.hook_read)(self.reg_pc); // Dummy read
+		self.decimal_cycle = false;
+		self.end_opcode();
+	}
+	
+	
+	// The handler's first instruction always runs before another NMI is taken
+	fn end_interrupt(&mut self)
+	{
+		self.poll_nmi = false;
+		self.end_opcode();
 	}
 	
 	
@@ -499,7 +675,136 @@ impl Cpu
 			/* 0xff ??? --- */ [Cpu::fetch_op, Cpu::trap,      Cpu::trap,      Cpu::trap,      Cpu::trap,      Cpu::trap,      Cpu::trap,      Cpu::trap,      ], 
 		];
 	
-		OPCODE_TABLE[self.opcode as usize][(self.opcode_step - 1) as usize](self);
+		let step = (self.opcode_step - 1) as usize;
+		
+		if self.variant == CpuVariant::Wdc65C02
+		{
+			if let Some(steps) = Cpu::get_65c02_steps(self.opcode)
+				{ return steps[step](self); }
+		}
+		
+		OPCODE_TABLE[self.opcode as usize][step](self);
+	}
+	
+	
+	// Opcodes that are new or take different steps on the 65C02
+	fn get_65c02_steps(opcode: u8) -> Option<CpuOpcodeSteps>
+	{
+		let steps: CpuOpcodeSteps = match opcode
+		{
+			cpu_opcodes::BRA =>
+				[Cpu::fetch_op, Cpu::read_data, Cpu::exec_rel3, Cpu::exec_rel4, Cpu::exec_rel5, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::JMP_IND =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_abs3, Cpu::dummy_read, Cpu::exec_ind4, Cpu::exec_ind5, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::JMP_IAX =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_abs3, Cpu::exec_iax4, Cpu::exec_ind4, Cpu::exec_ind5, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::ORA_PTR |
+			cpu_opcodes::AND_PTR |
+			cpu_opcodes::EOR_PTR |
+			cpu_opcodes::ADC_PTR |
+			cpu_opcodes::SBC_PTR |
+			cpu_opcodes::CMP_PTR |
+			cpu_opcodes::LDA_PTR =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_pty3, Cpu::exec_pty4, Cpu::exec_rd1, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::STA_PTR =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_pty3, Cpu::exec_pty4, Cpu::exec_wrt1, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::BIT_ZRX =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_zrx3, Cpu::exec_rd1, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::BIT_ABX =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_abs3, Cpu::exec_abx4_r, Cpu::exec_rd1, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::BIT_IMM =>
+				[Cpu::fetch_op, Cpu::exec_imm, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::INC_IMP |
+			cpu_opcodes::DEC_IMP =>
+				[Cpu::fetch_op, Cpu::exec_imp, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::PHX =>
+				[Cpu::fetch_op, Cpu::read_dat2, Cpu::push_x, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::PHY =>
+				[Cpu::fetch_op, Cpu::read_dat2, Cpu::push_y, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::PLX =>
+				[Cpu::fetch_op, Cpu::read_dat2, Cpu::incr_s, Cpu::exec_plx4, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::PLY =>
+				[Cpu::fetch_op, Cpu::read_dat2, Cpu::incr_s, Cpu::exec_ply4, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::STZ_ZER =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_wrt2, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::STZ_ZRX =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_zrx3, Cpu::exec_wrt1, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::STZ_ABS =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_abs3, Cpu::exec_wrt1, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::STZ_ABX =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_abs3, Cpu::exec_abx4, Cpu::exec_wrt1, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::TSB_ZER |
+			cpu_opcodes::TRB_ZER =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_mdf1, Cpu::exec_mdf2, Cpu::exec_zer5, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::TSB_ABS |
+			cpu_opcodes::TRB_ABS =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_abs3, Cpu::exec_mdf1, Cpu::exec_abs5, Cpu::exec_abs6, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::ASL_ABX |
+			cpu_opcodes::LSR_ABX |
+			cpu_opcodes::ROL_ABX |
+			cpu_opcodes::ROR_ABX =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_abs3, Cpu::exec_abx4_m, Cpu::exec_mdf1, Cpu::exec_abx6, Cpu::exec_abx7, Cpu::trap],
+				
+			cpu_opcodes::WAI =>
+				[Cpu::fetch_op, Cpu::read_dat2, Cpu::exec_wai3, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			cpu_opcodes::STP =>
+				[Cpu::fetch_op, Cpu::read_dat2, Cpu::exec_stp3, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			// RMB and SMB
+			_ if (opcode & 0x0f) == 0x07 =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_mdf1, Cpu::exec_mdf2, Cpu::exec_zer5, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			// BBR and BBS
+			_ if (opcode & 0x0f) == 0x0f =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_bbr3, Cpu::exec_bbr4, Cpu::exec_bbr5, Cpu::exec_bbr6, Cpu::exec_rel4, Cpu::exec_rel5],
+				
+			// Unused opcodes are NOPs that still go through their addressing mode
+			0x02 | 0x22 | 0x42 | 0x62 | 0x82 | 0xc2 | 0xe2 =>
+				[Cpu::fetch_op, Cpu::exec_imm, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			0x44 =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_rd1, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			0x54 | 0xd4 | 0xf4 =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_zrx3, Cpu::exec_rd1, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			0xdc | 0xfc =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_abs3, Cpu::exec_rd1, Cpu::trap, Cpu::trap, Cpu::trap, Cpu::trap],
+				
+			0x5c =>
+				[Cpu::fetch_op, Cpu::read_addr, Cpu::exec_abs3, Cpu::dummy, Cpu::dummy, Cpu::dummy, Cpu::dummy, Cpu::exec_rd1],
+				
+			_ => return None
+		};
+		
+		Some(steps)
+	}
+	
+	
+	// The rest of the 65C02's unused opcodes take a single cycle
+	fn is_65c02_single_cycle_nop(opcode: u8) -> bool
+	{
+		(opcode & 0x0f) == 0x03 || ((opcode & 0x0f) == 0x0b && opcode != cpu_opcodes::WAI && opcode != cpu_opcodes::STP)
 	}
 	
 	
@@ -534,35 +839,50 @@ impl Cpu
 	}
 	
 	
+	// Reads the last operand byte again
+	fn dummy_read(self: &mut Cpu)
+	{
+		(self.hook_read)(self.reg_pc.wrapping_sub(1));
+	}
+	
+	
+	// Interrupts are taken if they were seen at the end of the previous instruction's next-to-last cycle
 	fn fetch_op(self: &mut Cpu)
 	{
-		if self.acknowledge_nmi
+		if self.poll_prev_nmi
 		{
+			(self.hook_read)(self.reg_pc); // Opcode is read and discarded
 			self.acknowledge_nmi = false;
+			self.poll_nmi = false;
 			self.routine_nmi = true;
+			self.last_event = CpuEvent::Nmi;
 		}
 		
-		else if self.acknowledge_irq
+		else if self.poll_prev_irq
 		{
-			self.acknowledge_irq = false;
-			if (self.reg_p & FLAG_I) == 0
-				{ self.routine_irq = true; }
+			(self.hook_read)(self.reg_pc); // Opcode is read and discarded
+			self.routine_irq = true;
+			self.last_event = CpuEvent::Irq;
 		}
 		
 		else
 		{	
 			self.opcode = (self.hook_read)(self.reg_pc);
+			self.last_event = CpuEvent::FetchOpcode(self.reg_pc);
 			
+			if let Some(ref func) = self.hook_execute_instr
 			{
 				let reg_pc = self.reg_pc;
 				let next_byte1 = (self.hook_read)(self.reg_pc.wrapping_add(1));
 				let next_byte2 = (self.hook_read)(self.reg_pc.wrapping_add(2));
 				
-				if let Some(ref func) = self.hook_execute_instr
-					{ (func)(self, reg_pc, self.opcode, next_byte1, next_byte2); }
+				(func)(self, reg_pc, self.opcode, next_byte1, next_byte2);
 			}
 			
 			self.increment_pc();
+			
+			if self.variant == CpuVariant::Wdc65C02 && Cpu::is_65c02_single_cycle_nop(self.opcode)
+				{ self.end_opcode(); }
 		}
 	}
 	
@@ -620,7 +940,7 @@ impl Cpu
 	
 	fn exec_mdf2(self: &mut Cpu)
 	{
-		(self.hook_write)(self.internal_addr, self.internal_data); // Dummy write
+		self.dummy_modify();
 		self.exec_op();
 	}
 	
@@ -694,16 +1014,50 @@ impl Cpu
 			cpu_opcodes::BCS => (self.reg_p & FLAG_C) != 0,
 			cpu_opcodes::BNE => (self.reg_p & FLAG_Z) == 0,
 			cpu_opcodes::BEQ => (self.reg_p & FLAG_Z) != 0,
+			cpu_opcodes::BRA => true,
 			_ => unreachable!()
 		};
 		
 		if branch_taken
-			{ (self.hook_read)(self.reg_pc); } // Dummy read
+		{
+			// A taken branch doesn't poll on its last cycle, so interrupts that only showed up
+			// during the operand fetch wait for the next instruction
+			if self.poll_nmi && !self.poll_prev_nmi
+				{ self.poll_nmi = false; }
+				
+			if self.poll_irq && !self.poll_prev_irq
+				{ self.poll_irq = false; }
+				
+			(self.hook_read)(self.reg_pc); // Dummy read
+		}
 		else
 			{ self.end_opcode_and_prefetch(); }
 	}
 	
 	
+	fn exec_bbr3(self: &mut Cpu)
+	{
+		self.internal_data = (self.hook_read)(self.internal_addr);
+	}
+	
+	
+	// Waits with the bus idle until an interrupt shows up, even a masked IRQ
+	fn exec_wai3(self: &mut Cpu)
+	{
+		if self.acknowledge_nmi || self.signal_irq
+			{ self.end_opcode(); }
+		else
+			{ self.opcode_step -= 1; }
+	}
+	
+	
+	// Only a reset gets the CPU going again
+	fn exec_stp3(self: &mut Cpu)
+	{
+		self.opcode_step -= 1;
+	}
+	
+	
 	fn push_brk3(self: &mut Cpu)
 	{
 		let reg_pc = self.reg_pc;
@@ -725,6 +1079,22 @@ impl Cpu
 	}
 	
 	
+	fn push_x(self: &mut Cpu)
+	{
+		let reg_x = self.reg_x;
+		self.push_stack(reg_x);
+		self.end_opcode();
+	}
+	
+	
+	fn push_y(self: &mut Cpu)
+	{
+		let reg_y = self.reg_y;
+		self.push_stack(reg_y);
+		self.end_opcode();
+	}
+	
+	
 	fn push_p(self: &mut Cpu)
 	{
 		let reg_p = self.reg_p;
@@ -759,6 +1129,20 @@ impl Cpu
 	}
 	
 	
+	// Without a page crossing, the 65C02 skips the second read of shifts and rotates
+	fn exec_abx4_m(self: &mut Cpu)
+	{
+		let addr_without_carry = Cpu::calculate_effective_addr(self.internal_addr, self.reg_x, false);
+		let addr_with_carry    = Cpu::calculate_effective_addr(self.internal_addr, self.reg_x, true);
+	
+		self.internal_addr = addr_with_carry;
+		self.internal_data = (self.hook_read)(addr_without_carry); // Wrong read if address needs carry
+		
+		if addr_without_carry == addr_with_carry
+			{ self.opcode_step += 1; }
+	}
+	
+	
 	fn exec_aby4(self: &mut Cpu)
 	{
 		let addr_without_carry = Cpu::calculate_effective_addr(self.internal_addr, self.reg_y, false);
@@ -797,6 +1181,13 @@ impl Cpu
 	}
 	
 	
+	fn exec_iax4(self: &mut Cpu)
+	{
+		Cpu::dummy_read(self);
+		self.internal_addr = self.internal_addr.wrapping_add(self.reg_x as u16);
+	}
+	
+	
 	fn exec_pty4(self: &mut Cpu)
 	{
 		self.internal_addr = ((self.hook_read)(self.internal_addr.wrapping_add(1) & 0xff) as u16) << 8;
@@ -819,6 +1210,15 @@ impl Cpu
 	}
 	
 	
+	fn exec_bbr4(self: &mut Cpu)
+	{
+		(self.hook_read)(self.internal_addr); // Dummy read
+		
+		// Keep the tested value around while the branch offset is read
+		self.internal_addr = self.internal_data as u16;
+	}
+	
+	
 	fn push_brk4(self: &mut Cpu)
 	{
 		let reg_pc = self.reg_pc;
@@ -850,6 +1250,26 @@ impl Cpu
 	}
 	
 	
+	fn exec_plx4(self: &mut Cpu)
+	{
+		let reg_x = self.read_stack();
+		self.adjust_flag_z(reg_x);
+		self.adjust_flag_n(reg_x);
+		self.reg_x = reg_x;
+		self.end_opcode();
+	}
+	
+	
+	fn exec_ply4(self: &mut Cpu)
+	{
+		let reg_y = self.read_stack();
+		self.adjust_flag_z(reg_y);
+		self.adjust_flag_n(reg_y);
+		self.reg_y = reg_y;
+		self.end_opcode();
+	}
+	
+	
 	fn exec_plp4(self: &mut Cpu)
 	{
 		self.reg_p = self.read_stack() | FLAG_U;
@@ -873,7 +1293,7 @@ impl Cpu
 	
 	fn exec_abs5(self: &mut Cpu)
 	{
-		(self.hook_write)(self.internal_addr, self.internal_data);
+		self.dummy_modify();
 		self.exec_op();
 	}
 	
@@ -917,10 +1337,30 @@ impl Cpu
 	}
 	
 	
+	// BBRn branches if bit n is clear, BBSn if it's set
+	fn exec_bbr5(self: &mut Cpu)
+	{
+		self.internal_data = (self.hook_read)(self.reg_pc);
+		self.increment_pc();
+		
+		let bit_set = (self.internal_addr & (1 << ((self.opcode >> 4) & 7))) != 0;
+		let branch_taken = bit_set == ((self.opcode & 0x80) != 0);
+		
+		if !branch_taken
+			{ self.end_opcode(); }
+	}
+	
+	
+	// The NMOS pointer's high byte never crosses a page, which the 65C02 fixed
 	fn exec_ind5(self: &mut Cpu)
 	{
+		let addr_high = if self.variant == CpuVariant::Wdc65C02
+			{ self.internal_addr.wrapping_add(1) }
+		else
+			{ (self.internal_addr & 0xff00) | (self.internal_addr.wrapping_add(1) & 0xff) };
+			
 		self.reg_pc = self.internal_data as u16;
-		self.reg_pc |= ((self.hook_read)((self.internal_addr & 0xff00) | (self.internal_addr.wrapping_add(1) & 0xff)) as u16) << 8;
+		self.reg_pc |= ((self.hook_read)(addr_high) as u16) << 8;
 		self.end_opcode();
 	}
 	
@@ -928,7 +1368,8 @@ impl Cpu
 	fn exec_brk5(self: &mut Cpu)
 	{
 		let reg_p = self.reg_p;
-		self.push_stack(reg_p);
+		self.push_stack(reg_p | FLAG_B | FLAG_U);
+		self.select_interrupt_vector(VECTOR_IRQ);
 	}
 	
 	
@@ -959,16 +1400,22 @@ impl Cpu
 	}
 	
 	
+	fn exec_bbr6(self: &mut Cpu)
+	{
+		(self.hook_read)(self.reg_pc); // Dummy read
+	}
+	
+	
 	fn exec_abx6(self: &mut Cpu)
 	{
-		(self.hook_write)(self.internal_addr, self.internal_data);
+		self.dummy_modify();
 		self.exec_op();
 	}
 	
 	
 	fn exec_brk6(self: &mut Cpu)
 	{
-		self.reg_pc = (self.hook_read)(0xfffe) as u16;
+		self.reg_pc = (self.hook_read)(self.interrupt_vector) as u16;
 	}
 	
 	
@@ -1009,8 +1456,8 @@ impl Cpu
 	
 	fn exec_stk7(self: &mut Cpu)
 	{
-		self.reg_pc |= ((self.hook_read)(0xffff) as u16) << 8;
-		self.end_opcode();
+		self.reg_pc |= ((self.hook_read)(self.interrupt_vector + 1) as u16) << 8;
+		self.end_interrupt();
 	}
 	
 	
@@ -1023,6 +1470,9 @@ impl Cpu
 	
 	fn exec_op(self: &mut Cpu)
 	{
+		if self.variant == CpuVariant::Wdc65C02 && self.exec_op_65c02()
+			{ return; }
+			
 		match self.opcode
 		{
 			cpu_opcodes::NOP |
@@ -1142,7 +1592,8 @@ impl Cpu
 			cpu_opcodes::LDA_ABX |
 			cpu_opcodes::LDA_ABY |
 			cpu_opcodes::LDA_PTX |
-			cpu_opcodes::LDA_PTY =>
+			cpu_opcodes::LDA_PTY |
+			cpu_opcodes::LDA_PTR =>
 			{
 				self.reg_a = self.internal_data;
 				
@@ -1183,7 +1634,8 @@ impl Cpu
 			cpu_opcodes::STA_ABX |
 			cpu_opcodes::STA_ABY |
 			cpu_opcodes::STA_PTX |
-			cpu_opcodes::STA_PTY =>
+			cpu_opcodes::STA_PTY |
+			cpu_opcodes::STA_PTR =>
 				(self.hook_write)(self.internal_addr, self.reg_a),
 			
 			cpu_opcodes::STX_ZER |
@@ -1203,8 +1655,11 @@ impl Cpu
 			cpu_opcodes::ADC_ABX |
 			cpu_opcodes::ADC_ABY |
 			cpu_opcodes::ADC_PTX |
-			cpu_opcodes::ADC_PTY =>
+			cpu_opcodes::ADC_PTY |
+			cpu_opcodes::ADC_PTR =>
 			{
+				let (reg_a, data, carry_in) = (self.reg_a, self.internal_data, (self.reg_p & FLAG_C) != 0);
+				
 				let val = (self.reg_a as u16)
 					.wrapping_add(self.internal_data as u16)
 					.wrapping_add(if (self.reg_p & FLAG_C) != 0 { 1 } else { 0 });
@@ -1223,6 +1678,9 @@ impl Cpu
 				self.reg_p |= if carry { FLAG_C } else { 0 };
 					
 				self.reg_a = (val & 0xff) as u8;
+				
+				if self.is_decimal_mode()
+					{ self.adjust_adc_decimal(reg_a, data, carry_in); }
 			}
 			
 			cpu_opcodes::SBC_IMM |
@@ -1232,8 +1690,11 @@ impl Cpu
 			cpu_opcodes::SBC_ABX |
 			cpu_opcodes::SBC_ABY |
 			cpu_opcodes::SBC_PTX |
-			cpu_opcodes::SBC_PTY =>
+			cpu_opcodes::SBC_PTY |
+			cpu_opcodes::SBC_PTR =>
 			{
+				let (reg_a, data, carry_in) = (self.reg_a, self.internal_data, (self.reg_p & FLAG_C) != 0);
+				
 				let val = ((self.reg_a as u16) + 0x100)
 					.wrapping_sub(self.internal_data as u16)
 					.wrapping_sub(if (self.reg_p & FLAG_C) != 0 { 0 } else { 1 });
@@ -1252,6 +1713,9 @@ impl Cpu
 				self.reg_p |= if carry { FLAG_C } else { 0 };
 					
 				self.reg_a = (val & 0xff) as u8;
+				
+				if self.is_decimal_mode()
+					{ self.adjust_sbc_decimal(reg_a, data, carry_in); }
 			}
 			
 			cpu_opcodes::CMP_IMM |
@@ -1261,7 +1725,8 @@ impl Cpu
 			cpu_opcodes::CMP_ABX |
 			cpu_opcodes::CMP_ABY |
 			cpu_opcodes::CMP_PTX |
-			cpu_opcodes::CMP_PTY =>
+			cpu_opcodes::CMP_PTY |
+			cpu_opcodes::CMP_PTR =>
 			{
 				let val = ((self.reg_a as u16) + 0x100)
 					.wrapping_sub(self.internal_data as u16);
@@ -1314,7 +1779,8 @@ impl Cpu
 			cpu_opcodes::AND_ABX |
 			cpu_opcodes::AND_ABY |
 			cpu_opcodes::AND_PTX |
-			cpu_opcodes::AND_PTY =>
+			cpu_opcodes::AND_PTY |
+			cpu_opcodes::AND_PTR =>
 			{
 				let val = self.reg_a & self.internal_data;
 				self.adjust_flag_z(val);
@@ -1330,7 +1796,8 @@ impl Cpu
 			cpu_opcodes::ORA_ABX |
 			cpu_opcodes::ORA_ABY |
 			cpu_opcodes::ORA_PTX |
-			cpu_opcodes::ORA_PTY =>
+			cpu_opcodes::ORA_PTY |
+			cpu_opcodes::ORA_PTR =>
 			{
 				let val = self.reg_a | self.internal_data;
 				self.adjust_flag_z(val);
@@ -1346,7 +1813,8 @@ impl Cpu
 			cpu_opcodes::EOR_ABX |
 			cpu_opcodes::EOR_ABY |
 			cpu_opcodes::EOR_PTX |
-			cpu_opcodes::EOR_PTY =>
+			cpu_opcodes::EOR_PTY |
+			cpu_opcodes::EOR_PTR =>
 			{
 				let val = self.reg_a ^ self.internal_data;
 				self.adjust_flag_z(val);
@@ -1356,7 +1824,9 @@ impl Cpu
 			}
 			
 			cpu_opcodes::BIT_ZER |
-			cpu_opcodes::BIT_ABS =>
+			cpu_opcodes::BIT_ZRX |
+			cpu_opcodes::BIT_ABS |
+			cpu_opcodes::BIT_ABX =>
 			{
 				let val_z = self.reg_a & self.internal_data;
 				let val_n = self.internal_data;
@@ -1494,6 +1964,173 @@ impl Cpu
 	}
 	
 	
+	// Returns whether the opcode was one of the 65C02's own
+	fn exec_op_65c02(&mut self) -> bool
+	{
+		match self.opcode
+		{
+			cpu_opcodes::INC_IMP =>
+			{
+				let val = self.reg_a.wrapping_add(1);
+				self.adjust_flag_z(val);
+				self.adjust_flag_n(val);
+				self.reg_a = val;
+			}
+			
+			cpu_opcodes::DEC_IMP =>
+			{
+				let val = self.reg_a.wrapping_sub(1);
+				self.adjust_flag_z(val);
+				self.adjust_flag_n(val);
+				self.reg_a = val;
+			}
+			
+			// Only Z is affected, since there's no memory operand to take N and V from
+			cpu_opcodes::BIT_IMM =>
+			{
+				let val = self.reg_a & self.internal_data;
+				self.adjust_flag_z(val);
+			}
+			
+			cpu_opcodes::STZ_ZER |
+			cpu_opcodes::STZ_ZRX |
+			cpu_opcodes::STZ_ABS |
+			cpu_opcodes::STZ_ABX =>
+				(self.hook_write)(self.internal_addr, 0),
+				
+			cpu_opcodes::TSB_ZER |
+			cpu_opcodes::TSB_ABS =>
+			{
+				let val_z = self.reg_a & self.internal_data;
+				self.adjust_flag_z(val_z);
+				self.internal_data |= self.reg_a;
+			}
+			
+			cpu_opcodes::TRB_ZER |
+			cpu_opcodes::TRB_ABS =>
+			{
+				let val_z = self.reg_a & self.internal_data;
+				self.adjust_flag_z(val_z);
+				self.internal_data &= !self.reg_a;
+			}
+			
+			// RMB and SMB
+			opcode if (opcode & 0x0f) == 0x07 =>
+			{
+				let mask = 1 << ((opcode >> 4) & 7);
+				
+				if (opcode & 0x80) != 0
+					{ self.internal_data |= mask; }
+				else
+					{ self.internal_data &= !mask; }
+			}
+			
+			0x02 | 0x22 | 0x42 | 0x62 | 0x82 | 0xc2 | 0xe2 |
+			0x44 | 0x54 | 0xd4 | 0xf4 | 0x5c | 0xdc | 0xfc =>
+				{ }
+				
+			_ => return false
+		}
+		
+		true
+	}
+	
+	
+	// The 2A03 has the D flag, but ignores it
+	fn is_decimal_mode(&self) -> bool
+	{
+		(self.reg_p & FLAG_D) != 0 && self.variant != CpuVariant::Ricoh2A03
+	}
+	
+	
+	// Called after the binary addition. On the NMOS 6502 Z stays as in binary mode, while N and V
+	// come from the sum before the high digit is adjusted; the 65C02 takes N and Z from the result
+	fn adjust_adc_decimal(&mut self, reg_a: u8, data: u8, carry_in: bool)
+	{
+		let (a, m) = (reg_a as u16, data as u16);
+		
+		let mut low = (a & 0x0f) + (m & 0x0f) + (carry_in as u16);
+		if low >= 0x0a
+			{ low = ((low + 0x06) & 0x0f) + 0x10; }
+			
+		let mut val = (a & 0xf0) + (m & 0xf0) + low;
+		
+		let overflow = ((a ^ val) & (m ^ val) & 0x80) != 0;
+		self.adjust_flag_n(val as u8);
+		
+		if val >= 0xa0
+			{ val += 0x60; }
+			
+		let carry = val > 0xff;
+		
+		self.reg_p &= !(FLAG_V | FLAG_C);
+		self.reg_p |= if overflow { FLAG_V } else { 0 };
+		self.reg_p |= if carry { FLAG_C } else { 0 };
+		
+		self.reg_a = val as u8;
+		self.adjust_decimal_65c02();
+	}
+	
+	
+	// Called after the binary subtraction, whose flags the NMOS 6502 keeps
+	fn adjust_sbc_decimal(&mut self, reg_a: u8, data: u8, carry_in: bool)
+	{
+		let (a, m, borrow) = (reg_a as i16, data as i16, !carry_in as i16);
+		
+		let mut low = (a & 0x0f) - (m & 0x0f) - borrow;
+		
+		let val = if self.variant == CpuVariant::Wdc65C02
+		{
+			let mut val = a - m - borrow;
+			
+			if val < 0
+				{ val -= 0x60; }
+				
+			if low < 0
+				{ val -= 0x06; }
+				
+			val
+		}
+		else
+		{
+			if low < 0
+				{ low = ((low - 0x06) & 0x0f) - 0x10; }
+				
+			let mut val = (a & 0xf0) - (m & 0xf0) + low;
+			
+			if val < 0
+				{ val -= 0x60; }
+				
+			val
+		};
+		
+		self.reg_a = val as u8;
+		self.adjust_decimal_65c02();
+	}
+	
+	
+	fn adjust_decimal_65c02(&mut self)
+	{
+		if self.variant != CpuVariant::Wdc65C02
+			{ return; }
+			
+		let reg_a = self.reg_a;
+		self.adjust_flag_z(reg_a);
+		self.adjust_flag_n(reg_a);
+		self.decimal_cycle = true;
+	}
+	
+	
+	// The NMOS 6502 writes the unmodified value back, while the 65C02 reads it again
+	fn dummy_modify(&mut self)
+	{
+		if self.variant == CpuVariant::Wdc65C02
+			{ (self.hook_read)(self.internal_addr); }
+		else
+			{ (self.hook_write)(self.internal_addr, self.internal_data); }
+	}
+	
+	
 	fn calculate_effective_addr(base: u16, offset: u8, carry: bool) -> u16
 	{
 		if carry
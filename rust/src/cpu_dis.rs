@@ -1,272 +1,422 @@
 use cpu_opcodes;
+use cpu::CpuVariant;
 
 
-pub fn disassemble_instruction(addr: u16, byte1: u8, byte2: u8, byte3: u8) -> String
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AddressingMode
 {
-	match byte1
+	Imp, Imm,
+	Zer, Zrx, Zry,
+	Abs, Abx, Aby,
+	Ind, Ptx, Pty,
+	Rel,
+	
+	// 65C02 only
+	Ptr, Iax, Zrl
+}
+
+
+impl AddressingMode
+{
+	pub fn get_length(&self) -> usize
 	{
-		cpu_opcodes::BRK     => dis_instr_imp("brk", byte2, byte3),
-                             
-		cpu_opcodes::PLA     => dis_instr_imp("pla", byte2, byte3),
-		cpu_opcodes::PLP     => dis_instr_imp("plp", byte2, byte3),
-		cpu_opcodes::PHA     => dis_instr_imp("pha", byte2, byte3),
-		cpu_opcodes::PHP     => dis_instr_imp("php", byte2, byte3),
-                             
-		cpu_opcodes::JMP_ABS => dis_instr_abs("jmp", byte2, byte3),
-		cpu_opcodes::JMP_IND => dis_instr_ind("jmp", byte2, byte3),
-		cpu_opcodes::BPL     => dis_instr_rel("bpl", byte2, byte3, addr),
-		cpu_opcodes::BMI     => dis_instr_rel("bmi", byte2, byte3, addr),
-		cpu_opcodes::BVC     => dis_instr_rel("bvc", byte2, byte3, addr),
-		cpu_opcodes::BVS     => dis_instr_rel("bvs", byte2, byte3, addr),
-		cpu_opcodes::BCC     => dis_instr_rel("bcc", byte2, byte3, addr),
-		cpu_opcodes::BCS     => dis_instr_rel("bcs", byte2, byte3, addr),
-		cpu_opcodes::BNE     => dis_instr_rel("bne", byte2, byte3, addr),
-		cpu_opcodes::BEQ     => dis_instr_rel("beq", byte2, byte3, addr),
-                             
-		cpu_opcodes::JSR     => dis_instr_abs("jsr", byte2, byte3),
-		cpu_opcodes::RTI     => dis_instr_imp("rti", byte2, byte3),
-		cpu_opcodes::RTS     => dis_instr_imp("rts", byte2, byte3),
-                             
-		cpu_opcodes::LDA_IMM => dis_instr_imm("lda", byte2, byte3),
-		cpu_opcodes::LDA_ZER => dis_instr_zer("lda", byte2, byte3),
-		cpu_opcodes::LDA_ZRX => dis_instr_zrx("lda", byte2, byte3),
-		cpu_opcodes::LDA_ABS => dis_instr_abs("lda", byte2, byte3),
-		cpu_opcodes::LDA_ABX => dis_instr_abx("lda", byte2, byte3),
-		cpu_opcodes::LDA_ABY => dis_instr_aby("lda", byte2, byte3),
-		cpu_opcodes::LDA_PTX => dis_instr_ptx("lda", byte2, byte3),
-		cpu_opcodes::LDA_PTY => dis_instr_pty("lda", byte2, byte3),
-                             
-		cpu_opcodes::LDX_IMM => dis_instr_imm("ldx", byte2, byte3),
-		cpu_opcodes::LDX_ZER => dis_instr_zer("ldx", byte2, byte3),
-		cpu_opcodes::LDX_ZRY => dis_instr_zry("ldx", byte2, byte3),
-		cpu_opcodes::LDX_ABS => dis_instr_abs("ldx", byte2, byte3),
-		cpu_opcodes::LDX_ABY => dis_instr_aby("ldx", byte2, byte3),
-                             
-		cpu_opcodes::LDY_IMM => dis_instr_imm("ldy", byte2, byte3),
-		cpu_opcodes::LDY_ZER => dis_instr_zer("ldy", byte2, byte3),
-		cpu_opcodes::LDY_ZRX => dis_instr_zrx("ldy", byte2, byte3),
-		cpu_opcodes::LDY_ABS => dis_instr_abs("ldy", byte2, byte3),
-		cpu_opcodes::LDY_ABX => dis_instr_abx("ldy", byte2, byte3),
-                             
-		cpu_opcodes::STA_ZER => dis_instr_zer("sta", byte2, byte3),
-		cpu_opcodes::STA_ZRX => dis_instr_zrx("sta", byte2, byte3),
-		cpu_opcodes::STA_ABS => dis_instr_abs("sta", byte2, byte3),
-		cpu_opcodes::STA_ABX => dis_instr_abx("sta", byte2, byte3),
-		cpu_opcodes::STA_ABY => dis_instr_aby("sta", byte2, byte3),
-		cpu_opcodes::STA_PTX => dis_instr_ptx("sta", byte2, byte3),
-		cpu_opcodes::STA_PTY => dis_instr_pty("sta", byte2, byte3),
-                             
-		cpu_opcodes::STX_ZER => dis_instr_zer("stx", byte2, byte3),
-		cpu_opcodes::STX_ZRY => dis_instr_zry("stx", byte2, byte3),
-		cpu_opcodes::STX_ABS => dis_instr_abs("stx", byte2, byte3),
-                             
-		cpu_opcodes::STY_ZER => dis_instr_zer("sty", byte2, byte3),
-		cpu_opcodes::STY_ZRX => dis_instr_zrx("sty", byte2, byte3),
-		cpu_opcodes::STY_ABS => dis_instr_abs("sty", byte2, byte3),
-                             
-		cpu_opcodes::AND_IMM => dis_instr_imm("and", byte2, byte3),
-		cpu_opcodes::AND_ZER => dis_instr_zer("and", byte2, byte3),
-		cpu_opcodes::AND_ZRX => dis_instr_zrx("and", byte2, byte3),
-		cpu_opcodes::AND_ABS => dis_instr_abs("and", byte2, byte3),
-		cpu_opcodes::AND_ABX => dis_instr_abx("and", byte2, byte3),
-		cpu_opcodes::AND_ABY => dis_instr_aby("and", byte2, byte3),
-		cpu_opcodes::AND_PTX => dis_instr_ptx("and", byte2, byte3),
-		cpu_opcodes::AND_PTY => dis_instr_pty("and", byte2, byte3),
-                             
-		cpu_opcodes::ORA_IMM => dis_instr_imm("ora", byte2, byte3),
-		cpu_opcodes::ORA_ZER => dis_instr_zer("ora", byte2, byte3),
-		cpu_opcodes::ORA_ZRX => dis_instr_zrx("ora", byte2, byte3),
-		cpu_opcodes::ORA_ABS => dis_instr_abs("ora", byte2, byte3),
-		cpu_opcodes::ORA_ABX => dis_instr_abx("ora", byte2, byte3),
-		cpu_opcodes::ORA_ABY => dis_instr_aby("ora", byte2, byte3),
-		cpu_opcodes::ORA_PTX => dis_instr_ptx("ora", byte2, byte3),
-		cpu_opcodes::ORA_PTY => dis_instr_pty("ora", byte2, byte3),
-                             
-		cpu_opcodes::EOR_IMM => dis_instr_imm("eor", byte2, byte3),
-		cpu_opcodes::EOR_ZER => dis_instr_zer("eor", byte2, byte3),
-		cpu_opcodes::EOR_ZRX => dis_instr_zrx("eor", byte2, byte3),
-		cpu_opcodes::EOR_ABS => dis_instr_abs("eor", byte2, byte3),
-		cpu_opcodes::EOR_ABX => dis_instr_abx("eor", byte2, byte3),
-		cpu_opcodes::EOR_ABY => dis_instr_aby("eor", byte2, byte3),
-		cpu_opcodes::EOR_PTX => dis_instr_ptx("eor", byte2, byte3),
-		cpu_opcodes::EOR_PTY => dis_instr_pty("eor", byte2, byte3),
-                             
-		cpu_opcodes::ADC_IMM => dis_instr_imm("adc", byte2, byte3),
-		cpu_opcodes::ADC_ZER => dis_instr_zer("adc", byte2, byte3),
-		cpu_opcodes::ADC_ZRX => dis_instr_zrx("adc", byte2, byte3),
-		cpu_opcodes::ADC_ABS => dis_instr_abs("adc", byte2, byte3),
-		cpu_opcodes::ADC_ABX => dis_instr_abx("adc", byte2, byte3),
-		cpu_opcodes::ADC_ABY => dis_instr_aby("adc", byte2, byte3),
-		cpu_opcodes::ADC_PTX => dis_instr_ptx("adc", byte2, byte3),
-		cpu_opcodes::ADC_PTY => dis_instr_pty("adc", byte2, byte3),
-                             
-		cpu_opcodes::SBC_IMM => dis_instr_imm("sbc", byte2, byte3),
-		cpu_opcodes::SBC_ZER => dis_instr_zer("sbc", byte2, byte3),
-		cpu_opcodes::SBC_ZRX => dis_instr_zrx("sbc", byte2, byte3),
-		cpu_opcodes::SBC_ABS => dis_instr_abs("sbc", byte2, byte3),
-		cpu_opcodes::SBC_ABX => dis_instr_abx("sbc", byte2, byte3),
-		cpu_opcodes::SBC_ABY => dis_instr_aby("sbc", byte2, byte3),
-		cpu_opcodes::SBC_PTX => dis_instr_ptx("sbc", byte2, byte3),
-		cpu_opcodes::SBC_PTY => dis_instr_pty("sbc", byte2, byte3),
-                             
-		cpu_opcodes::CMP_IMM => dis_instr_imm("cmp", byte2, byte3),
-		cpu_opcodes::CMP_ZER => dis_instr_zer("cmp", byte2, byte3),
-		cpu_opcodes::CMP_ZRX => dis_instr_zrx("cmp", byte2, byte3),
-		cpu_opcodes::CMP_ABS => dis_instr_abs("cmp", byte2, byte3),
-		cpu_opcodes::CMP_ABX => dis_instr_abx("cmp", byte2, byte3),
-		cpu_opcodes::CMP_ABY => dis_instr_aby("cmp", byte2, byte3),
-		cpu_opcodes::CMP_PTX => dis_instr_ptx("cmp", byte2, byte3),
-		cpu_opcodes::CMP_PTY => dis_instr_pty("cmp", byte2, byte3),
-                             
-		cpu_opcodes::CPX_IMM => dis_instr_imm("cpx", byte2, byte3),
-		cpu_opcodes::CPX_ZER => dis_instr_zer("cpx", byte2, byte3),
-		cpu_opcodes::CPX_ABS => dis_instr_abs("cpx", byte2, byte3),
-                             
-		cpu_opcodes::CPY_IMM => dis_instr_imm("cpy", byte2, byte3),
-		cpu_opcodes::CPY_ZER => dis_instr_zer("cpy", byte2, byte3),
-		cpu_opcodes::CPY_ABS => dis_instr_abs("cpy", byte2, byte3),
-                             
-		cpu_opcodes::INX     => dis_instr_imp("inx", byte2, byte3),
-		cpu_opcodes::DEX     => dis_instr_imp("dex", byte2, byte3),
-		cpu_opcodes::INY     => dis_instr_imp("iny", byte2, byte3),
-		cpu_opcodes::DEY     => dis_instr_imp("dey", byte2, byte3),
-                             
-		cpu_opcodes::INC_ZER => dis_instr_zer("inc", byte2, byte3),
-		cpu_opcodes::INC_ZRX => dis_instr_zrx("inc", byte2, byte3),
-		cpu_opcodes::INC_ABS => dis_instr_abs("inc", byte2, byte3),
-		cpu_opcodes::INC_ABX => dis_instr_abx("inc", byte2, byte3),
-                             
-		cpu_opcodes::DEC_ZER => dis_instr_zer("dec", byte2, byte3),
-		cpu_opcodes::DEC_ZRX => dis_instr_zrx("dec", byte2, byte3),
-		cpu_opcodes::DEC_ABS => dis_instr_abs("dec", byte2, byte3),
-		cpu_opcodes::DEC_ABX => dis_instr_abx("dec", byte2, byte3),
-                             
-		cpu_opcodes::ASL_IMP => dis_instr_imp("asl a", byte2, byte3),
-		cpu_opcodes::ASL_ZER => dis_instr_zer("asl", byte2, byte3),
-		cpu_opcodes::ASL_ZRX => dis_instr_zrx("asl", byte2, byte3),
-		cpu_opcodes::ASL_ABS => dis_instr_abs("asl", byte2, byte3),
-		cpu_opcodes::ASL_ABX => dis_instr_abx("asl", byte2, byte3),
-                             
-		cpu_opcodes::LSR_IMP => dis_instr_imp("lsr a", byte2, byte3),
-		cpu_opcodes::LSR_ZER => dis_instr_zer("lsr", byte2, byte3),
-		cpu_opcodes::LSR_ZRX => dis_instr_zrx("lsr", byte2, byte3),
-		cpu_opcodes::LSR_ABS => dis_instr_abs("lsr", byte2, byte3),
-		cpu_opcodes::LSR_ABX => dis_instr_abx("lsr", byte2, byte3),
-                             
-		cpu_opcodes::ROL_IMP => dis_instr_imp("rol a", byte2, byte3),
-		cpu_opcodes::ROL_ZER => dis_instr_zer("rol", byte2, byte3),
-		cpu_opcodes::ROL_ZRX => dis_instr_zrx("rol", byte2, byte3),
-		cpu_opcodes::ROL_ABS => dis_instr_abs("rol", byte2, byte3),
-		cpu_opcodes::ROL_ABX => dis_instr_abx("rol", byte2, byte3),
-                             
-		cpu_opcodes::ROR_IMP => dis_instr_imp("ror a", byte2, byte3),
-		cpu_opcodes::ROR_ZER => dis_instr_zer("ror", byte2, byte3),
-		cpu_opcodes::ROR_ZRX => dis_instr_zrx("ror", byte2, byte3),
-		cpu_opcodes::ROR_ABS => dis_instr_abs("ror", byte2, byte3),
-		cpu_opcodes::ROR_ABX => dis_instr_abx("ror", byte2, byte3),
-                             
-		cpu_opcodes::BIT_ZER => dis_instr_zer("bit", byte2, byte3),
-		cpu_opcodes::BIT_ABS => dis_instr_abs("bit", byte2, byte3),
-                             
-		cpu_opcodes::TXA     => dis_instr_imp("txa", byte2, byte3),
-		cpu_opcodes::TAX     => dis_instr_imp("tax", byte2, byte3),
-		cpu_opcodes::TYA     => dis_instr_imp("tya", byte2, byte3),
-		cpu_opcodes::TAY     => dis_instr_imp("tay", byte2, byte3),
-		cpu_opcodes::TXS     => dis_instr_imp("txs", byte2, byte3),
-		cpu_opcodes::TSX     => dis_instr_imp("tsx", byte2, byte3),
-                             
-		cpu_opcodes::CLC     => dis_instr_imp("clc", byte2, byte3),
-		cpu_opcodes::SEC     => dis_instr_imp("sec", byte2, byte3),
-		cpu_opcodes::CLI     => dis_instr_imp("cli", byte2, byte3),
-		cpu_opcodes::SEI     => dis_instr_imp("sei", byte2, byte3),
-		cpu_opcodes::CLD     => dis_instr_imp("cld", byte2, byte3),
-		cpu_opcodes::SED     => dis_instr_imp("sed", byte2, byte3),
-		cpu_opcodes::CLV     => dis_instr_imp("clv", byte2, byte3),
-                             
-		cpu_opcodes::NOP     => dis_instr_imp("nop", byte2, byte3),
-		cpu_opcodes::NOP_2   => dis_instr_imp("nop", byte2, byte3),
-		cpu_opcodes::NOP_3   => dis_instr_imp("nop", byte2, byte3),
-		cpu_opcodes::NOP_4   => dis_instr_imp("nop", byte2, byte3),
-		cpu_opcodes::NOP_5   => dis_instr_imp("nop", byte2, byte3),
-		cpu_opcodes::NOP_6   => dis_instr_imp("nop", byte2, byte3),
-		cpu_opcodes::NOP_7   => dis_instr_imp("nop", byte2, byte3),
-		cpu_opcodes::NOP_8   => dis_instr_imp("nop", byte2, byte3),
-		
-		_ => "???".to_string()
+		match *self
+		{
+			AddressingMode::Imp => 1,
+			AddressingMode::Abs | AddressingMode::Abx | AddressingMode::Aby | AddressingMode::Ind |
+			AddressingMode::Iax | AddressingMode::Zrl => 3,
+			_ => 2
+		}
 	}
 }
 
 
-fn dis_instr_imp(mnemonic: &str, _byte2: u8, _byte3: u8) -> String
+pub fn disassemble_instruction(addr: u16, byte1: u8, byte2: u8, byte3: u8) -> String
 {
-	mnemonic.to_string()
+	disassemble_instruction_labelled(addr, byte1, byte2, byte3, &|_| None)
 }
 
 
-fn dis_instr_imm(mnemonic: &str, byte2: u8, _byte3: u8) -> String
+pub fn disassemble_instruction_labelled(addr: u16, byte1: u8, byte2: u8, byte3: u8, labels: &Fn(u16) -> Option<String>) -> String
 {
-	format!("{} #${:02x}", mnemonic, byte2)
+	format_instruction(addr, decode_opcode(byte1), byte1, byte2, byte3, labels)
 }
 
 
-fn dis_instr_zer(mnemonic: &str, byte2: u8, _byte3: u8) -> String
+pub fn disassemble_instruction_variant(addr: u16, byte1: u8, byte2: u8, byte3: u8, variant: CpuVariant) -> String
 {
-	format!("{} <${:02x}", mnemonic, byte2)
+	disassemble_instruction_labelled_variant(addr, byte1, byte2, byte3, variant, &|_| None)
 }
 
 
-fn dis_instr_zrx(mnemonic: &str, byte2: u8, _byte3: u8) -> String
+pub fn disassemble_instruction_labelled_variant(addr: u16, byte1: u8, byte2: u8, byte3: u8, variant: CpuVariant, labels: &Fn(u16) -> Option<String>) -> String
 {
-	format!("{} <${:02x}", mnemonic, byte2)
+	format_instruction(addr, decode_opcode_variant(byte1, variant), byte1, byte2, byte3, labels)
 }
 
 
-fn dis_instr_zry(mnemonic: &str, byte2: u8, _byte3: u8) -> String
+fn format_instruction(addr: u16, decoded: Option<(&'static str, AddressingMode)>, byte1: u8, byte2: u8, byte3: u8, labels: &Fn(u16) -> Option<String>) -> String
 {
-	format!("{} <${:02x}, y", mnemonic, byte2)
+	match decoded
+	{
+		Some((mnemonic, mode)) =>
+		{
+			let operand = format_operand(addr, mode, byte2, byte3, labels);
+			
+			if operand.is_empty()
+				{ mnemonic.to_string() }
+			else
+				{ format!("{} {}", mnemonic, operand) }
+		}
+		
+		None => format!(".byte ${:02x}", byte1)
+	}
 }
 
 
-fn dis_instr_abs(mnemonic: &str, byte2: u8, byte3: u8) -> String
+// Operands use ca65 syntax, so the output can be fed back to the assembler
+pub fn format_operand(addr: u16, mode: AddressingMode, byte2: u8, byte3: u8, labels: &Fn(u16) -> Option<String>) -> String
 {
-	format!("{} ${:04x}", mnemonic, byte2 as u16 | ((byte3 as u16) << 8))
+	let abs = byte2 as u16 | ((byte3 as u16) << 8);
+	
+	let zp_name = || labels(byte2 as u16).unwrap_or_else(|| format!("${:02x}", byte2));
+	let abs_name = |target: u16| labels(target).unwrap_or_else(|| format!("${:04x}", target));
+	
+	// Force absolute addressing for operands that would otherwise assemble as zero page
+	let abs_prefix = if abs < 0x100 { "a:" } else { "" };
+	
+	match mode
+	{
+		AddressingMode::Imp => String::new(),
+		AddressingMode::Imm => format!("#${:02x}", byte2),
+		AddressingMode::Zer => zp_name(),
+		AddressingMode::Zrx => format!("{},x", zp_name()),
+		AddressingMode::Zry => format!("{},y", zp_name()),
+		AddressingMode::Abs => format!("{}{}", abs_prefix, abs_name(abs)),
+		AddressingMode::Abx => format!("{}{},x", abs_prefix, abs_name(abs)),
+		AddressingMode::Aby => format!("{}{},y", abs_prefix, abs_name(abs)),
+		AddressingMode::Ind => format!("({})", abs_name(abs)),
+		AddressingMode::Ptx => format!("({},x)", zp_name()),
+		AddressingMode::Pty => format!("({}),y", zp_name()),
+		AddressingMode::Rel => abs_name(get_branch_target(addr, byte2)),
+		AddressingMode::Ptr => format!("({})", zp_name()),
+		AddressingMode::Iax => format!("({},x)", abs_name(abs)),
+		AddressingMode::Zrl => format!("{}, {}", zp_name(), abs_name(addr.wrapping_add(3).wrapping_add(byte3 as i8 as u16)))
+	}
 }
 
 
-fn dis_instr_abx(mnemonic: &str, byte2: u8, byte3: u8) -> String
+pub fn get_branch_target(addr: u16, offset: u8) -> u16
 {
-	format!("{} ${:04x}, x", mnemonic, byte2 as u16 | ((byte3 as u16) << 8))
+	addr.wrapping_add(2).wrapping_add(offset as i8 as u16)
 }
 
 
-fn dis_instr_aby(mnemonic: &str, byte2: u8, byte3: u8) -> String
+pub fn get_effective_addr(mode: AddressingMode, byte2: u8, byte3: u8, reg_x: u8, reg_y: u8, variant: CpuVariant, peek: &Fn(u16) -> u8) -> Option<u16>
 {
-	format!("{} ${:04x}, y", mnemonic, byte2 as u16 | ((byte3 as u16) << 8))
+	let abs = byte2 as u16 | ((byte3 as u16) << 8);
+	let peek_zp_word = |zp: u8| peek(zp as u16) as u16 | ((peek(zp.wrapping_add(1) as u16) as u16) << 8);
+	
+	match mode
+	{
+		AddressingMode::Zer => Some(byte2 as u16),
+		AddressingMode::Zrx => Some(byte2.wrapping_add(reg_x) as u16),
+		AddressingMode::Zry => Some(byte2.wrapping_add(reg_y) as u16),
+		AddressingMode::Abs => Some(abs),
+		AddressingMode::Abx => Some(abs.wrapping_add(reg_x as u16)),
+		AddressingMode::Aby => Some(abs.wrapping_add(reg_y as u16)),
+		AddressingMode::Ptx => Some(peek_zp_word(byte2.wrapping_add(reg_x))),
+		AddressingMode::Pty => Some(peek_zp_word(byte2).wrapping_add(reg_y as u16)),
+		AddressingMode::Ptr => Some(peek_zp_word(byte2)),
+		AddressingMode::Zrl => Some(byte2 as u16),
+		AddressingMode::Iax => Some(peek(abs.wrapping_add(reg_x as u16)) as u16 | ((peek(abs.wrapping_add(reg_x as u16).wrapping_add(1)) as u16) << 8)),
+		
+		AddressingMode::Ind => Some(peek(abs) as u16 | ((peek(get_pointer_high_addr(abs, variant)) as u16) << 8)),
+		
+		_ => None
+	}
 }
 
 
-fn dis_instr_ind(mnemonic: &str, byte2: u8, byte3: u8) -> String
+pub fn decode_opcode(opcode: u8) -> Option<(&'static str, AddressingMode)>
 {
-	format!("{} (${:04x})", mnemonic, byte2 as u16 | ((byte3 as u16) << 8))
+	match opcode
+	{
+		cpu_opcodes::BRK     => Some(("brk", AddressingMode::Imp)),
+                             
+		cpu_opcodes::PLA     => Some(("pla", AddressingMode::Imp)),
+		cpu_opcodes::PLP     => Some(("plp", AddressingMode::Imp)),
+		cpu_opcodes::PHA     => Some(("pha", AddressingMode::Imp)),
+		cpu_opcodes::PHP     => Some(("php", AddressingMode::Imp)),
+                             
+		cpu_opcodes::JMP_ABS => Some(("jmp", AddressingMode::Abs)),
+		cpu_opcodes::JMP_IND => Some(("jmp", AddressingMode::Ind)),
+		cpu_opcodes::BPL     => Some(("bpl", AddressingMode::Rel)),
+		cpu_opcodes::BMI     => Some(("bmi", AddressingMode::Rel)),
+		cpu_opcodes::BVC     => Some(("bvc", AddressingMode::Rel)),
+		cpu_opcodes::BVS     => Some(("bvs", AddressingMode::Rel)),
+		cpu_opcodes::BCC     => Some(("bcc", AddressingMode::Rel)),
+		cpu_opcodes::BCS     => Some(("bcs", AddressingMode::Rel)),
+		cpu_opcodes::BNE     => Some(("bne", AddressingMode::Rel)),
+		cpu_opcodes::BEQ     => Some(("beq", AddressingMode::Rel)),
+                             
+		cpu_opcodes::JSR     => Some(("jsr", AddressingMode::Abs)),
+		cpu_opcodes::RTI     => Some(("rti", AddressingMode::Imp)),
+		cpu_opcodes::RTS     => Some(("rts", AddressingMode::Imp)),
+                             
+		cpu_opcodes::LDA_IMM => Some(("lda", AddressingMode::Imm)),
+		cpu_opcodes::LDA_ZER => Some(("lda", AddressingMode::Zer)),
+		cpu_opcodes::LDA_ZRX => Some(("lda", AddressingMode::Zrx)),
+		cpu_opcodes::LDA_ABS => Some(("lda", AddressingMode::Abs)),
+		cpu_opcodes::LDA_ABX => Some(("lda", AddressingMode::Abx)),
+		cpu_opcodes::LDA_ABY => Some(("lda", AddressingMode::Aby)),
+		cpu_opcodes::LDA_PTX => Some(("lda", AddressingMode::Ptx)),
+		cpu_opcodes::LDA_PTY => Some(("lda", AddressingMode::Pty)),
+                             
+		cpu_opcodes::LDX_IMM => Some(("ldx", AddressingMode::Imm)),
+		cpu_opcodes::LDX_ZER => Some(("ldx", AddressingMode::Zer)),
+		cpu_opcodes::LDX_ZRY => Some(("ldx", AddressingMode::Zry)),
+		cpu_opcodes::LDX_ABS => Some(("ldx", AddressingMode::Abs)),
+		cpu_opcodes::LDX_ABY => Some(("ldx", AddressingMode::Aby)),
+                             
+		cpu_opcodes::LDY_IMM => Some(("ldy", AddressingMode::Imm)),
+		cpu_opcodes::LDY_ZER => Some(("ldy", AddressingMode::Zer)),
+		cpu_opcodes::LDY_ZRX => Some(("ldy", AddressingMode::Zrx)),
+		cpu_opcodes::LDY_ABS => Some(("ldy", AddressingMode::Abs)),
+		cpu_opcodes::LDY_ABX => Some(("ldy", AddressingMode::Abx)),
+                             
+		cpu_opcodes::STA_ZER => Some(("sta", AddressingMode::Zer)),
+		cpu_opcodes::STA_ZRX => Some(("sta", AddressingMode::Zrx)),
+		cpu_opcodes::STA_ABS => Some(("sta", AddressingMode::Abs)),
+		cpu_opcodes::STA_ABX => Some(("sta", AddressingMode::Abx)),
+		cpu_opcodes::STA_ABY => Some(("sta", AddressingMode::Aby)),
+		cpu_opcodes::STA_PTX => Some(("sta", AddressingMode::Ptx)),
+		cpu_opcodes::STA_PTY => Some(("sta", AddressingMode::Pty)),
+                             
+		cpu_opcodes::STX_ZER => Some(("stx", AddressingMode::Zer)),
+		cpu_opcodes::STX_ZRY => Some(("stx", AddressingMode::Zry)),
+		cpu_opcodes::STX_ABS => Some(("stx", AddressingMode::Abs)),
+                             
+		cpu_opcodes::STY_ZER => Some(("sty", AddressingMode::Zer)),
+		cpu_opcodes::STY_ZRX => Some(("sty", AddressingMode::Zrx)),
+		cpu_opcodes::STY_ABS => Some(("sty", AddressingMode::Abs)),
+                             
+		cpu_opcodes::AND_IMM => Some(("and", AddressingMode::Imm)),
+		cpu_opcodes::AND_ZER => Some(("and", AddressingMode::Zer)),
+		cpu_opcodes::AND_ZRX => Some(("and", AddressingMode::Zrx)),
+		cpu_opcodes::AND_ABS => Some(("and", AddressingMode::Abs)),
+		cpu_opcodes::AND_ABX => Some(("and", AddressingMode::Abx)),
+		cpu_opcodes::AND_ABY => Some(("and", AddressingMode::Aby)),
+		cpu_opcodes::AND_PTX => Some(("and", AddressingMode::Ptx)),
+		cpu_opcodes::AND_PTY => Some(("and", AddressingMode::Pty)),
+                             
+		cpu_opcodes::ORA_IMM => Some(("ora", AddressingMode::Imm)),
+		cpu_opcodes::ORA_ZER => Some(("ora", AddressingMode::Zer)),
+		cpu_opcodes::ORA_ZRX => Some(("ora", AddressingMode::Zrx)),
+		cpu_opcodes::ORA_ABS => Some(("ora", AddressingMode::Abs)),
+		cpu_opcodes::ORA_ABX => Some(("ora", AddressingMode::Abx)),
+		cpu_opcodes::ORA_ABY => Some(("ora", AddressingMode::Aby)),
+		cpu_opcodes::ORA_PTX => Some(("ora", AddressingMode::Ptx)),
+		cpu_opcodes::ORA_PTY => Some(("ora", AddressingMode::Pty)),
+                             
+		cpu_opcodes::EOR_IMM => Some(("eor", AddressingMode::Imm)),
+		cpu_opcodes::EOR_ZER => Some(("eor", AddressingMode::Zer)),
+		cpu_opcodes::EOR_ZRX => Some(("eor", AddressingMode::Zrx)),
+		cpu_opcodes::EOR_ABS => Some(("eor", AddressingMode::Abs)),
+		cpu_opcodes::EOR_ABX => Some(("eor", AddressingMode::Abx)),
+		cpu_opcodes::EOR_ABY => Some(("eor", AddressingMode::Aby)),
+		cpu_opcodes::EOR_PTX => Some(("eor", AddressingMode::Ptx)),
+		cpu_opcodes::EOR_PTY => Some(("eor", AddressingMode::Pty)),
+                             
+		cpu_opcodes::ADC_IMM => Some(("adc", AddressingMode::Imm)),
+		cpu_opcodes::ADC_ZER => Some(("adc", AddressingMode::Zer)),
+		cpu_opcodes::ADC_ZRX => Some(("adc", AddressingMode::Zrx)),
+		cpu_opcodes::ADC_ABS => Some(("adc", AddressingMode::Abs)),
+		cpu_opcodes::ADC_ABX => Some(("adc", AddressingMode::Abx)),
+		cpu_opcodes::ADC_ABY => Some(("adc", AddressingMode::Aby)),
+		cpu_opcodes::ADC_PTX => Some(("adc", AddressingMode::Ptx)),
+		cpu_opcodes::ADC_PTY => Some(("adc", AddressingMode::Pty)),
+                             
+		cpu_opcodes::SBC_IMM => Some(("sbc", AddressingMode::Imm)),
+		cpu_opcodes::SBC_ZER => Some(("sbc", AddressingMode::Zer)),
+		cpu_opcodes::SBC_ZRX => Some(("sbc", AddressingMode::Zrx)),
+		cpu_opcodes::SBC_ABS => Some(("sbc", AddressingMode::Abs)),
+		cpu_opcodes::SBC_ABX => Some(("sbc", AddressingMode::Abx)),
+		cpu_opcodes::SBC_ABY => Some(("sbc", AddressingMode::Aby)),
+		cpu_opcodes::SBC_PTX => Some(("sbc", AddressingMode::Ptx)),
+		cpu_opcodes::SBC_PTY => Some(("sbc", AddressingMode::Pty)),
+                             
+		cpu_opcodes::CMP_IMM => Some(("cmp", AddressingMode::Imm)),
+		cpu_opcodes::CMP_ZER => Some(("cmp", AddressingMode::Zer)),
+		cpu_opcodes::CMP_ZRX => Some(("cmp", AddressingMode::Zrx)),
+		cpu_opcodes::CMP_ABS => Some(("cmp", AddressingMode::Abs)),
+		cpu_opcodes::CMP_ABX => Some(("cmp", AddressingMode::Abx)),
+		cpu_opcodes::CMP_ABY => Some(("cmp", AddressingMode::Aby)),
+		cpu_opcodes::CMP_PTX => Some(("cmp", AddressingMode::Ptx)),
+		cpu_opcodes::CMP_PTY => Some(("cmp", AddressingMode::Pty)),
+                             
+		cpu_opcodes::CPX_IMM => Some(("cpx", AddressingMode::Imm)),
+		cpu_opcodes::CPX_ZER => Some(("cpx", AddressingMode::Zer)),
+		cpu_opcodes::CPX_ABS => Some(("cpx", AddressingMode::Abs)),
+                             
+		cpu_opcodes::CPY_IMM => Some(("cpy", AddressingMode::Imm)),
+		cpu_opcodes::CPY_ZER => Some(("cpy", AddressingMode::Zer)),
+		cpu_opcodes::CPY_ABS => Some(("cpy", AddressingMode::Abs)),
+                             
+		cpu_opcodes::INX     => Some(("inx", AddressingMode::Imp)),
+		cpu_opcodes::DEX     => Some(("dex", AddressingMode::Imp)),
+		cpu_opcodes::INY     => Some(("iny", AddressingMode::Imp)),
+		cpu_opcodes::DEY     => Some(("dey", AddressingMode::Imp)),
+                             
+		cpu_opcodes::INC_ZER => Some(("inc", AddressingMode::Zer)),
+		cpu_opcodes::INC_ZRX => Some(("inc", AddressingMode::Zrx)),
+		cpu_opcodes::INC_ABS => Some(("inc", AddressingMode::Abs)),
+		cpu_opcodes::INC_ABX => Some(("inc", AddressingMode::Abx)),
+                             
+		cpu_opcodes::DEC_ZER => Some(("dec", AddressingMode::Zer)),
+		cpu_opcodes::DEC_ZRX => Some(("dec", AddressingMode::Zrx)),
+		cpu_opcodes::DEC_ABS => Some(("dec", AddressingMode::Abs)),
+		cpu_opcodes::DEC_ABX => Some(("dec", AddressingMode::Abx)),
+                             
+		cpu_opcodes::ASL_IMP => Some(("asl a", AddressingMode::Imp)),
+		cpu_opcodes::ASL_ZER => Some(("asl", AddressingMode::Zer)),
+		cpu_opcodes::ASL_ZRX => Some(("asl", AddressingMode::Zrx)),
+		cpu_opcodes::ASL_ABS => Some(("asl", AddressingMode::Abs)),
+		cpu_opcodes::ASL_ABX => Some(("asl", AddressingMode::Abx)),
+                             
+		cpu_opcodes::LSR_IMP => Some(("lsr a", AddressingMode::Imp)),
+		cpu_opcodes::LSR_ZER => Some(("lsr", AddressingMode::Zer)),
+		cpu_opcodes::LSR_ZRX => Some(("lsr", AddressingMode::Zrx)),
+		cpu_opcodes::LSR_ABS => Some(("lsr", AddressingMode::Abs)),
+		cpu_opcodes::LSR_ABX => Some(("lsr", AddressingMode::Abx)),
+                             
+		cpu_opcodes::ROL_IMP => Some(("rol a", AddressingMode::Imp)),
+		cpu_opcodes::ROL_ZER => Some(("rol", AddressingMode::Zer)),
+		cpu_opcodes::ROL_ZRX => Some(("rol", AddressingMode::Zrx)),
+		cpu_opcodes::ROL_ABS => Some(("rol", AddressingMode::Abs)),
+		cpu_opcodes::ROL_ABX => Some(("rol", AddressingMode::Abx)),
+                             
+		cpu_opcodes::ROR_IMP => Some(("ror a", AddressingMode::Imp)),
+		cpu_opcodes::ROR_ZER => Some(("ror", AddressingMode::Zer)),
+		cpu_opcodes::ROR_ZRX => Some(("ror", AddressingMode::Zrx)),
+		cpu_opcodes::ROR_ABS => Some(("ror", AddressingMode::Abs)),
+		cpu_opcodes::ROR_ABX => Some(("ror", AddressingMode::Abx)),
+                             
+		cpu_opcodes::BIT_ZER => Some(("bit", AddressingMode::Zer)),
+		cpu_opcodes::BIT_ABS => Some(("bit", AddressingMode::Abs)),
+                             
+		cpu_opcodes::TXA     => Some(("txa", AddressingMode::Imp)),
+		cpu_opcodes::TAX     => Some(("tax", AddressingMode::Imp)),
+		cpu_opcodes::TYA     => Some(("tya", AddressingMode::Imp)),
+		cpu_opcodes::TAY     => Some(("tay", AddressingMode::Imp)),
+		cpu_opcodes::TXS     => Some(("txs", AddressingMode::Imp)),
+		cpu_opcodes::TSX     => Some(("tsx", AddressingMode::Imp)),
+                             
+		cpu_opcodes::CLC     => Some(("clc", AddressingMode::Imp)),
+		cpu_opcodes::SEC     => Some(("sec", AddressingMode::Imp)),
+		cpu_opcodes::CLI     => Some(("cli", AddressingMode::Imp)),
+		cpu_opcodes::SEI     => Some(("sei", AddressingMode::Imp)),
+		cpu_opcodes::CLD     => Some(("cld", AddressingMode::Imp)),
+		cpu_opcodes::SED     => Some(("sed", AddressingMode::Imp)),
+		cpu_opcodes::CLV     => Some(("clv", AddressingMode::Imp)),
+                             
+		cpu_opcodes::NOP     => Some(("nop", AddressingMode::Imp)),
+		cpu_opcodes::NOP_2   => Some(("nop", AddressingMode::Imp)),
+		cpu_opcodes::NOP_3   => Some(("nop", AddressingMode::Imp)),
+		cpu_opcodes::NOP_4   => Some(("nop", AddressingMode::Imp)),
+		cpu_opcodes::NOP_5   => Some(("nop", AddressingMode::Imp)),
+		cpu_opcodes::NOP_6   => Some(("nop", AddressingMode::Imp)),
+		cpu_opcodes::NOP_7   => Some(("nop", AddressingMode::Imp)),
+		cpu_opcodes::NOP_8   => Some(("nop", AddressingMode::Imp)),
+		
+		_ => None
+	}
 }
 
 
-fn dis_instr_ptx(mnemonic: &str, byte2: u8, _byte3: u8) -> String
+// Where JMP ($nnnn) reads the high byte of its pointer: the NMOS CPUs don't carry into
+// the page, but the 65C02 does
+pub fn get_pointer_high_addr(pointer: u16, variant: CpuVariant) -> u16
 {
-	format!("{} (<$00{:02x}, x)", mnemonic, byte2)
+	match variant
+	{
+		CpuVariant::Wdc65C02 => pointer.wrapping_add(1),
+		_ => (pointer & 0xff00) | (pointer.wrapping_add(1) & 0xff)
+	}
 }
 
 
-fn dis_instr_pty(mnemonic: &str, byte2: u8, _byte3: u8) -> String
+// The NMOS 6502 decodes like the 2A03
+pub fn decode_opcode_variant(opcode: u8, variant: CpuVariant) -> Option<(&'static str, AddressingMode)>
 {
-	format!("{} (<$00{:02x}), y", mnemonic, byte2)
+	match variant
+	{
+		CpuVariant::Wdc65C02 => decode_opcode_65c02(opcode),
+		_ => decode_opcode(opcode)
+	}
 }
 
 
-fn dis_instr_rel(mnemonic: &str, byte2: u8, _byte3: u8, addr: u16) -> String
+pub fn decode_opcode_65c02(opcode: u8) -> Option<(&'static str, AddressingMode)>
 {
-	let extended_offset = if (byte2 & 0x80) == 0 { byte2 as u16 } else { 0xff00 | byte2 as u16 };
-	let final_addr = addr.wrapping_add(2).wrapping_add(extended_offset);
+	const RMB: [&str; 8] = ["rmb0", "rmb1", "rmb2", "rmb3", "rmb4", "rmb5", "rmb6", "rmb7"];
+	const SMB: [&str; 8] = ["smb0", "smb1", "smb2", "smb3", "smb4", "smb5", "smb6", "smb7"];
+	const BBR: [&str; 8] = ["bbr0", "bbr1", "bbr2", "bbr3", "bbr4", "bbr5", "bbr6", "bbr7"];
+	const BBS: [&str; 8] = ["bbs0", "bbs1", "bbs2", "bbs3", "bbs4", "bbs5", "bbs6", "bbs7"];
+	
+	let bit = ((opcode >> 4) & 7) as usize;
 	
-	format!("{} ${:04x}", mnemonic, final_addr)
+	match opcode
+	{
+		cpu_opcodes::BRA     => Some(("bra", AddressingMode::Rel)),
+		cpu_opcodes::JMP_IAX => Some(("jmp", AddressingMode::Iax)),
+		
+		cpu_opcodes::PHX     => Some(("phx", AddressingMode::Imp)),
+		cpu_opcodes::PHY     => Some(("phy", AddressingMode::Imp)),
+		cpu_opcodes::PLX     => Some(("plx", AddressingMode::Imp)),
+		cpu_opcodes::PLY     => Some(("ply", AddressingMode::Imp)),
+		
+		cpu_opcodes::INC_IMP => Some(("inc a", AddressingMode::Imp)),
+		cpu_opcodes::DEC_IMP => Some(("dec a", AddressingMode::Imp)),
+		
+		cpu_opcodes::ORA_PTR => Some(("ora", AddressingMode::Ptr)),
+		cpu_opcodes::AND_PTR => Some(("and", AddressingMode::Ptr)),
+		cpu_opcodes::EOR_PTR => Some(("eor", AddressingMode::Ptr)),
+		cpu_opcodes::ADC_PTR => Some(("adc", AddressingMode::Ptr)),
+		cpu_opcodes::STA_PTR => Some(("sta", AddressingMode::Ptr)),
+		cpu_opcodes::LDA_PTR => Some(("lda", AddressingMode::Ptr)),
+		cpu_opcodes::CMP_PTR => Some(("cmp", AddressingMode::Ptr)),
+		cpu_opcodes::SBC_PTR => Some(("sbc", AddressingMode::Ptr)),
+		
+		cpu_opcodes::BIT_IMM => Some(("bit", AddressingMode::Imm)),
+		cpu_opcodes::BIT_ZRX => Some(("bit", AddressingMode::Zrx)),
+		cpu_opcodes::BIT_ABX => Some(("bit", AddressingMode::Abx)),
+		
+		cpu_opcodes::STZ_ZER => Some(("stz", AddressingMode::Zer)),
+		cpu_opcodes::STZ_ZRX => Some(("stz", AddressingMode::Zrx)),
+		cpu_opcodes::STZ_ABS => Some(("stz", AddressingMode::Abs)),
+		cpu_opcodes::STZ_ABX => Some(("stz", AddressingMode::Abx)),
+		
+		cpu_opcodes::TSB_ZER => Some(("tsb", AddressingMode::Zer)),
+		cpu_opcodes::TSB_ABS => Some(("tsb", AddressingMode::Abs)),
+		cpu_opcodes::TRB_ZER => Some(("trb", AddressingMode::Zer)),
+		cpu_opcodes::TRB_ABS => Some(("trb", AddressingMode::Abs)),
+		
+		cpu_opcodes::WAI     => Some(("wai", AddressingMode::Imp)),
+		cpu_opcodes::STP     => Some(("stp", AddressingMode::Imp)),
+		
+		_ if (opcode & 0x8f) == cpu_opcodes::RMB0 => Some((RMB[bit], AddressingMode::Zer)),
+		_ if (opcode & 0x8f) == cpu_opcodes::SMB0 => Some((SMB[bit], AddressingMode::Zer)),
+		_ if (opcode & 0x8f) == cpu_opcodes::BBR0 => Some((BBR[bit], AddressingMode::Zrl)),
+		_ if (opcode & 0x8f) == cpu_opcodes::BBS0 => Some((BBS[bit], AddressingMode::Zrl)),
+		
+		// Unused opcodes are NOPs of various lengths
+		0x02 | 0x22 | 0x42 | 0x62 | 0x82 | 0xc2 | 0xe2 => Some(("nop", AddressingMode::Imm)),
+		0x44 => Some(("nop", AddressingMode::Zer)),
+		0x54 | 0xd4 | 0xf4 => Some(("nop", AddressingMode::Zrx)),
+		0x5c | 0xdc | 0xfc => Some(("nop", AddressingMode::Abs)),
+		_ if (opcode & 0x07) == 0x03 => Some(("nop", AddressingMode::Imp)),
+		
+		_ => decode_opcode(opcode)
+	}
 }
\ No newline at end of file
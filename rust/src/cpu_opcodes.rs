@@ -28,6 +28,7 @@ pub const LDA_ABX : u8 = 0xbd;
 pub const LDA_ABY : u8 = 0xb9;
 pub const LDA_PTX : u8 = 0xa1;
 pub const LDA_PTY : u8 = 0xb1;
+pub const LDA_PTR : u8 = 0xb2;
 
 pub const LDX_IMM : u8 = 0xa2;
 pub const LDX_ZER : u8 = 0xa6;
@@ -48,6 +49,7 @@ pub const STA_ABX : u8 = 0x9d;
 pub const STA_ABY : u8 = 0x99;
 pub const STA_PTX : u8 = 0x81;
 pub const STA_PTY : u8 = 0x91;
+pub const STA_PTR : u8 = 0x92;
 
 pub const STX_ZER : u8 = 0x86;
 pub const STX_ZRY : u8 = 0x96;
@@ -65,6 +67,7 @@ pub const AND_ABX : u8 = 0x3d;
 pub const AND_ABY : u8 = 0x39;
 pub const AND_PTX : u8 = 0x21;
 pub const AND_PTY : u8 = 0x31;
+pub const AND_PTR : u8 = 0x32;
 
 pub const ORA_IMM : u8 = 0x09;
 pub const ORA_ZER : u8 = 0x05;
@@ -74,6 +77,7 @@ pub const ORA_ABX : u8 = 0x1d;
 pub const ORA_ABY : u8 = 0x19;
 pub const ORA_PTX : u8 = 0x01;
 pub const ORA_PTY : u8 = 0x11;
+pub const ORA_PTR : u8 = 0x12;
 
 pub const EOR_IMM : u8 = 0x49;
 pub const EOR_ZER : u8 = 0x45;
@@ -83,6 +87,7 @@ pub const EOR_ABX : u8 = 0x5d;
 pub const EOR_ABY : u8 = 0x59;
 pub const EOR_PTX : u8 = 0x41;
 pub const EOR_PTY : u8 = 0x51;
+pub const EOR_PTR : u8 = 0x52;
 
 pub const ADC_IMM : u8 = 0x69;
 pub const ADC_ZER : u8 = 0x65;
@@ -92,6 +97,7 @@ pub const ADC_ABX : u8 = 0x7d;
 pub const ADC_ABY : u8 = 0x79;
 pub const ADC_PTX : u8 = 0x61;
 pub const ADC_PTY : u8 = 0x71;
+pub const ADC_PTR : u8 = 0x72;
 
 pub const SBC_IMM : u8 = 0xe9;
 pub const SBC_ZER : u8 = 0xe5;
@@ -101,6 +107,7 @@ pub const SBC_ABX : u8 = 0xfd;
 pub const SBC_ABY : u8 = 0xf9;
 pub const SBC_PTX : u8 = 0xe1;
 pub const SBC_PTY : u8 = 0xf1;
+pub const SBC_PTR : u8 = 0xf2;
 
 pub const CMP_IMM : u8 = 0xc9;
 pub const CMP_ZER : u8 = 0xc5;
@@ -110,6 +117,7 @@ pub const CMP_ABX : u8 = 0xdd;
 pub const CMP_ABY : u8 = 0xd9;
 pub const CMP_PTX : u8 = 0xc1;
 pub const CMP_PTY : u8 = 0xd1;
+pub const CMP_PTR : u8 = 0xd2;
 
 pub const CPX_IMM : u8 = 0xe0;
 pub const CPX_ZER : u8 = 0xe4;
@@ -160,6 +168,9 @@ pub const ROR_ABX : u8 = 0x7e;
 
 pub const BIT_ZER : u8 = 0x24;
 pub const BIT_ABS : u8 = 0x2c;
+pub const BIT_IMM : u8 = 0x89;
+pub const BIT_ZRX : u8 = 0x34;
+pub const BIT_ABX : u8 = 0x3c;
 
 pub const TXA     : u8 = 0x8a;
 pub const TAX     : u8 = 0xaa;
@@ -183,4 +194,35 @@ pub const NOP_4   : u8 = 0x5a;
 pub const NOP_5   : u8 = 0x7a;
 pub const NOP_6   : u8 = 0xda;
 pub const NOP_7   : u8 = 0xfa;
-pub const NOP_8   : u8 = 0x80;
\ No newline at end of file
+pub const NOP_8   : u8 = 0x80;
+
+// 65C02 only, some reusing the opcodes of unofficial NOPs
+pub const BRA     : u8 = 0x80;
+pub const JMP_IAX : u8 = 0x7c;
+
+pub const PHX     : u8 = 0xda;
+pub const PHY     : u8 = 0x5a;
+pub const PLX     : u8 = 0xfa;
+pub const PLY     : u8 = 0x7a;
+
+pub const INC_IMP : u8 = 0x1a;
+pub const DEC_IMP : u8 = 0x3a;
+
+pub const STZ_ZER : u8 = 0x64;
+pub const STZ_ZRX : u8 = 0x74;
+pub const STZ_ABS : u8 = 0x9c;
+pub const STZ_ABX : u8 = 0x9e;
+
+pub const TSB_ZER : u8 = 0x04;
+pub const TSB_ABS : u8 = 0x0c;
+pub const TRB_ZER : u8 = 0x14;
+pub const TRB_ABS : u8 = 0x1c;
+
+pub const WAI     : u8 = 0xcb;
+pub const STP     : u8 = 0xdb;
+
+// The bit number goes in the high nibble, so RMB3 is RMB0 + 0x30
+pub const RMB0    : u8 = 0x07;
+pub const SMB0    : u8 = 0x87;
+pub const BBR0    : u8 = 0x0f;
+pub const BBS0    : u8 = 0x8f;
\ No newline at end of file
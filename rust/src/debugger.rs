@@ -0,0 +1,447 @@
+use Core;
+use cpu::CpuEvent;
+use debugger_expr::{Expression, ExpressionContext, Variable};
+
+
+pub const WATCH_READ: u8 = 0b001;
+pub const WATCH_WRITE: u8 = 0b010;
+pub const WATCH_EXECUTE: u8 = 0b100;
+
+
+const OPCODE_JSR: u8 = 0x20;
+const OPCODE_RTI: u8 = 0x40;
+const OPCODE_RTS: u8 = 0x60;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AddressSpace
+{
+	Cpu,
+	Ppu
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Breakpoint
+{
+	pub space: AddressSpace,
+	pub addr_start: u16,
+	pub addr_end: u16,
+	pub flags: u8,
+	pub enabled: bool,
+	pub condition: Option<Expression>
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason
+{
+	Breakpoint { index: usize, addr: u16 },
+	Watchpoint { index: usize, space: AddressSpace, addr: u16, value: u8, write: bool },
+	Step,
+	Scanline(usize),
+	Nmi,
+	Irq
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum StepMode
+{
+	None,
+	Into,
+	Over { addr: u16, reg_s: u8 },
+	Out { reg_s: u8 }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Access
+{
+	space: AddressSpace,
+	addr: u16,
+	value: u8,
+	write: bool
+}
+
+
+pub struct Debugger
+{
+	pub breakpoints: Vec<Option<Breakpoint>>,
+	pub break_on_nmi: bool,
+	pub break_on_irq: bool,
+	
+	step_mode: StepMode,
+	target_scanline: Option<usize>,
+	
+	watch_cpu: bool,
+	watch_ppu: bool,
+	accesses: Vec<Access>,
+	
+	instr_addr: Option<u16>,
+	instr_opcode: u8,
+	instr_reg_s: u8
+}
+
+
+struct DebuggerContext<'a>
+{
+	core: &'a Core,
+	access: Access,
+	pc: u16
+}
+
+
+impl Debugger
+{
+	pub fn new() -> Debugger
+	{
+		Debugger
+		{
+			breakpoints: Vec::new(),
+			break_on_nmi: false,
+			break_on_irq: false,
+			
+			step_mode: StepMode::None,
+			target_scanline: None,
+			
+			watch_cpu: false,
+			watch_ppu: false,
+			accesses: Vec::new(),
+			
+			instr_addr: None,
+			instr_opcode: 0,
+			instr_reg_s: 0
+		}
+	}
+	
+	
+	pub fn add(&mut self, breakpoint: Breakpoint) -> usize
+	{
+		self.breakpoints.push(Some(breakpoint));
+		self.refresh_watch_flags();
+		self.breakpoints.len() - 1
+	}
+	
+	
+	pub fn add_breakpoint(&mut self, addr: u16) -> usize
+	{
+		self.add(Breakpoint
+		{
+			space: AddressSpace::Cpu,
+			addr_start: addr,
+			addr_end: addr,
+			flags: WATCH_EXECUTE,
+			enabled: true,
+			condition: None
+		})
+	}
+	
+	
+	pub fn add_watchpoint(&mut self, space: AddressSpace, addr_start: u16, addr_end: u16, flags: u8) -> usize
+	{
+		self.add(Breakpoint
+		{
+			space,
+			addr_start,
+			addr_end,
+			flags,
+			enabled: true,
+			condition: None
+		})
+	}
+	
+	
+	pub fn remove(&mut self, index: usize)
+	{
+		if index < self.breakpoints.len()
+			{ self.breakpoints[index] = None; }
+			
+		self.refresh_watch_flags();
+	}
+	
+	
+	pub fn clear(&mut self)
+	{
+		self.breakpoints.clear();
+		self.refresh_watch_flags();
+	}
+	
+	
+	pub fn set_enabled(&mut self, index: usize, enabled: bool)
+	{
+		if let Some(&mut Some(ref mut breakpoint)) = self.breakpoints.get_mut(index)
+			{ breakpoint.enabled = enabled; }
+			
+		self.refresh_watch_flags();
+	}
+	
+	
+	pub fn set_condition(&mut self, index: usize, condition: &str) -> Result<(), String>
+	{
+		let expr = if condition.trim().is_empty()
+			{ None }
+		else
+			{ Some(Expression::parse(condition)?) };
+			
+		match self.breakpoints.get_mut(index)
+		{
+			Some(&mut Some(ref mut breakpoint)) =>
+			{
+				breakpoint.condition = expr;
+				Ok(())
+			}
+			
+			_ => Err(format!("no breakpoint #{}", index))
+		}
+	}
+	
+	
+	pub fn step_into(&mut self)
+	{
+		self.step_mode = StepMode::Into;
+	}
+	
+	
+	pub fn step_over(&mut self)
+	{
+		// Only a JSR can be stepped over; anything else is a single step
+		self.step_mode = match self.instr_addr
+		{
+			Some(addr) if self.instr_opcode == OPCODE_JSR =>
+				StepMode::Over { addr: addr.wrapping_add(3), reg_s: self.instr_reg_s },
+				
+			_ => StepMode::Into
+		};
+	}
+	
+	
+	pub fn step_out(&mut self)
+	{
+		self.step_mode = match self.instr_addr
+		{
+			Some(_) => StepMode::Out { reg_s: self.instr_reg_s },
+			None => StepMode::Into
+		};
+	}
+	
+	
+	pub fn run_to_scanline(&mut self, scanline: usize)
+	{
+		self.target_scanline = Some(scanline);
+	}
+	
+	
+	pub fn cancel_step(&mut self)
+	{
+		self.step_mode = StepMode::None;
+		self.target_scanline = None;
+	}
+	
+	
+	pub fn record_access(&mut self, space: AddressSpace, addr: u16, value: u8, write: bool)
+	{
+		let watching = match space
+		{
+			AddressSpace::Cpu => self.watch_cpu,
+			AddressSpace::Ppu => self.watch_ppu
+		};
+		
+		if watching
+			{ self.accesses.push(Access { space, addr, value, write }); }
+	}
+	
+	
+	pub fn check_cpu(&mut self, core: &Core) -> Option<StopReason>
+	{
+		let mut stop = self.check_accesses(core);
+		
+		match core.cpu.last_event
+		{
+			CpuEvent::None => { }
+			
+			CpuEvent::Nmi =>
+			{
+				if self.break_on_nmi
+					{ stop = stop.or(Some(StopReason::Nmi)); }
+			}
+			
+			CpuEvent::Irq =>
+			{
+				if self.break_on_irq
+					{ stop = stop.or(Some(StopReason::Irq)); }
+			}
+			
+			CpuEvent::FetchOpcode(addr) =>
+			{
+				let access = Access { space: AddressSpace::Cpu, addr, value: core.cpu.opcode, write: false };
+				let reg_s = core.cpu.reg_s;
+				
+				let step_done = match self.step_mode
+				{
+					StepMode::None => false,
+					StepMode::Into => true,
+					StepMode::Over { addr: ret_addr, reg_s: ret_s } => addr == ret_addr && reg_s == ret_s,
+					StepMode::Out { reg_s: out_s } =>
+						(self.instr_opcode == OPCODE_RTS || self.instr_opcode == OPCODE_RTI) && reg_s > out_s
+				};
+				
+				self.instr_addr = Some(addr);
+				self.instr_opcode = core.cpu.opcode;
+				self.instr_reg_s = reg_s;
+				
+				stop = stop
+					.or_else(|| self.find_hit(core, access, WATCH_EXECUTE)
+						.map(|index| StopReason::Breakpoint { index, addr }))
+					.or(if step_done { Some(StopReason::Step) } else { None });
+			}
+		}
+		
+		self.finish_check(stop)
+	}
+	
+	
+	pub fn check_ppu(&mut self, core: &Core) -> Option<StopReason>
+	{
+		let mut stop = self.check_accesses(core);
+		
+		if let Some(scanline) = self.target_scanline
+		{
+			if core.ppu.get_scanline() == scanline && core.ppu.get_dot() == 0
+				{ stop = stop.or(Some(StopReason::Scanline(scanline))); }
+		}
+		
+		self.finish_check(stop)
+	}
+	
+	
+	fn finish_check(&mut self, stop: Option<StopReason>) -> Option<StopReason>
+	{
+		if stop.is_some()
+			{ self.cancel_step(); }
+			
+		stop
+	}
+	
+	
+	fn check_accesses(&mut self, core: &Core) -> Option<StopReason>
+	{
+		if self.accesses.is_empty()
+			{ return None; }
+			
+		let accesses: Vec<Access> = self.accesses.drain(..).collect();
+		
+		for access in accesses
+		{
+			let flag = if access.write { WATCH_WRITE } else { WATCH_READ };
+			
+			if let Some(index) = self.find_hit(core, access, flag)
+			{
+				return Some(StopReason::Watchpoint
+				{
+					index,
+					space: access.space,
+					addr: access.addr,
+					value: access.value,
+					write: access.write
+				});
+			}
+		}
+		
+		None
+	}
+	
+	
+	fn find_hit(&self, core: &Core, access: Access, flag: u8) -> Option<usize>
+	{
+		for (index, breakpoint) in self.breakpoints.iter().enumerate()
+		{
+			let breakpoint = match *breakpoint
+			{
+				Some(ref breakpoint) => breakpoint,
+				None => continue
+			};
+			
+			if !breakpoint.enabled || breakpoint.space != access.space || (breakpoint.flags & flag) == 0
+				{ continue; }
+				
+			if access.addr < breakpoint.addr_start || access.addr > breakpoint.addr_end
+				{ continue; }
+				
+			let triggered = match breakpoint.condition
+			{
+				Some(ref expr) =>
+				{
+					// Report the address of the instruction being executed, not the fetch position
+					let pc = self.instr_addr.unwrap_or(core.cpu.reg_pc);
+					expr.eval(&DebuggerContext { core, access, pc }) != 0
+				}
+				
+				None => true
+			};
+			
+			if triggered
+				{ return Some(index); }
+		}
+		
+		None
+	}
+	
+	
+	fn refresh_watch_flags(&mut self)
+	{
+		let watching = |space| self.breakpoints.iter().any(|breakpoint| match *breakpoint
+		{
+			Some(ref breakpoint) =>
+				breakpoint.enabled && breakpoint.space == space && (breakpoint.flags & (WATCH_READ | WATCH_WRITE)) != 0,
+				
+			None => false
+		});
+		
+		let watch_cpu = watching(AddressSpace::Cpu);
+		let watch_ppu = watching(AddressSpace::Ppu);
+		
+		self.watch_cpu = watch_cpu;
+		self.watch_ppu = watch_ppu;
+		
+		if !self.watch_cpu && !self.watch_ppu
+			{ self.accesses.clear(); }
+	}
+}
+
+
+impl<'a> ExpressionContext for DebuggerContext<'a>
+{
+	fn get_variable(&self, var: Variable) -> i64
+	{
+		let cpu = &self.core.cpu;
+		
+		match var
+		{
+			Variable::A => cpu.reg_a as i64,
+			Variable::X => cpu.reg_x as i64,
+			Variable::Y => cpu.reg_y as i64,
+			Variable::S => cpu.reg_s as i64,
+			Variable::P => cpu.reg_p as i64,
+			Variable::PC => self.pc as i64,
+			Variable::FlagC => (cpu.reg_p & 0x01 != 0) as i64,
+			Variable::FlagZ => (cpu.reg_p & 0x02 != 0) as i64,
+			Variable::FlagI => (cpu.reg_p & 0x04 != 0) as i64,
+			Variable::FlagD => (cpu.reg_p & 0x08 != 0) as i64,
+			Variable::FlagV => (cpu.reg_p & 0x40 != 0) as i64,
+			Variable::FlagN => (cpu.reg_p & 0x80 != 0) as i64,
+			Variable::Scanline => self.core.ppu.get_scanline() as i64,
+			Variable::Dot => self.core.ppu.get_dot() as i64,
+			Variable::Frame => self.core.ppu.get_frame() as i64,
+			Variable::Cycle => cpu.clocks as i64,
+			Variable::Addr => self.access.addr as i64,
+			Variable::Value => self.access.value as i64
+		}
+	}
+	
+	
+	fn read_memory(&self, addr: u16) -> u8
+	{
+		self.core.peek_cpu(addr)
+	}
+}
\ No newline at end of file
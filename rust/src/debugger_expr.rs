@@ -0,0 +1,367 @@
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression
+{
+	Literal(i64),
+	Variable(Variable),
+	Memory(Box<Expression>),
+	Unary(UnaryOp, Box<Expression>),
+	Binary(BinaryOp, Box<Expression>, Box<Expression>)
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Variable
+{
+	A, X, Y, S, P, PC,
+	FlagC, FlagZ, FlagI, FlagD, FlagV, FlagN,
+	Scanline, Dot, Frame, Cycle,
+	Addr, Value
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp
+{
+	Neg, Not, BitNot
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp
+{
+	Or, And,
+	Eq, Ne, Lt, Le, Gt, Ge,
+	BitOr, BitXor, BitAnd,
+	Add, Sub
+}
+
+
+pub trait ExpressionContext
+{
+	fn get_variable(&self, var: Variable) -> i64;
+	fn read_memory(&self, addr: u16) -> u8;
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token
+{
+	Number(i64),
+	Identifier(String),
+	Symbol(&'static str)
+}
+
+
+static SYMBOLS: [&str; 21] =
+[
+	"==", "!=", "<=", ">=", "&&", "||",
+	"<", ">", "&", "|", "^", "+", "-", "!", "~", "(", ")", "[", "]", "=", "$"
+];
+
+
+impl Expression
+{
+	pub fn parse(src: &str) -> Result<Expression, String>
+	{
+		Expression::parse_with_labels(src, &|_| None)
+	}
+	
+	
+	// Identifiers that aren't registers or variables are looked up as labels
+	pub fn parse_with_labels(src: &str, labels: &Fn(&str) -> Option<u16>) -> Result<Expression, String>
+	{
+		let tokens = tokenize(src)?;
+		let mut parser = Parser { tokens: &tokens, index: 0, labels };
+		
+		let expr = parser.parse_or()?;
+		
+		if parser.index < tokens.len()
+			{ return Err(format!("unexpected `{:?}`", tokens[parser.index])); }
+			
+		Ok(expr)
+	}
+	
+	
+	pub fn eval(&self, ctx: &ExpressionContext) -> i64
+	{
+		match *self
+		{
+			Expression::Literal(val) => val,
+			Expression::Variable(var) => ctx.get_variable(var),
+			Expression::Memory(ref addr) => ctx.read_memory(addr.eval(ctx) as u16) as i64,
+			
+			Expression::Unary(op, ref inner) =>
+			{
+				let val = inner.eval(ctx);
+				match op
+				{
+					UnaryOp::Neg => val.wrapping_neg(),
+					UnaryOp::Not => (val == 0) as i64,
+					UnaryOp::BitNot => !val
+				}
+			}
+			
+			Expression::Binary(op, ref lhs, ref rhs) =>
+			{
+				// Short-circuit the logical operators
+				match op
+				{
+					BinaryOp::Or => return (lhs.eval(ctx) != 0 || rhs.eval(ctx) != 0) as i64,
+					BinaryOp::And => return (lhs.eval(ctx) != 0 && rhs.eval(ctx) != 0) as i64,
+					_ => { }
+				}
+				
+				let a = lhs.eval(ctx);
+				let b = rhs.eval(ctx);
+				
+				match op
+				{
+					BinaryOp::Eq => (a == b) as i64,
+					BinaryOp::Ne => (a != b) as i64,
+					BinaryOp::Lt => (a < b) as i64,
+					BinaryOp::Le => (a <= b) as i64,
+					BinaryOp::Gt => (a > b) as i64,
+					BinaryOp::Ge => (a >= b) as i64,
+					BinaryOp::BitOr => a | b,
+					BinaryOp::BitXor => a ^ b,
+					BinaryOp::BitAnd => a & b,
+					BinaryOp::Add => a.wrapping_add(b),
+					BinaryOp::Sub => a.wrapping_sub(b),
+					BinaryOp::Or | BinaryOp::And => unreachable!()
+				}
+			}
+		}
+	}
+}
+
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String>
+{
+	let chars: Vec<char> = src.chars().collect();
+	let mut tokens = Vec::new();
+	let mut i = 0;
+	
+	while i < chars.len()
+	{
+		let c = chars[i];
+		
+		if c.is_whitespace()
+			{ i += 1; }
+			
+		else if c == '$' || c == '%' || c.is_ascii_digit()
+		{
+			let radix = match c
+			{
+				'$' => { i += 1; 16 }
+				'%' => { i += 1; 2 }
+				_ if c == '0' && i + 1 < chars.len() && (chars[i + 1] == 'x' || chars[i + 1] == 'X') => { i += 2; 16 }
+				_ => 10
+			};
+			
+			let start = i;
+			while i < chars.len() && chars[i].is_digit(radix)
+				{ i += 1; }
+				
+			let digits: String = chars[start..i].iter().collect();
+			match i64::from_str_radix(&digits, radix)
+			{
+				Ok(val) => tokens.push(Token::Number(val)),
+				Err(_) => return Err(format!("invalid number at column {}", start))
+			}
+		}
+		
+		else if c.is_ascii_alphabetic() || c == '_'
+		{
+			let start = i;
+			while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_')
+				{ i += 1; }
+				
+			tokens.push(Token::Identifier(chars[start..i].iter().collect::<String>()));
+		}
+		
+		else
+		{
+			let rest: String = chars[i..].iter().take(2).collect();
+			
+			match SYMBOLS.iter().find(|sym| rest.starts_with(*sym))
+			{
+				Some(sym) =>
+				{
+					i += sym.len();
+					tokens.push(Token::Symbol(sym));
+				}
+				
+				None => return Err(format!("unexpected `{}` at column {}", c, i))
+			}
+		}
+	}
+	
+	Ok(tokens)
+}
+
+
+struct Parser<'a>
+{
+	tokens: &'a [Token],
+	index: usize,
+	labels: &'a Fn(&str) -> Option<u16>
+}
+
+
+impl<'a> Parser<'a>
+{
+	fn peek_symbol(&self) -> Option<&'static str>
+	{
+		match self.tokens.get(self.index)
+		{
+			Some(&Token::Symbol(sym)) => Some(sym),
+			_ => None
+		}
+	}
+	
+	
+	fn expect_symbol(&mut self, sym: &str) -> Result<(), String>
+	{
+		if self.peek_symbol() == Some(sym)
+		{
+			self.index += 1;
+			Ok(())
+		}
+		else
+			{ Err(format!("expected `{}`", sym)) }
+	}
+	
+	
+	fn parse_binary_level(&mut self, ops: &[(&str, BinaryOp)], next: fn(&mut Parser<'a>) -> Result<Expression, String>) -> Result<Expression, String>
+	{
+		let mut lhs = next(self)?;
+		
+		while let Some(sym) = self.peek_symbol()
+		{
+			let op = match ops.iter().find(|&&(op_sym, _)| op_sym == sym)
+			{
+				Some(&(_, op)) => op,
+				None => break
+			};
+			
+			self.index += 1;
+			let rhs = next(self)?;
+			lhs = Expression::Binary(op, Box::new(lhs), Box::new(rhs));
+		}
+		
+		Ok(lhs)
+	}
+	
+	
+	fn parse_or(&mut self) -> Result<Expression, String>
+	{
+		self.parse_binary_level(&[("||", BinaryOp::Or)], Parser::parse_and)
+	}
+	
+	
+	fn parse_and(&mut self) -> Result<Expression, String>
+	{
+		self.parse_binary_level(&[("&&", BinaryOp::And)], Parser::parse_comparison)
+	}
+	
+	
+	fn parse_comparison(&mut self) -> Result<Expression, String>
+	{
+		self.parse_binary_level(&[
+			("==", BinaryOp::Eq), ("=", BinaryOp::Eq), ("!=", BinaryOp::Ne),
+			("<=", BinaryOp::Le), (">=", BinaryOp::Ge), ("<", BinaryOp::Lt), (">", BinaryOp::Gt)],
+			Parser::parse_bitwise)
+	}
+	
+	
+	fn parse_bitwise(&mut self) -> Result<Expression, String>
+	{
+		self.parse_binary_level(&[("|", BinaryOp::BitOr), ("^", BinaryOp::BitXor), ("&", BinaryOp::BitAnd)], Parser::parse_additive)
+	}
+	
+	
+	fn parse_additive(&mut self) -> Result<Expression, String>
+	{
+		self.parse_binary_level(&[("+", BinaryOp::Add), ("-", BinaryOp::Sub)], Parser::parse_unary)
+	}
+	
+	
+	fn parse_unary(&mut self) -> Result<Expression, String>
+	{
+		let op = match self.peek_symbol()
+		{
+			Some("-") => UnaryOp::Neg,
+			Some("!") => UnaryOp::Not,
+			Some("~") => UnaryOp::BitNot,
+			_ => return self.parse_primary()
+		};
+		
+		self.index += 1;
+		Ok(Expression::Unary(op, Box::new(self.parse_unary()?)))
+	}
+	
+	
+	fn parse_primary(&mut self) -> Result<Expression, String>
+	{
+		let token = match self.tokens.get(self.index)
+		{
+			Some(token) => token.clone(),
+			None => return Err("unexpected end of expression".to_string())
+		};
+		
+		self.index += 1;
+		
+		match token
+		{
+			Token::Number(val) => Ok(Expression::Literal(val)),
+			
+			Token::Identifier(name) =>
+			{
+				let var = match name.to_ascii_uppercase().as_ref()
+				{
+					"A" => Variable::A,
+					"X" => Variable::X,
+					"Y" => Variable::Y,
+					"S" | "SP" => Variable::S,
+					"P" => Variable::P,
+					"PC" => Variable::PC,
+					"C" => Variable::FlagC,
+					"Z" => Variable::FlagZ,
+					"I" => Variable::FlagI,
+					"D" => Variable::FlagD,
+					"V" => Variable::FlagV,
+					"N" => Variable::FlagN,
+					"SCANLINE" => Variable::Scanline,
+					"DOT" => Variable::Dot,
+					"FRAME" => Variable::Frame,
+					"CYCLE" => Variable::Cycle,
+					"ADDR" => Variable::Addr,
+					"VALUE" => Variable::Value,
+					_ => return match (self.labels)(&name)
+					{
+						Some(addr) => Ok(Expression::Literal(addr as i64)),
+						None => Err(format!("unknown identifier `{}`", name))
+					}
+				};
+				
+				Ok(Expression::Variable(var))
+			}
+			
+			Token::Symbol("(") =>
+			{
+				let inner = self.parse_or()?;
+				self.expect_symbol(")")?;
+				Ok(inner)
+			}
+			
+			Token::Symbol("[") =>
+			{
+				let inner = self.parse_or()?;
+				self.expect_symbol("]")?;
+				Ok(Expression::Memory(Box::new(inner)))
+			}
+			
+			Token::Symbol(sym) => Err(format!("unexpected `{}`", sym))
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,580 @@
+use Core;
+use Debugger;
+use cpu::CpuEvent;
+use debugger::{AddressSpace, StopReason, WATCH_READ, WATCH_WRITE};
+use std::io;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+// Instructions to run between checks for a break request from the client
+const INTERRUPT_POLL_INTERVAL: usize = 10000;
+
+const REGISTER_NUM: usize = 6;
+
+// Advertised to the client; memory goes over the wire as two hex digits per byte
+const PACKET_SIZE: usize = 0x1000;
+const MAX_MEMORY_LENGTH: usize = PACKET_SIZE / 2;
+
+const TARGET_XML: &str = "<?xml version=\"1.0\"?>\
+<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+<target version=\"1.0\">\
+<feature name=\"org.mahnes.6502\">\
+<reg name=\"a\" bitsize=\"8\" regnum=\"0\"/>\
+<reg name=\"x\" bitsize=\"8\" regnum=\"1\"/>\
+<reg name=\"y\" bitsize=\"8\" regnum=\"2\"/>\
+<reg name=\"p\" bitsize=\"8\" regnum=\"3\"/>\
+<reg name=\"sp\" bitsize=\"8\" regnum=\"4\"/>\
+<reg name=\"pc\" bitsize=\"16\" regnum=\"5\" type=\"code_ptr\"/>\
+</feature>\
+</target>";
+
+
+struct StubBreakpoint
+{
+	kind: u8,
+	addr: u16,
+	length: u16,
+	index: usize
+}
+
+
+// Serves one GDB remote serial protocol connection; the core is always
+// stopped right after an opcode fetch, so the fetched address is the PC
+pub struct GdbStub
+{
+	stream: TcpStream,
+	no_ack: bool,
+	breakpoints: Vec<StubBreakpoint>,
+	last_stop: String
+}
+
+
+impl GdbStub
+{
+	pub fn new(stream: TcpStream) -> GdbStub
+	{
+		// Packets are small and strictly request/response
+		let _ = stream.set_nodelay(true);
+		
+		GdbStub
+		{
+			stream,
+			no_ack: false,
+			breakpoints: Vec::new(),
+			last_stop: format!("S{:02x}", SIGTRAP)
+		}
+	}
+	
+	
+	// Returns once the client detaches, kills the session or disconnects
+	pub fn run(&mut self, core: &mut Core) -> io::Result<()>
+	{
+		if core.debugger.is_none()
+			{ core.debugger = Some(Box::new(Debugger::new())); }
+			
+		if !is_at_instruction(core)
+			{ core.step_instruction(); }
+			
+		let result = self.serve(core);
+		
+		if let Some(ref mut debugger) = core.debugger
+		{
+			for breakpoint in self.breakpoints.drain(..)
+				{ debugger.remove(breakpoint.index); }
+		}
+		
+		result
+	}
+	
+	
+	fn serve(&mut self, core: &mut Core) -> io::Result<()>
+	{
+		while let Some(packet) = self.read_packet()?
+		{
+			let reply = match packet.chars().next()
+			{
+				Some('?') => self.last_stop.clone(),
+				Some('g') => read_registers(core),
+				Some('G') => write_registers(core, &packet[1..]),
+				Some('p') => read_register(core, &packet[1..]),
+				Some('P') => write_register(core, &packet[1..]),
+				Some('m') => read_memory(core, &packet[1..]),
+				Some('M') => write_memory(core, &packet[1..]),
+				Some('Z') => self.insert_breakpoint(core, &packet[1..]),
+				Some('z') => self.remove_breakpoint(core, &packet[1..]),
+				Some('c') => self.resume(core, &packet[1..], false)?,
+				Some('s') => self.resume(core, &packet[1..], true)?,
+				Some('H') | Some('T') => "OK".to_string(),
+				Some('v') => self.handle_v_packet(core, &packet)?,
+				Some('q') | Some('Q') => self.handle_query(&packet),
+				
+				Some('D') =>
+				{
+					self.write_packet("OK")?;
+					return Ok(());
+				}
+				
+				Some('k') => return Ok(()),
+				_ => String::new()
+			};
+			
+			self.write_packet(&reply)?;
+		}
+		
+		Ok(())
+	}
+	
+	
+	fn handle_v_packet(&mut self, core: &mut Core, packet: &str) -> io::Result<String>
+	{
+		if packet == "vCont?"
+			{ return Ok("vCont;c;s".to_string()); }
+			
+		// Only one thread exists, so the first action applies
+		if let Some(actions) = packet.strip_prefix("vCont;")
+		{
+			let action = actions.split(';').next().unwrap_or("");
+			
+			return match action.chars().next()
+			{
+				Some('c') | Some('C') => self.resume(core, "", false),
+				Some('s') | Some('S') => self.resume(core, "", true),
+				_ => Ok("E01".to_string())
+			};
+		}
+		
+		Ok(String::new())
+	}
+	
+	
+	fn handle_query(&mut self, packet: &str) -> String
+	{
+		if packet.starts_with("qSupported")
+			{ return format!("PacketSize={:x};qXfer:features:read+;QStartNoAckMode+", PACKET_SIZE); }
+			
+		if let Some(args) = packet.strip_prefix("qXfer:features:read:target.xml:")
+			{ return read_target_xml(args); }
+			
+		match packet
+		{
+			"QStartNoAckMode" =>
+			{
+				self.no_ack = true;
+				"OK".to_string()
+			}
+			
+			"qAttached" => "1".to_string(),
+			"qC" => "QC1".to_string(),
+			"qfThreadInfo" => "m1".to_string(),
+			"qsThreadInfo" => "l".to_string(),
+			_ => String::new()
+		}
+	}
+	
+	
+	fn insert_breakpoint(&mut self, core: &mut Core, args: &str) -> String
+	{
+		let (kind, addr, length) = match parse_breakpoint_args(args)
+		{
+			Some(parsed) => parsed,
+			None => return "E01".to_string()
+		};
+		
+		let debugger = core.debugger.as_mut().unwrap();
+		let addr_end = addr.wrapping_add(length.max(1) - 1);
+		
+		let index = match kind
+		{
+			0 | 1 => debugger.add_breakpoint(addr),
+			2 => debugger.add_watchpoint(AddressSpace::Cpu, addr, addr_end, WATCH_WRITE),
+			3 => debugger.add_watchpoint(AddressSpace::Cpu, addr, addr_end, WATCH_READ),
+			4 => debugger.add_watchpoint(AddressSpace::Cpu, addr, addr_end, WATCH_READ | WATCH_WRITE),
+			_ => return String::new()
+		};
+		
+		self.breakpoints.push(StubBreakpoint { kind, addr, length, index });
+		"OK".to_string()
+	}
+	
+	
+	fn remove_breakpoint(&mut self, core: &mut Core, args: &str) -> String
+	{
+		let (kind, addr, length) = match parse_breakpoint_args(args)
+		{
+			Some(parsed) => parsed,
+			None => return "E01".to_string()
+		};
+		
+		let position = self.breakpoints.iter().position(|breakpoint|
+			breakpoint.kind == kind && breakpoint.addr == addr && (kind <= 1 || breakpoint.length == length));
+			
+		if let Some(position) = position
+		{
+			let breakpoint = self.breakpoints.remove(position);
+			core.debugger.as_mut().unwrap().remove(breakpoint.index);
+		}
+		
+		"OK".to_string()
+	}
+	
+	
+	fn resume(&mut self, core: &mut Core, args: &str, step: bool) -> io::Result<String>
+	{
+		if !args.is_empty()
+		{
+			match u16::from_str_radix(args, 16)
+			{
+				Ok(addr) => set_pc(core, addr),
+				Err(_) => return Ok("E01".to_string())
+			}
+		}
+		
+		let mut instructions = 0;
+		
+		let reply = loop
+		{
+			if let Some(reason) = core.step_instruction()
+				{ break self.format_stop(core, reason); }
+				
+			if step
+				{ break format!("S{:02x}", SIGTRAP); }
+				
+			instructions += 1;
+			if instructions % INTERRUPT_POLL_INTERVAL == 0 && self.poll_interrupt()?
+				{ break format!("S{:02x}", SIGINT); }
+		};
+		
+		self.last_stop = reply.clone();
+		Ok(reply)
+	}
+	
+	
+	fn format_stop(&self, core: &Core, reason: StopReason) -> String
+	{
+		match reason
+		{
+			StopReason::Watchpoint { index, addr, .. } =>
+			{
+				let flags = match core.debugger.as_ref().and_then(|debugger| debugger.breakpoints.get(index))
+				{
+					Some(Some(breakpoint)) => breakpoint.flags,
+					_ => 0
+				};
+				
+				let name = match flags & (WATCH_READ | WATCH_WRITE)
+				{
+					WATCH_WRITE => "watch",
+					WATCH_READ => "rwatch",
+					_ => "awatch"
+				};
+				
+				format!("T{:02x}{}:{:04x};", SIGTRAP, name, addr)
+			}
+			
+			_ => format!("S{:02x}", SIGTRAP)
+		}
+	}
+	
+	
+	fn poll_interrupt(&mut self) -> io::Result<bool>
+	{
+		let mut byte = [0];
+		
+		self.stream.set_nonblocking(true)?;
+		let result = self.stream.read(&mut byte);
+		self.stream.set_nonblocking(false)?;
+		
+		match result
+		{
+			Ok(1) => Ok(byte[0] == 0x03),
+			Ok(_) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "client disconnected")),
+			Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Ok(false),
+			Err(err) => Err(err)
+		}
+	}
+	
+	
+	fn read_byte(&mut self) -> io::Result<Option<u8>>
+	{
+		let mut byte = [0];
+		
+		match self.stream.read(&mut byte)?
+		{
+			0 => Ok(None),
+			_ => Ok(Some(byte[0]))
+		}
+	}
+	
+	
+	fn read_packet(&mut self) -> io::Result<Option<String>>
+	{
+		loop
+		{
+			match self.read_byte()?
+			{
+				None => return Ok(None),
+				
+				// A break request while already stopped just reports the stop again
+				Some(0x03) => return Ok(Some("?".to_string())),
+				
+				Some(b'$') => { }
+				Some(_) => continue
+			}
+			
+			let mut data = Vec::new();
+			loop
+			{
+				match self.read_byte()?
+				{
+					None => return Ok(None),
+					Some(b'#') => break,
+					Some(byte) => data.push(byte)
+				}
+			}
+			
+			let checksum_hex = [self.read_byte()?.unwrap_or(0), self.read_byte()?.unwrap_or(0)];
+			let checksum = String::from_utf8_lossy(&checksum_hex).to_string();
+			let valid = u8::from_str_radix(&checksum, 16).ok() == Some(compute_checksum(&data));
+			
+			if !self.no_ack
+				{ self.stream.write_all(if valid { b"+" } else { b"-" })?; }
+				
+			if valid
+				{ return Ok(Some(String::from_utf8_lossy(&data).to_string())); }
+		}
+	}
+	
+	
+	fn write_packet(&mut self, data: &str) -> io::Result<()>
+	{
+		let packet = format!("${}#{:02x}", data, compute_checksum(data.as_bytes()));
+		
+		loop
+		{
+			self.stream.write_all(packet.as_bytes())?;
+			
+			if self.no_ack
+				{ return Ok(()); }
+				
+			// Resend on '-', give up quietly if the client goes away
+			match self.read_byte()?
+			{
+				Some(b'+') | None => return Ok(()),
+				Some(b'-') => continue,
+				Some(_) => return Ok(())
+			}
+		}
+	}
+}
+
+
+fn is_at_instruction(core: &Core) -> bool
+{
+	matches!(core.cpu.last_event, CpuEvent::FetchOpcode(_))
+}
+
+
+// The opcode has already been fetched, so redirect the fetch instead of just moving PC
+fn set_pc(core: &mut Core, addr: u16)
+{
+	core.cpu.opcode = core.peek_cpu(addr);
+	core.cpu.reg_pc = addr.wrapping_add(1);
+	core.cpu.last_event = CpuEvent::FetchOpcode(addr);
+}
+
+
+fn get_register(core: &Core, index: usize) -> Option<u16>
+{
+	let cpu = &core.cpu;
+	
+	match index
+	{
+		0 => Some(cpu.reg_a as u16),
+		1 => Some(cpu.reg_x as u16),
+		2 => Some(cpu.reg_y as u16),
+		3 => Some(cpu.reg_p as u16),
+		4 => Some(cpu.reg_s as u16),
+		5 => Some(cpu.get_instruction_addr()),
+		_ => None
+	}
+}
+
+
+fn set_register(core: &mut Core, index: usize, val: u16)
+{
+	match index
+	{
+		0 => core.cpu.reg_a = val as u8,
+		1 => core.cpu.reg_x = val as u8,
+		2 => core.cpu.reg_y = val as u8,
+		3 => core.cpu.reg_p = val as u8,
+		4 => core.cpu.reg_s = val as u8,
+		5 => set_pc(core, val),
+		_ => { }
+	}
+}
+
+
+fn get_register_size(index: usize) -> usize
+{
+	if index == 5 { 2 } else { 1 }
+}
+
+
+fn read_registers(core: &Core) -> String
+{
+	(0..REGISTER_NUM).map(|index| encode_le(get_register(core, index).unwrap(), get_register_size(index))).collect()
+}
+
+
+fn write_registers(core: &mut Core, hex: &str) -> String
+{
+	let bytes = match decode_hex(hex)
+	{
+		Some(ref bytes) if bytes.len() >= 7 => bytes.clone(),
+		_ => return "E01".to_string()
+	};
+	
+	for (index, &byte) in bytes[0..5].iter().enumerate()
+		{ set_register(core, index, byte as u16); }
+		
+	set_register(core, 5, bytes[5] as u16 | ((bytes[6] as u16) << 8));
+	"OK".to_string()
+}
+
+
+fn read_register(core: &Core, args: &str) -> String
+{
+	match usize::from_str_radix(args, 16).ok().and_then(|index| get_register(core, index).map(|val| (index, val)))
+	{
+		Some((index, val)) => encode_le(val, get_register_size(index)),
+		None => "E01".to_string()
+	}
+}
+
+
+fn write_register(core: &mut Core, args: &str) -> String
+{
+	let mut parts = args.splitn(2, '=');
+	
+	let index = parts.next().and_then(|index| usize::from_str_radix(index, 16).ok());
+	let bytes = parts.next().and_then(decode_hex);
+	
+	match (index, bytes)
+	{
+		(Some(index), Some(bytes)) if index < REGISTER_NUM && bytes.len() == get_register_size(index) =>
+		{
+			let val = bytes.iter().rev().fold(0, |val, &byte| (val << 8) | byte as u16);
+			set_register(core, index, val);
+			"OK".to_string()
+		}
+		
+		_ => "E01".to_string()
+	}
+}
+
+
+fn read_memory(core: &Core, args: &str) -> String
+{
+	match parse_addr_length(args)
+	{
+		Some((addr, length)) if length <= MAX_MEMORY_LENGTH =>
+			(0..length).map(|i| format!("{:02x}", core.peek_cpu(addr.wrapping_add(i as u16)))).collect(),
+			
+		_ => "E01".to_string()
+	}
+}
+
+
+// Writes go around the bus, so I/O registers can't be changed this way
+fn write_memory(core: &mut Core, args: &str) -> String
+{
+	let mut parts = args.splitn(2, ':');
+	
+	let range = parts.next().and_then(parse_addr_length);
+	let bytes = parts.next().and_then(decode_hex);
+	
+	match (range, bytes)
+	{
+		(Some((addr, length)), Some(bytes)) if length <= MAX_MEMORY_LENGTH && bytes.len() == length =>
+		{
+			for (i, &byte) in bytes.iter().enumerate()
+				{ core.poke_cpu(addr.wrapping_add(i as u16), byte); }
+				
+			"OK".to_string()
+		}
+		
+		_ => "E01".to_string()
+	}
+}
+
+
+fn read_target_xml(args: &str) -> String
+{
+	let (offset, length) = match parse_addr_length(args)
+	{
+		Some((offset, length)) if length < PACKET_SIZE => (offset as usize, length),
+		_ => return "E01".to_string()
+	};
+	
+	if offset >= TARGET_XML.len()
+		{ return "l".to_string(); }
+		
+	let end = (offset + length).min(TARGET_XML.len());
+	let prefix = if end == TARGET_XML.len() { "l" } else { "m" };
+	
+	format!("{}{}", prefix, &TARGET_XML[offset..end])
+}
+
+
+fn parse_breakpoint_args(args: &str) -> Option<(u8, u16, u16)>
+{
+	let mut parts = args.splitn(3, ',');
+	
+	let kind = parts.next()?.parse::<u8>().ok()?;
+	let addr = u16::from_str_radix(parts.next()?, 16).ok()?;
+	
+	// Breakpoint conditions after ';' are not supported
+	let length = u16::from_str_radix(parts.next()?.split(';').next()?, 16).ok()?;
+	
+	Some((kind, addr, length))
+}
+
+
+fn parse_addr_length(args: &str) -> Option<(u16, usize)>
+{
+	let mut parts = args.splitn(2, ',');
+	
+	let addr = usize::from_str_radix(parts.next()?, 16).ok()?;
+	let length = usize::from_str_radix(parts.next()?, 16).ok()?;
+	
+	if addr > 0xffff
+		{ return None; }
+		
+	Some((addr as u16, length))
+}
+
+
+fn encode_le(val: u16, size: usize) -> String
+{
+	(0..size).map(|i| format!("{:02x}", (val >> (i * 8)) & 0xff)).collect()
+}
+
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>>
+{
+	if hex.len() % 2 != 0
+		{ return None; }
+		
+	(0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+
+fn compute_checksum(data: &[u8]) -> u8
+{
+	data.iter().fold(0, |sum: u8, &byte| sum.wrapping_add(byte))
+}
\ No newline at end of file
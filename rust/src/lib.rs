@@ -1,9 +1,45 @@
+// The code is written in the 2015 style (bare `Fn` trait objects, `{ }` one-line branches,
+// `static mut` for the wasm bindings), so these lints are silenced crate-wide instead of
+// restyling it
+#![allow(bare_trait_objects)]
+#![allow(static_mut_refs)]
+#![allow(clippy::suspicious_else_formatting)]
+#![allow(clippy::identity_op)]
+#![allow(clippy::collapsible_if)]
+#![allow(clippy::collapsible_else_if)]
+#![allow(clippy::manual_range_contains)]
+#![allow(clippy::manual_range_patterns)]
+#![allow(clippy::manual_is_multiple_of)]
+#![allow(clippy::redundant_field_names)]
+#![allow(clippy::new_without_default)]
+// `map_or` stands in for `is_none_or` and `is_some_and` so older toolchains can build the crate
+#![allow(clippy::unnecessary_map_or)]
+#![cfg_attr(test, allow(unused_assignments, clippy::useless_transmute, clippy::manual_memcpy))]
+
+
 mod core;
 mod cpu;
 mod ppu;
 mod rom_ines;
 mod cartridge;
 mod cartridge_nrom;
+mod palette;
+mod palette_ram;
+mod ntsc;
+mod viewer;
+mod debugger;
+mod debugger_expr;
+mod trace;
+mod blargg;
+mod png;
+mod cdl;
+mod symbols;
+mod rom_dis;
+mod gdb;
+mod movie;
+mod cheats;
+mod ram_search;
+mod processor_tests;
 mod wasm;
 
 
@@ -11,12 +47,29 @@ pub mod cpu_opcodes;
 pub mod cpu_dis;
 
 
-pub use core::Core;
+pub use core::{Core, PowerOnRam};
 pub use cartridge::Cartridge;
 pub use cartridge_nrom::CartridgeNROM;
-pub use cpu::Cpu;
+pub use cpu::{Cpu, CpuEvent, CpuState, CpuVariant};
 pub use ppu::Ppu;
 pub use rom_ines::RomINES;
+pub use palette::{Palette, NtscPaletteParams};
+pub use palette_ram::PaletteRam;
+pub use ntsc::{NtscFilter, NTSC_SCREEN_WIDTH};
+pub use viewer::SpriteInfo;
+pub use debugger::{Debugger, Breakpoint, StopReason, AddressSpace, WATCH_READ, WATCH_WRITE, WATCH_EXECUTE};
+pub use debugger_expr::Expression;
+pub use trace::{TraceLogger, TraceFormat};
+pub use blargg::{run_blargg_rom, BlarggResult};
+pub use png::write_png;
+pub use cdl::{CodeDataLogger, CDL_PRG_CODE, CDL_PRG_DATA, CDL_PRG_INDIRECT_CODE, CDL_PRG_INDIRECT_DATA, CDL_CHR_RENDERED, CDL_CHR_READ};
+pub use symbols::{SymbolTable, Symbol};
+pub use rom_dis::{RomDisassembly, XRef, XRefKind};
+pub use gdb::GdbStub;
+pub use movie::{Movie, MovieFrame, MovieMode, MOVIE_SOFT_RESET, MOVIE_POWER};
+pub use cheats::{Cheat, CheatKind};
+pub use ram_search::{RamSearch, SearchSize, SearchFilter, SearchResult};
+pub use processor_tests::{run_processor_tests, ProcessorTestResults};
 
 
 #[test]
@@ -105,56 +158,1665 @@ fn test_cpu_nestest()
 
 
 #[test]
-fn test_core_bkgcolor()
+fn test_cpu_nestest_log()
 {
-	use std::fs::File;
-	use std::io::Read;
+	use std::fs;
 	
-	let mut file = File::open("../examples/bkgcolor.nes").unwrap();
-	let mut buffer = Vec::<u8>::new();
-	file.read_to_end(&mut buffer).unwrap();
+	let rom = fs::read("../examples/nestest.nes").unwrap();
 	
+	let ines = RomINES::new(&rom);
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	core.cpu.set_pc(0xc000);
+	core.cpu.clocks = 7;
 	
-	let ines = RomINES::new(&buffer);
-	let cartridge = ines.make_cartridge().unwrap();
+	// The official opcode tests end where the first unofficial one starts, leaving the
+	// number of any failed test in $02 and $03
+	let mut instructions = 0;
+	loop
+	{
+		core.run();
+		
+		if let CpuEvent::FetchOpcode(addr) = core.cpu.last_event
+		{
+			if addr == 0xc6bd
+				{ break; }
+				
+			instructions += 1;
+			assert!(instructions < 6000);
+		}
+	}
+	
+	assert!(instructions == 5003);
+	assert!(core.ram[0x02] == 0 && core.ram[0x03] == 0);
+	
+	// The golden log from the nestest package also checks every register and the timing
+	match fs::read_to_string("../examples/nestest.log")
+	{
+		Ok(log) => assert!(compare_nestest_log(&rom, &log).unwrap() == 5003),
+		Err(_) => println!("nestest: ../examples/nestest.log not found, only checking the result codes")
+	}
+}
+
+
+#[test]
+fn test_cpu_nestest_log_synthetic()
+{
+	use std::fs;
+	
+	let rom = fs::read("../examples/nestest.nes").unwrap();
+	
+	let log =
+		"C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7\n\
+		C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 30 CYC:10\n\
+		C5F7  86 00     STX $00 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 36 CYC:12\n\
+		C5F9  86 10     STX $10 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 45 CYC:15\n\
+		C5FB  86 11     STX $11 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 54 CYC:18\n\
+		C5FD  20 2D C7  JSR $C72D                       A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 63 CYC:21\n\
+		C72D  EA        NOP                             A:00 X:00 Y:00 P:26 SP:FB PPU:  0, 81 CYC:27\n\
+		C72E  38        SEC                             A:00 X:00 Y:00 P:26 SP:FB PPU:  0, 87 CYC:29\n\
+		C72F  B0 04     BCS $C735                       A:00 X:00 Y:00 P:27 SP:FB PPU:  0, 93 CYC:31\n";
+		
+	assert!(compare_nestest_log(&rom, log) == Ok(9));
+	
+	// Only the first differing line is reported, with each field that differs
+	let log = log.replacen("P:26 SP:FD PPU:  0, 36", "P:A4 SP:FC PPU:  0, 36", 1);
+	let err = compare_nestest_log(&rom, &log).unwrap_err();
+	
+	assert!(err.starts_with("nestest mismatch at line 3: P expected A4 got 26, SP expected FC got FD\n"));
+	assert!(err.contains("expected: C5F7  86 00     STX $00 = 00"));
+	assert!(err.contains("actual:   C5F7  86 00     STX $00 = 00"));
+}
+
 
-	let mut core = Core::new(Box::new(cartridge));
-	//let core_ptr = unsafe { std::mem::transmute::<_, *mut Core>(&mut core) };
+// Returns the number of lines that matched, or a description of the first mismatch
+#[cfg(test)]
+fn compare_nestest_log(rom: &[u8], log: &str) -> Result<usize, String>
+{
+	let ines = RomINES::new(rom);
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	core.cpu.set_pc(0xc000);
+	core.cpu.clocks = 7;
+	
+	let trace_logger = TraceLogger::new(TraceFormat::nestest());
+	let mut matched = 0;
 	
-	core.cpu.hook_execute_instr = Some(Box::new(move |cpu, addr, opcode, imm1, imm2|
+	for (line_index, expected) in log.lines().enumerate()
 	{
-		if cpu.clocks < 59670
-			{ return; }
+		// Unofficial opcodes are marked with '*' and aren't emulated
+		if expected.as_bytes().get(15) == Some(&b'*')
+		{
+			println!("nestest: stopping at unofficial opcode on line {}", line_index + 1);
+			break;
+		}
 		
-		println!("Clock {:5} | 0x{:04x} | A:{:02x} X:{:02x} Y:{:02x} S:{:02x} P:{:02x} | {}",
-			cpu.clocks,
-			addr,
-			cpu.reg_a, cpu.reg_x, cpu.reg_y, cpu.reg_s, cpu.reg_p,
-			cpu_dis::disassemble_instruction(addr, opcode, imm1, imm2));
+		let mut cycles = 0;
+		let addr = loop
+		{
+			core.run();
 			
-		/*println!();
+			if let CpuEvent::FetchOpcode(addr) = core.cpu.last_event
+				{ break addr; }
+				
+			cycles += 1;
+			if cycles >= 100
+				{ return Err(format!("nestest: no instruction fetched after line {}", line_index)); }
+		};
+		
+		let actual = trace_logger.format_line(&core, addr);
+		let expected_fields = parse_nestest_fields(expected);
+		let actual_fields = parse_nestest_fields(&actual);
 		
-		for j in 0x0..0x10
+		if expected_fields != actual_fields
 		{
-			print!("{:04x} : ", 0x100 + j * 0x10);
-			
-			for i in 0x0..0x10
+			let diff: Vec<String> = expected_fields.iter().zip(actual_fields.iter())
+				.filter(|&(e, a)| e != a)
+				.map(|(e, a)| format!("{} expected {} got {}", e.0, e.1, a.1))
+				.collect();
+				
+			return Err(format!("nestest mismatch at line {}: {}\nexpected: {}\nactual:   {}", line_index + 1, diff.join(", "), expected, actual));
+		}
+		
+		matched += 1;
+	}
+	
+	Ok(matched)
+}
+
+
+#[cfg(test)]
+fn parse_nestest_fields(line: &str) -> Vec<(&'static str, String)>
+{
+	let mut fields = vec![("PC", line.get(0..4).unwrap_or("").to_string())];
+	
+	for &(name, key) in &[("A", " A:"), ("X", " X:"), ("Y", " Y:"), ("P", " P:"), ("SP", " SP:"), ("CYC", " CYC:")]
+	{
+		let value = match line.find(key)
+		{
+			Some(pos) => line[pos + key.len()..].split_whitespace().next().unwrap_or("").to_string(),
+			None => String::new()
+		};
+		
+		fields.push((name, value));
+	}
+	
+	fields
+}
+
+
+#[test]
+fn test_core_bkgcolor()
+{
+	check_screenshot("../examples/bkgcolor.nes", 60, 0xd1c48cf2);
+}
+
+
+#[cfg(test)]
+fn check_screenshot(rom_path: &str, frames: usize, expected_hash: u32)
+{
+	use std::fs;
+	use std::path::Path;
+	
+	let buffer = fs::read(rom_path).unwrap();
+	let ines = RomINES::new(&buffer);
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	
+	for _ in 0..frames
+		{ core.run_frame(); }
+		
+	let hash = core.get_screen_hash();
+	if hash != expected_hash
+	{
+		let name = Path::new(rom_path).file_stem().unwrap().to_string_lossy().into_owned();
+		let png_path = format!("target/screenshots/{}_{:08x}.png", name, hash);
+		
+		fs::create_dir_all("target/screenshots").unwrap();
+		write_png(&mut fs::File::create(&png_path).unwrap(), 256, 240, &core.screen).unwrap();
+		
+		panic!("screenshot mismatch for {} after {} frames: expected {:08x}, got {:08x}; actual frame written to {}",
+			rom_path, frames, expected_hash, hash, png_path);
+	}
+}
+
+
+#[test]
+fn test_palette_loading()
+{
+	let mut pal64 = vec![0_u8; 64 * 3];
+	pal64[0x21 * 3 + 0] = 0x40;
+	pal64[0x21 * 3 + 1] = 0x80;
+	pal64[0x21 * 3 + 2] = 0xc0;
+	
+	let palette = Palette::from_pal(&pal64).unwrap();
+	assert!(palette.get_rgba(0x21, 0x00) == [0x40, 0x80, 0xc0, 0xff]);
+	assert!(palette.get_rgba(0x21, 0x20) == [0x40, 0x68, 0x9c, 0xff]);
+	
+	let mut pal512 = vec![0_u8; 512 * 3];
+	pal512[0x1ff * 3 + 1] = 0x12;
+	
+	let palette = Palette::from_pal(&pal512).unwrap();
+	assert!(palette.get_rgba(0x3f, 0xe0) == [0x00, 0x12, 0x00, 0xff]);
+	
+	assert!(Palette::from_pal(&[0; 100]).is_none());
+	
+	let palette = Palette::from_ntsc(&NtscPaletteParams::new());
+	assert!(palette.get_rgba(0x0f, 0) == [0x00, 0x00, 0x00, 0xff]);
+	assert!(palette.get_rgba(0x20, 0) == palette.get_rgba(0x30, 0));
+	assert!(palette.get_rgba(0x30, 0)[0] > 0xf0);
+}
+
+
+#[test]
+fn test_ntsc_filter_flat_color()
+{
+	let palette = Palette::from_ntsc(&NtscPaletteParams::new());
+	let mut filter = NtscFilter::new();
+	
+	for dot in 0..256
+		{ filter.output_dot(0, dot, 0x21, 0x00); }
+		
+	let expected = palette.get_rgba(0x21, 0x00);
+	let actual = &filter.screen[(NTSC_SCREEN_WIDTH / 2) * 4 .. (NTSC_SCREEN_WIDTH / 2) * 4 + 4];
+	
+	for channel in 0..3
+		{ assert!((expected[channel] as i32 - actual[channel] as i32).abs() <= 2); }
+}
+
+
+#[test]
+fn test_ntsc_filter_dot_crawl()
+{
+	// Alternating columns show artifact colors that depend on the frame's phase
+	let run_frames = |mask|
+	{
+		let mut filter = NtscFilter::new();
+		let mut lines = Vec::new();
+		
+		for _ in 0..3
+		{
+			for scanline in 0..240
 			{
-				let is_cur = cpu.reg_s as usize == (j * 0x10 + i);
-					
-				print!("{}", if is_cur { '[' } else { ' ' });
-				print!("{:02x}", unsafe { (*core_ptr).ram[0x100 + j * 0x10 + i] });
-				print!("{}", if is_cur { ']' } else { ' ' });
+				for dot in 0..256
+					{ filter.output_dot(scanline, dot, if dot % 2 == 0 { 0x30 } else { 0x0f }, mask); }
 			}
-				
-			println!();
+			
+			lines.push(filter.screen[0 .. NTSC_SCREEN_WIDTH * 4].to_vec());
+		}
+		
+		lines
+	};
+	
+	let rendering = run_frames(0x18);
+	assert!(rendering[0] != rendering[1] && rendering[0] == rendering[2]);
+	
+	let blank = run_frames(0x00);
+	assert!(blank[0] == blank[1] && blank[0] == blank[2]);
+}
+
+
+#[test]
+fn test_viewer_helpers()
+{
+	let mut buffer = vec![0_u8; 16 + 0x4000 + 0x2000];
+	buffer[0..4].copy_from_slice(b"NES\x1a");
+	buffer[4] = 1;
+	buffer[5] = 1;
+	
+	// Tile 1 of the first pattern table: top-left pixel uses color 3
+	buffer[16 + 0x4000 + 0x10] = 0x80;
+	buffer[16 + 0x4000 + 0x18] = 0x80;
+	
+	let ines = RomINES::new(&buffer);
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	
+	core.palram.write(0x3f00, 0x0f);
+	core.palram.write(0x3f03, 0x30);
+	core.palram.write(0x3f13, 0x16);
+	core.vram[0] = 0x01;
+	
+	core.ppu.oam[4..8].copy_from_slice(&[0x10, 0x01, 0xc0, 0x20]);
+	
+	let white = core.palette.get_rgba(0x30, 0).to_vec();
+	let black = core.palette.get_rgba(0x0f, 0).to_vec();
+	let red = core.palette.get_rgba(0x16, 0).to_vec();
+	
+	let patterns = core.render_pattern_tables(0);
+	assert!(patterns[8 * 4 .. 8 * 4 + 4] == white[..]);
+	assert!(patterns[9 * 4 .. 9 * 4 + 4] == black[..]);
+	
+	let nametables = core.render_nametables();
+	assert!(nametables[(512 + 1) * 4 .. (512 + 1) * 4 + 4] == black[..]);
+	
+	let sprites = core.get_sprite_list();
+	assert!(sprites[1].x == 0x20 && sprites[1].y == 0x10 && sprites[1].tile == 0x01);
+	assert!(sprites[1].palette_index == 0 && sprites[1].flip_h && sprites[1].flip_v);
+	
+	let sprite_sheet = core.render_sprite_sheet();
+	let flipped_pixel = (7 * 64 + 8 + 7) * 4;
+	assert!(sprite_sheet[flipped_pixel .. flipped_pixel + 4] == red[..]);
+	assert!(sprite_sheet[8 * 4 + 3] == 0);
+	
+	let palette = core.render_palette();
+	assert!(palette[0x13 * 4 .. 0x13 * 4 + 4] == red[..]);
+}
+
+
+#[cfg(test)]
+fn make_test_ines(prg: &[u8]) -> Vec<u8>
+{
+	let mut buffer = vec![0_u8; 16 + 0x4000 + 0x2000];
+	buffer[0..4].copy_from_slice(b"NES\x1a");
+	buffer[4] = 1;
+	buffer[5] = 1;
+	
+	buffer[16 .. 16 + prg.len()].copy_from_slice(prg);
+	
+	// Reset vector pointing at $8000
+	buffer[16 + 0x3ffc] = 0x00;
+	buffer[16 + 0x3ffd] = 0x80;
+	
+	buffer
+}
+
+
+#[test]
+fn test_open_bus()
+{
+	let prg =
+	[
+		0xad, 0x00, 0x50, // lda $5000
+		0x85, 0x00,       // sta $00
+		0xa9, 0x55,       // lda #$55
+		0x8d, 0x03, 0x20, // sta $2003
+		0xad, 0x00, 0x20, // lda $2000
+		0x85, 0x01,       // sta $01
+		0xad, 0x16, 0x40, // lda $4016
+		0x85, 0x02,       // sta $02
+		0xad, 0x02, 0x20, // lda $2002
+		0x85, 0x03,       // sta $03
+		0xad, 0x05, 0x30, // lda $3005
+		0x85, 0x04,       // sta $04
+		0x4c, 0x1e, 0x80, // jmp *
+	];
+	
+	let ines = RomINES::new(&make_test_ines(&prg));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	
+	for _ in 0..100
+		{ core.run(); }
+		
+	assert!(core.ram[0] == 0x50);
+	assert!(core.ram[1] == 0x55);
+	assert!(core.ram[2] == 0x40);
+	assert!(core.ram[3] & 0x1f == 0x15);
+	assert!(core.ram[4] == core.ram[3]);
+	
+	for _ in 0..(29781 * 40)
+		{ core.run(); }
+		
+	assert!(core.ppu.read_reg_open_bus() == 0);
+}
+
+
+#[test]
+fn test_palette_ram_mirroring()
+{
+	let ines = RomINES::new(&make_test_ines(&[]));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	
+	core.palram.write(0x3f04, 0x11);
+	core.palram.write(0x3f10, 0x0f);
+	assert!(core.palram.read(0x3f00) == 0x0f);
+	assert!(core.palram.read(0x3f04) == 0x11);
+	
+	core.palram.write(0x3f14, 0xea);
+	assert!(core.palram.read(0x3f04) == 0x2a);
+	assert!(core.palram.read(0x3f15) == 0x00);
+	
+	core.ppu.write_reg_addr(0x2f);
+	core.ppu.write_reg_addr(0x04);
+	core.ppu.write_reg_data(0x77);
+	
+	core.ppu.write_reg_addr(0x3f);
+	core.ppu.write_reg_addr(0x04);
+	assert!(core.ppu.read_reg_data() & 0x3f == 0x2a);
+	
+	// The read buffer now holds the nametable byte under the palette
+	core.ppu.write_reg_addr(0x00);
+	core.ppu.write_reg_addr(0x00);
+	assert!(core.ppu.read_reg_data() == 0x77);
+	
+	// Address wraps from $3fff back to $0000
+	core.ppu.write_reg_addr(0x3f);
+	core.ppu.write_reg_addr(0xff);
+	core.ppu.read_reg_data();
+	core.ppu.read_reg_data();
+	assert!(core.ppu.read_reg_data() == core.cartridge.ppu_peek(0x0000));
+}
+
+
+#[test]
+fn test_debugger()
+{
+	let prg =
+	[
+		0xa9, 0x05,       // $8000: lda #$05
+		0x8d, 0x00, 0x03, // $8002: sta $0300
+		0x20, 0x10, 0x80, // $8005: jsr $8010
+		0xee, 0x00, 0x03, // $8008: inc $0300
+		0x4c, 0x05, 0x80, // $800b: jmp $8005
+		0xea, 0xea,       // $800e: nop, nop
+		0xa9, 0x10,       // $8010: lda #$10
+		0x60,             // $8012: rts
+	];
+	
+	let ines = RomINES::new(&make_test_ines(&prg));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	core.debugger = Some(Box::new(Debugger::new()));
+	
+	let run_until_stop = |core: &mut Core|
+	{
+		for _ in 0..100000
+		{
+			if let Some(reason) = core.run()
+				{ return Some(reason); }
 		}
 		
-		println!();
+		None
+	};
+	
+	{
+		let debugger = core.debugger.as_mut().unwrap();
+		let index = debugger.add_breakpoint(0x8010);
+		assert!(debugger.set_condition(index, "A == $05 && [$0300] > 3").is_ok());
+		assert!(debugger.set_condition(index, "A == (").is_err());
+	}
+	
+	assert!(run_until_stop(&mut core) == Some(StopReason::Breakpoint { index: 0, addr: 0x8010 }));
+	
+	core.debugger.as_mut().unwrap().step_out();
+	assert!(run_until_stop(&mut core) == Some(StopReason::Step));
+	assert!(core.cpu.opcode == 0xee);
+	
+	core.debugger.as_mut().unwrap().step_into();
+	assert!(run_until_stop(&mut core) == Some(StopReason::Step));
+	assert!(core.cpu.opcode == 0x4c);
+	
+	core.debugger.as_mut().unwrap().step_into();
+	assert!(run_until_stop(&mut core) == Some(StopReason::Step));
+	assert!(core.cpu.opcode == 0x20);
+	
+	// The conditional breakpoint inside the subroutine no longer matches
+	core.debugger.as_mut().unwrap().step_over();
+	assert!(run_until_stop(&mut core) == Some(StopReason::Step));
+	assert!(core.cpu.opcode == 0xee);
+	
+	core.debugger.as_mut().unwrap().add_watchpoint(AddressSpace::Cpu, 0x0300, 0x0300, WATCH_WRITE);
+	match run_until_stop(&mut core)
+	{
+		Some(StopReason::Watchpoint { addr, write, .. }) => assert!(addr == 0x0300 && write),
+		_ => panic!("watchpoint not triggered")
+	}
+	
+	core.debugger.as_mut().unwrap().clear();
+	core.debugger.as_mut().unwrap().run_to_scanline(100);
+	assert!(run_until_stop(&mut core) == Some(StopReason::Scanline(100)));
+	assert!(core.ppu.get_scanline() == 100);
+}
+
+
+#[test]
+fn test_trace_logger()
+{
+	let prg =
+	[
+		0xa2, 0x05,       // $8000: ldx #$05
+		0xbd, 0x00, 0x03, // $8002: lda $0300, x
+		0x8d, 0x00, 0x03, // $8005: sta $0300
+		0x4c, 0x02, 0x80, // $8008: jmp $8002
+	];
+	
+	let ines = RomINES::new(&make_test_ines(&prg));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	core.ram[0x305] = 0x7f;
+	
+	let mut trace_logger = TraceLogger::new(TraceFormat::nestest());
+	trace_logger.set_ring_buffer_size(4);
+	core.trace_logger = Some(Box::new(trace_logger));
+	
+	for _ in 0..30
+		{ core.run(); }
 		
-		std::thread::sleep(std::time::Duration::from_millis(500));*/
-	}));
+	{
+		let ring_buffer = core.trace_logger.as_ref().unwrap().get_ring_buffer();
+		assert!(ring_buffer.len() == 4);
+		assert!(ring_buffer[1] == "8002  BD 00 03  LDA $0300,X @ 0305 = 7F         A:7F X:05 Y:00 P:24 SP:FD PPU:240, 57 CYC:19");
+		assert!(ring_buffer[2].starts_with("8005  8D 00 03  STA $0300 = 7F "));
+	}
+	
+	let mut trace_logger = TraceLogger::new(TraceFormat
+	{
+		bytes: false,
+		effective_addr: true,
+		prefixed_values: true,
+		registers: false,
+		ppu: false,
+		cycles: false
+	});
 	
-	for _ in 0..500000
+	trace_logger.set_ring_buffer_size(100);
+	trace_logger.add_filter(0x8002, 0x8004);
+	core.trace_logger = Some(Box::new(trace_logger));
+	
+	for _ in 0..30
 		{ core.run(); }
-}
\ No newline at end of file
+		
+	let ring_buffer = core.trace_logger.as_ref().unwrap().get_ring_buffer();
+	assert!(!ring_buffer.is_empty());
+	assert!(ring_buffer.iter().all(|line| line == "8002  LDA $0300,X @ $0305 = #$7F"));
+}
+
+
+// Most suites cover hardware that isn't emulated yet (the APU, MMC3, unofficial opcodes),
+// so they report a score to watch over time instead of failing. A crash is a bug either way.
+#[cfg(test)]
+fn run_blargg_suite(suite: &str)
+{
+	use std::fs;
+	use std::path::Path;
+	
+	let dir = Path::new("../examples/blargg").join(suite);
+	if !dir.is_dir()
+	{
+		println!("skipping {}: {} not found", suite, dir.display());
+		return;
+	}
+	
+	let mut roms = Vec::new();
+	let mut pending_dirs = vec![dir];
+	
+	while let Some(dir) = pending_dirs.pop()
+	{
+		for entry in fs::read_dir(&dir).unwrap()
+		{
+			let path = entry.unwrap().path();
+			
+			if path.is_dir()
+				{ pending_dirs.push(path); }
+			else if path.extension().map_or(false, |ext| ext == "nes")
+				{ roms.push(path); }
+		}
+	}
+	
+	roms.sort();
+	
+	let mut passed = 0;
+	let mut crashes = Vec::new();
+	for path in &roms
+	{
+		let result = run_blargg_rom(&fs::read(path).unwrap(), 60 * 30);
+		println!("{:<60} {:?}", path.display(), result);
+		
+		if result.is_pass()
+			{ passed += 1; }
+		else if let BlarggResult::Crashed(_) = result
+			{ crashes.push(path.display().to_string()); }
+	}
+	
+	println!("{}: {}/{} passed", suite, passed, roms.len());
+	assert!(crashes.is_empty(), "{} crashed: {:?}", suite, crashes);
+}
+
+
+#[test]
+fn test_blargg_instr_test_v5()
+{
+	run_blargg_suite("instr_test-v5");
+}
+
+
+#[test]
+fn test_blargg_ppu_vbl_nmi()
+{
+	run_blargg_suite("ppu_vbl_nmi");
+}
+
+
+#[test]
+fn test_blargg_apu_test()
+{
+	run_blargg_suite("apu_test");
+}
+
+
+#[test]
+fn test_blargg_cpu_interrupts()
+{
+	run_blargg_suite("cpu_interrupts_v2");
+}
+
+
+#[test]
+fn test_blargg_mmc3_test()
+{
+	run_blargg_suite("mmc3_test_2");
+}
+
+
+#[test]
+fn test_blargg_protocol()
+{
+	// Writes the result text, then reports a failure code of 3
+	let prg =
+	[
+		0xa9, 0x80,       // lda #$80
+		0x8d, 0x00, 0x60, // sta $6000
+		0xa9, 0xde,       // lda #$de
+		0x8d, 0x01, 0x60, // sta $6001
+		0xa9, 0xb0,       // lda #$b0
+		0x8d, 0x02, 0x60, // sta $6002
+		0xa9, 0x61,       // lda #$61
+		0x8d, 0x03, 0x60, // sta $6003
+		0xa9, 0x4f,       // lda #'O'
+		0x8d, 0x04, 0x60, // sta $6004
+		0xa9, 0x4b,       // lda #'K'
+		0x8d, 0x05, 0x60, // sta $6005
+		0xa9, 0x03,       // lda #$03
+		0x8d, 0x00, 0x60, // sta $6000
+		0x4c, 0x23, 0x80, // jmp *
+	];
+	
+	assert!(run_blargg_rom(&make_test_ines(&prg), 10) == BlarggResult::Failed(3, "OK".to_string()));
+	assert!(run_blargg_rom(&make_test_ines(&[0x4c, 0x00, 0x80]), 10) == BlarggResult::Timeout(String::new()));
+}
+
+
+#[test]
+fn test_cdl()
+{
+	let mut prg = vec![0_u8; 0x104];
+	prg[0x00..0x0e].copy_from_slice(&
+	[
+		0xa9, 0x00,       // $8000: lda #$00
+		0x8d, 0x06, 0x20, // $8002: sta $2006
+		0xa9, 0x10,       // $8005: lda #$10
+		0x8d, 0x06, 0x20, // $8007: sta $2006
+		0xad, 0x07, 0x20, // $800a: lda $2007
+		0xea,             // $800d: nop
+	]);
+	
+	prg[0x0e..0x14].copy_from_slice(&
+	[
+		0xad, 0x00, 0x81, // $800e: lda $8100
+		0x6c, 0x02, 0x81, // $8011: jmp ($8102)
+	]);
+	
+	prg[0x20..0x23].copy_from_slice(&[0x4c, 0x20, 0x80]); // $8020: jmp *
+	prg[0x102..0x104].copy_from_slice(&[0x20, 0x80]);
+	
+	let ines = RomINES::new(&make_test_ines(&prg));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	core.set_cdl(true);
+	
+	for _ in 0..200
+		{ core.run(); }
+		
+	let cdl = core.cdl.as_ref().unwrap();
+	assert!(cdl.prg.len() == 0x4000 && cdl.chr.len() == 0x2000);
+	assert!(cdl.prg[0x00..0x14].iter().all(|&flags| flags == CDL_PRG_CODE));
+	assert!(cdl.prg[0x14] == 0);
+	assert!(cdl.prg[0x20] == CDL_PRG_CODE | CDL_PRG_INDIRECT_CODE);
+	assert!(cdl.prg[0x21] == CDL_PRG_CODE);
+	assert!(cdl.prg[0x100] == CDL_PRG_DATA);
+	assert!(cdl.prg[0x102] == CDL_PRG_DATA && cdl.prg[0x103] == CDL_PRG_DATA);
+	assert!(cdl.prg[0x3ffc] == 0);
+	assert!(cdl.chr[0x10] == CDL_CHR_READ);
+	assert!(cdl.chr[0x11] == 0);
+	assert!(cdl.to_cdl().len() == 0x6000);
+}
+
+
+#[test]
+fn test_symbols()
+{
+	let prg =
+	[
+		0xb5, 0x10,       // $8000: lda $10,x
+		0x8d, 0x00, 0x03, // $8002: sta $0300
+		0x20, 0x10, 0x80, // $8005: jsr $8010
+		0xd0, 0xf7,       // $8008: bne $8001
+		0x6c, 0x00, 0x02, // $800a: jmp ($0200)
+		0xad, 0x20, 0x00, // $800d: lda a:$0020
+	];
+	
+	let ines = RomINES::new(&make_test_ines(&prg));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	
+	core.ram[0x200] = 0x20;
+	core.ram[0x201] = 0x80;
+	
+	assert!(core.disassemble(0x8000).0 == "lda $10,x ; $0010 = $00");
+	assert!(core.disassemble(0x800d).0 == "lda a:$0020 ; $0020 = $00");
+	
+	core.symbols.load_nl("$0010#counter#\n$0300/10#buffer#Output buffer#\n", None);
+	core.symbols.load_nl("$8010#Sub#Entry point\n", Some(0));
+	core.symbols.load_mlb("P:0020:Loop:\nR:0020:temp\nR:0200:vector\nbad line\n");
+	core.symbols.load_dbg("seg\tid=0,name=\"CODE\",start=0x008000,size=0x4000,addrsize=absolute,type=ro,oname=\"test.nes\",ooffs=16\n\
+		sym\tid=0,name=\"Start\",addrsize=absolute,scope=0,def=1,ref=2,val=0x8000,seg=0,type=lab\n\
+		sym\tid=1,name=\"SIZE\",addrsize=zeropage,scope=0,def=3,val=0x10,type=equ\n");
+		
+	assert!(core.symbols.cpu_symbols[&0x0300].comment == "Output buffer");
+	assert!(core.symbols.prg_symbols[&0x0000].name == "Start");
+	
+	// The 16K PRG bank is mirrored, so its labels show up at $c000 too
+	assert!(core.symbols.find_label(&core, 0xc010) == Some("Sub".to_string()));
+	assert!(core.symbols.find_address(&core, "Loop") == Some(0x8020));
+	assert!(core.symbols.find_address(&core, "SIZE").is_none());
+	
+	assert!(core.disassemble(0x8000) == ("lda counter,x ; $0010 = $00".to_string(), 2));
+	assert!(core.disassemble(0x8002).0 == "sta buffer ; $0300 = $00");
+	assert!(core.disassemble(0x8005).0 == "jsr Sub");
+	assert!(core.disassemble(0x8008).0 == "bne $8001");
+	assert!(core.disassemble(0x800a).0 == "jmp (vector) ; -> Loop");
+	assert!(core.disassemble(0x800d).0 == "lda a:temp ; $0020 = $00");
+	
+	assert!(core.parse_expression("pc == Sub").is_ok());
+	assert!(core.parse_expression("[counter] == 3").unwrap() == Expression::parse("[$10] == 3").unwrap());
+	assert!(core.parse_expression("nowhere").is_err());
+	
+	let trace = TraceLogger::new(TraceFormat::nestest());
+	assert!(trace.format_line(&core, 0x8002).starts_with("8002  8D 00 03  STA buffer = 00"));
+	
+	core.ram[0x400..0x406].copy_from_slice(&[0xb2, 0x10, 0x1a, 0x6c, 0xff, 0x04]);
+	core.ram[0x11] = 0x03;
+	core.ram[0x4ff] = 0x00;
+	core.ram[0x500] = 0x06;
+	core.cpu.variant = CpuVariant::Wdc65C02;
+	
+	assert!(core.disassemble(0x0400).0 == "lda (counter) ; $0300 = $00");
+	assert!(core.disassemble(0x0402) == ("inc a".to_string(), 1));
+	assert!(trace.format_line(&core, 0x0400).starts_with("0400  B2 10     LDA (counter) = 0300 = 00"));
+	assert!(trace.format_line(&core, 0x0402).starts_with("0402  1A        INC A "));
+	assert!(trace.format_line(&core, 0x0403).starts_with("0403  6C FF 04  JMP ($04FF) = 0600 "));
+}
+
+
+#[test]
+fn test_rom_disassembly()
+{
+	let mut prg = vec![0_u8; 0x4000];
+	prg[0x00..0x14].copy_from_slice(&
+	[
+		0x78,             // $c000: sei
+		0x20, 0x0a, 0xc0, // $c001: jsr $c00a
+		0xbd, 0x20, 0xc0, // $c004: lda $c020,x
+		0x4c, 0x07, 0xc0, // $c007: jmp $c007
+		0x85, 0x10,       // $c00a: sta $10
+		0xf0, 0x02,       // $c00c: beq $c010
+		0x1a,             // $c00e: nop (unofficial)
+		0x60,             // $c00f: rts
+		0x40,             // $c010: rti
+		0x6c, 0x40, 0xc0, // $c011: jmp ($c040)
+	]);
+	
+	prg[0x20..0x23].copy_from_slice(&[0x01, 0x02, 0x03]);
+	prg[0x30..0x32].copy_from_slice(&[0xea, 0x60]);
+	prg[0x40..0x42].copy_from_slice(&[0x50, 0xc0]);
+	prg[0x50] = 0x60;
+	prg[0x3ffa..0x4000].copy_from_slice(&[0x10, 0xc0, 0x00, 0xc0, 0x11, 0xc0]);
+	
+	let mut cdl = vec![0_u8; 0x4000];
+	cdl[0x30] = CDL_PRG_CODE | CDL_PRG_INDIRECT_CODE;
+	cdl[0x31] = CDL_PRG_CODE | CDL_PRG_INDIRECT_CODE;
+	
+	let dis = RomDisassembly::new(&prg, 0xc000, None);
+	assert!(dis.is_code(0xc00f) && !dis.is_code(0xc00b) && !dis.is_code(0xc030));
+	assert!(dis.xrefs[&0xc00a] == vec![XRef { from: 0xc001, kind: XRefKind::Call }]);
+	assert!(dis.xrefs[&0x0010] == vec![XRef { from: 0xc00a, kind: XRefKind::Write }]);
+	assert!(dis.is_code(0xc050) && dis.xrefs[&0xc050] == vec![XRef { from: 0xc040, kind: XRefKind::Pointer }]);
+	
+	let labels = |addr| if addr == 0x10 { Some("temp".to_string()) } else { None };
+	let source = dis.to_ca65(&labels);
+	
+	assert!(source.contains("temp = $0010\n"));
+	assert!(source.contains(".org $c000\n"));
+	assert!(source.contains("\tjsr Lc00a"));
+	assert!(source.contains("\tlda Lc020,x"));
+	assert!(source.contains("\nLc007:\n\tjmp Lc007"));
+	assert!(source.contains("\tsta temp"));
+	assert!(source.contains("\tbeq Lc010"));
+	assert!(source.contains("\t.byte $1a "));
+	assert!(source.contains("\nLc020:\n\t.byte $01, $02, $03, $00"));
+	assert!(source.contains("\tjmp (Lc040)"));
+	assert!(source.contains("\nLc040:\n\t.addr Lc050"));
+	assert!(source.contains("\t.addr Lc010                     ; $fffa\n\t.addr Lc000"));
+	
+	let xrefs = dis.format_xrefs(&labels);
+	assert!(xrefs.contains("temp ($0010): $c00a write\n"));
+	assert!(xrefs.contains("Lc010 ($c010): $c00c branch, $fffa pointer\n"));
+	
+	// Code only reached through a jump table shows up once the code/data log has seen it, and
+	// indirect jump targets next to each other are still code rather than a pointer
+	let dis = RomDisassembly::new(&prg, 0xc000, Some(&cdl));
+	assert!(dis.is_code(0xc030) && dis.is_code(0xc031));
+	assert!(!dis.to_ca65(&labels).contains(".addr $60ea"));
+}
+
+
+#[cfg(test)]
+fn gdb_command(stream: &mut std::net::TcpStream, command: &str) -> String
+{
+	use std::io::{Read, Write};
+	
+	let checksum = command.bytes().fold(0_u8, |sum, byte| sum.wrapping_add(byte));
+	write!(stream, "${}#{:02x}", command, checksum).unwrap();
+	
+	// Skip the ack, then read up to the checksum and ack the reply
+	let mut reply = Vec::new();
+	let mut byte = [0];
+	
+	loop
+	{
+		stream.read_exact(&mut byte).unwrap();
+		if byte[0] == b'$'
+			{ break; }
+	}
+	
+	loop
+	{
+		stream.read_exact(&mut byte).unwrap();
+		if byte[0] == b'#'
+			{ break; }
+			
+		reply.push(byte[0]);
+	}
+	
+	let mut checksum = [0; 2];
+	stream.read_exact(&mut checksum).unwrap();
+	stream.write_all(b"+").unwrap();
+	
+	String::from_utf8(reply).unwrap()
+}
+
+
+#[test]
+fn test_gdb_stub()
+{
+	let prg =
+	[
+		0xa9, 0x05,       // $8000: lda #$05
+		0x85, 0x10,       // $8002: sta $10
+		0xe8,             // $8004: inx
+		0x4c, 0x04, 0x80, // $8005: jmp $8004
+	];
+	
+	let ines = RomINES::new(&make_test_ines(&prg));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	
+	let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+	let port = listener.local_addr().unwrap().port();
+	
+	let client = std::thread::spawn(move ||
+	{
+		let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+		stream.set_nodelay(true).unwrap();
+		
+		let mut command = |command: &str| gdb_command(&mut stream, command);
+		
+		assert!(command("qSupported:swbreak+").contains("qXfer:features:read+"));
+		assert!(command("qXfer:features:read:target.xml:0,fff").starts_with("l<?xml"));
+		assert!(command("qXfer:features:read:target.xml:0,ffffffffffff") == "E01");
+		assert!(command("?") == "S05");
+		assert!(command("g") == "00000024fd0080");
+		
+		assert!(command("s") == "S05");
+		assert!(command("p5") == "0280");
+		assert!(command("p0") == "05");
+		
+		assert!(command("Z2,10,1") == "OK");
+		assert!(command("c") == "T05watch:0010;");
+		assert!(command("p5") == "0480");
+		assert!(command("m10,1") == "05");
+		assert!(command("z2,10,1") == "OK");
+		
+		assert!(command("Z0,8005,1") == "OK");
+		assert!(command("c") == "S05");
+		assert!(command("p5") == "0580");
+		assert!(command("p1") == "01");
+		assert!(command("c") == "S05");
+		assert!(command("p1") == "02");
+		assert!(command("z0,8005,1") == "OK");
+		
+		assert!(command("P0=aa") == "OK");
+		assert!(command("M20,2:1234") == "OK");
+		assert!(command("m20,2") == "1234");
+		assert!(command("M6000,1:42") == "OK");
+		assert!(command("m6000,1") == "42");
+		
+		// Lengths that wouldn't fit in a packet are refused
+		assert!(command("m0,800").len() == 0x1000);
+		assert!(command("m0,801") == "E01");
+		assert!(command("m0,ffffffffffff") == "E01");
+		assert!(command("M0,ffffffffffff:00") == "E01");
+		
+		assert!(command("P5=0080") == "OK");
+		assert!(command("s") == "S05");
+		assert!(command("p0") == "05");
+		assert!(command("g") == "05020024fd0280");
+		
+		assert!(command("D") == "OK");
+	});
+	
+	let (stream, _) = listener.accept().unwrap();
+	let mut stub = GdbStub::new(stream);
+	stub.run(&mut core).unwrap();
+	client.join().unwrap();
+	
+	assert!(core.ram[0x10] == 0x05 && core.ram[0x21] == 0x34);
+	assert!(core.debugger.as_ref().unwrap().breakpoints.iter().all(|breakpoint| breakpoint.is_none()));
+}
+
+
+#[test]
+fn test_peek_poke()
+{
+	let ines = RomINES::new(&make_test_ines(&[0x4c, 0x00, 0x80]));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	
+	core.poke_cpu(0x0005, 0x12);
+	core.poke_cpu(0x6000, 0x34);
+	core.poke_cpu(0x8010, 0x56);
+	assert!(core.peek_cpu(0x0805) == 0x12 && core.ram[5] == 0x12);
+	assert!(core.peek_cpu(0x6000) == 0x34);
+	assert!(core.peek_cpu(0xc010) == 0x56);
+	
+	core.poke_ppu(0x0010, 0xaa);
+	core.poke_ppu(0x2005, 0x33);
+	core.poke_ppu(0x3f10, 0x0f);
+	assert!(core.peek_ppu(0x0010) == 0xaa);
+	assert!(core.peek_ppu(0x2005) == 0x33 && core.peek_ppu(0x3005) == 0x33);
+	assert!(core.peek_ppu(0x3f00) == 0x0f && core.peek_ppu(0x7f00) == 0x0f);
+	
+	// Peeking $2002 must not acknowledge vblank, nor $2007 move the VRAM address
+	while (core.peek_cpu(0x2002) & 0x80) == 0
+		{ core.run(); }
+		
+	let scroll_v = core.ppu.get_scroll_v();
+	assert!((core.peek_cpu(0x2002) & 0x80) != 0);
+	core.peek_cpu(0x2007);
+	assert!(core.ppu.get_scroll_v() == scroll_v);
+}
+
+
+#[test]
+fn test_movie()
+{
+	let prg =
+	[
+		0xad, 0x02, 0x20, // lda $2002
+		0x10, 0xfb,       // bpl $8000
+		0xa9, 0x01,       // lda #$01
+		0x8d, 0x16, 0x40, // sta $4016
+		0xa9, 0x00,       // lda #$00
+		0x8d, 0x16, 0x40, // sta $4016
+		0xa2, 0x08,       // ldx #$08
+		0xad, 0x16, 0x40, // lda $4016
+		0x4a,             // lsr a
+		0x26, 0x00,       // rol $00
+		0xca,             // dex
+		0xd0, 0xf7,       // bne $800f
+		0xa5, 0x00,       // lda $00
+		0x18,             // clc
+		0x65, 0x01,       // adc $01
+		0x85, 0x01,       // sta $01
+		0xe6, 0x02,       // inc $02
+		0x4c, 0x00, 0x80, // jmp $8000
+	];
+	
+	let rom = make_test_ines(&prg);
+	let mut core = Core::new(Box::new(RomINES::new(&rom).make_cartridge().unwrap()));
+	
+	let mut movie = Movie::new();
+	movie.rerecord_count = 7;
+	core.record_movie(movie);
+	
+	for frame in 0..40
+	{
+		core.controller1 = (frame * 37) as u8;
+		if frame == 20
+			{ core.soft_reset(); }
+			
+		core.run_frame();
+	}
+	
+	let movie = core.stop_movie().unwrap();
+	let expected_ram = core.ram;
+	
+	assert!(movie.frames.len() == 40);
+	assert!(movie.frames[20].commands == MOVIE_SOFT_RESET);
+	assert!(movie.frames.iter().filter(|frame| frame.commands != 0).count() == 1);
+	assert!(movie.frames[3] == MovieFrame { commands: 0, controller1: 111, controller2: 0 });
+	
+	// Export and import must round-trip
+	let text = movie.to_fm2();
+	assert!(text.starts_with("version 3\n"));
+	assert!(text.contains("rerecordCount 7\n"));
+	assert!(text.contains("|1|RLD..S..|||\n"));
+	
+	let parsed = Movie::parse_fm2(&text).unwrap();
+	assert!(parsed.frames == movie.frames && parsed.rerecord_count == 7);
+	assert!(parsed.to_fm2() == text);
+	assert!(Movie::parse_fm2("version 2\n").is_err());
+	assert!(Movie::parse_fm2("version 3\n|0|ABC|||\n").is_err());
+	
+	// Playback ignores the live controller and replays the soft reset
+	let mut core = Core::new(Box::new(RomINES::new(&rom).make_cartridge().unwrap()));
+	core.controller1 = 0xff;
+	core.play_movie(*movie);
+	
+	for _ in 0..40
+		{ core.run_frame(); }
+		
+	assert!(core.movie.as_ref().unwrap().mode == MovieMode::Finished);
+	assert!(core.ram[..] == expected_ram[..]);
+}
+
+
+#[test]
+fn test_lag_frames()
+{
+	// Polls the controllers only on odd frames
+	let prg =
+	[
+		0xad, 0x02, 0x20, // lda $2002
+		0x10, 0xfb,       // bpl $8000
+		0xe6, 0x00,       // inc $00
+		0xa5, 0x00,       // lda $00
+		0x29, 0x01,       // and #$01
+		0xf0, 0xf3,       // beq $8000
+		0xad, 0x16, 0x40, // lda $4016
+		0xad, 0x16, 0x40, // lda $4016
+		0xad, 0x17, 0x40, // lda $4017
+		0x4c, 0x00, 0x80, // jmp $8000
+	];
+	
+	let ines = RomINES::new(&make_test_ines(&prg));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	
+	for _ in 0..20
+		{ core.run_frame(); }
+		
+	// Exactly every other frame polls
+	assert!(core.lag_frames == 10);
+	
+	let mut lagged = 0;
+	for _ in 0..10
+	{
+		core.reset_lag_count();
+		core.run_frame();
+		
+		if core.is_lag_frame()
+		{
+			assert!(core.lag_frames == 1);
+			lagged += 1;
+		}
+		else
+			{ assert!(core.lag_frames == 0 && core.last_input_polls == [2, 1]); }
+	}
+	
+	assert!(lagged == 5);
+	
+	// Peeking the ports is not polling them
+	let polls = core.input_polls;
+	core.peek_cpu(0x4016);
+	core.peek_cpu(0x4017);
+	assert!(core.input_polls == polls);
+}
+
+
+#[test]
+fn test_cheats()
+{
+	let cheat = Cheat::parse("sxiopo").unwrap();
+	assert!(cheat.kind == CheatKind::Substitute && cheat.addr == 0x91d9 && cheat.value == 0xad && cheat.compare.is_none());
+	
+	let cheat = Cheat::parse("ZEXPYGLA").unwrap();
+	assert!(cheat.addr == 0x94a7 && cheat.value == 0x02 && cheat.compare == Some(0x03));
+	
+	assert!(Cheat::parse("SXIOP").is_err());
+	assert!(Cheat::parse("SXIOPB").is_err());
+	assert!(Cheat::parse("0010:05").unwrap().kind == CheatKind::Freeze);
+	assert!(Cheat::parse("0010?05:06").unwrap() == Cheat::new(CheatKind::Freeze, 0x0010, 0x06, Some(0x05)));
+	assert!(Cheat::parse("c010?11:22").unwrap() == Cheat::new(CheatKind::Substitute, 0xc010, 0x22, Some(0x11)));
+	
+	let cht = "SC8100:42:77:Wrong compare\n:0011:09:Disabled\n0010:05:Lives\n";
+	let cheats = Cheat::parse_cht(cht).unwrap();
+	assert!(cheats.len() == 3 && cheats[0].compare == Some(0x77) && !cheats[1].enabled && cheats[2].name == "Lives");
+	assert!(Cheat::to_cht(&cheats) == cht);
+	assert!(Cheat::parse_cht("SC8100:42").is_err());
+	
+	// ROM can't be frozen, and RAM can't be substituted
+	assert!(Cheat::parse_cht("8100:42:\nS0010:05:\nS6000:05:\n").unwrap().iter().map(|cheat| cheat.kind).collect::<Vec<_>>() ==
+		vec![CheatKind::Substitute, CheatKind::Freeze, CheatKind::Substitute]);
+	
+	let mut prg = vec![
+		0xad, 0x00, 0x81, // lda $8100
+		0x85, 0x00,       // sta $00
+		0xe6, 0x10,       // inc $10
+		0xe6, 0x11,       // inc $11
+		0x4c, 0x00, 0x80, // jmp $8000
+	];
+	prg.resize(0x101, 0);
+	prg[0x100] = 0x77;
+	
+	let ines = RomINES::new(&make_test_ines(&prg));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	
+	assert!(core.load_cheats(cht).unwrap() == 3);
+	core.set_cheat_enabled(0, false);
+	core.run_frame();
+	assert!(core.ram[0x00] == 0x77 && core.ram[0x10] == 0x05 && core.ram[0x11] != 0x09);
+	
+	// The compare byte is checked against what the ROM really holds
+	core.cheats[0].compare = Some(0x76);
+	core.set_cheat_enabled(0, true);
+	core.run_frame();
+	assert!(core.ram[0x00] == 0x77);
+	
+	core.cheats[0].compare = Some(0x77);
+	core.run_frame();
+	assert!(core.ram[0x00] == 0x42 && core.peek_cpu(0x8100) == 0x77);
+	
+	assert!(core.add_cheat("8100:99").unwrap() == 3);
+	core.set_cheat_enabled(0, false);
+	core.run_frame();
+	assert!(core.ram[0x00] == 0x99);
+	
+	// Freezes only apply to RAM, so disabling a cheat always gives the ROM byte back
+	core.cheats.push(Cheat::new(CheatKind::Freeze, 0x8100, 0x99, None));
+	core.run_frame();
+	core.cheats.truncate(3);
+	core.run_frame();
+	assert!(core.ram[0x00] == 0x77 && core.peek_cpu(0x8100) == 0x77);
+	
+	// A compare on a RAM cheat only freezes while the byte holds that value
+	core.cheats.clear();
+	assert!(core.add_cheat("0020?00:33").unwrap() == 0);
+	core.run_frame();
+	assert!(core.ram[0x20] == 0x33);
+	core.ram[0x20] = 0x44;
+	core.run_frame();
+	assert!(core.ram[0x20] == 0x44);
+}
+
+
+#[test]
+fn test_ram_search()
+{
+	let mut prg = vec![0_u8; 0x4000];
+	let code: &[(usize, &[u8])] =
+	&[
+		(0x0000, &[
+			0xa9, 0x80,       // lda #$80
+			0x8d, 0x00, 0x20, // sta $2000
+			0x4c, 0x05, 0x80, // jmp $8005
+		]),
+		
+		// NMI: one step of each counter per frame
+		(0x0010, &[
+			0xe6, 0x10,       // inc $10
+			0xce, 0x00, 0x03, // dec $0300
+			0x18,             // clc
+			0xad, 0x00, 0x04, // lda $0400
+			0x69, 0x90,       // adc #$90
+			0x8d, 0x00, 0x04, // sta $0400
+			0xad, 0x01, 0x04, // lda $0401
+			0x69, 0x00,       // adc #$00
+			0x8d, 0x01, 0x04, // sta $0401
+			0xee, 0x05, 0x60, // inc $6005
+			0x40,             // rti
+		]),
+		
+		(0x3ffa, &[0x10, 0x80]),
+	];
+	
+	for &(offset, bytes) in code
+		{ prg[offset .. offset + bytes.len()].copy_from_slice(bytes); }
+		
+	let ines = RomINES::new(&make_test_ines(&prg));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	
+	let mut search = RamSearch::new(&core, SearchSize::Byte, false);
+	assert!(search.get_candidate_count() == 0x800 + 0x2000);
+	
+	core.run_frame();
+	search.filter(&core, SearchFilter::IncreasedBy(1));
+	core.run_frame();
+	search.filter(&core, SearchFilter::Greater);
+	core.run_frame();
+	search.filter(&core, SearchFilter::Unchanged);
+	assert!(search.get_candidate_count() == 0);
+	
+	search.reset(&core);
+	core.run_frame();
+	search.filter(&core, SearchFilter::IncreasedBy(1));
+	core.run_frame();
+	search.filter(&core, SearchFilter::Changed);
+	
+	let results = search.get_results(&core);
+	let addrs: Vec<u16> = results.iter().map(|result| result.addr).collect();
+	assert!(addrs == vec![0x0010, 0x6005]);
+	assert!(results[0].value == core.ram[0x10] as i64 && results[0].previous == results[0].value);
+	
+	// $0300 counts down from zero, so it only reads as negative when signed
+	let mut search = RamSearch::new(&core, SearchSize::Byte, true);
+	core.run_frame();
+	search.filter(&core, SearchFilter::DecreasedBy(1));
+	core.run_frame();
+	search.filter(&core, SearchFilter::Less);
+	
+	let results = search.get_results(&core);
+	assert!(results.len() == 1 && results[0].addr == 0x0300 && results[0].value == core.ram[0x300] as i8 as i64);
+	
+	search.reset(&core);
+	core.run_frame();
+	search.filter(&core, SearchFilter::DecreasedBy(1));
+	
+	let results = search.get_results(&core);
+	assert!(results.len() == 1 && results[0].addr == 0x0300 && results[0].value == core.ram[0x300] as i8 as i64 && results[0].value < 0);
+	
+	search.set_view(SearchSize::Byte, false);
+	assert!(search.get_results(&core)[0].value == core.ram[0x300] as i64);
+	
+	// The 16-bit counter carries into its high byte
+	let mut search = RamSearch::new(&core, SearchSize::Word, false);
+	assert!(search.get_candidate_count() == 0x800 + 0x2000 - 2);
+	
+	for _ in 0..3
+	{
+		core.run_frame();
+		search.filter(&core, SearchFilter::IncreasedBy(0x90));
+	}
+	
+	let results = search.get_results(&core);
+	assert!(results.len() == 1 && results[0].addr == 0x0400 && results[0].value == 0x90 * core.ram[0x10] as i64);
+	
+	// Counters that wrap around still count as increased or decreased
+	core.ram[0x10] = 0xff;
+	core.ram[0x300] = 0x00;
+	
+	let mut increased = RamSearch::new(&core, SearchSize::Byte, false);
+	let mut decreased = RamSearch::new(&core, SearchSize::Byte, false);
+	core.run_frame();
+	
+	increased.filter(&core, SearchFilter::IncreasedBy(1));
+	decreased.filter(&core, SearchFilter::DecreasedBy(1));
+	assert!(increased.get_results(&core).iter().any(|result| result.addr == 0x0010 && result.value == 0x00));
+	assert!(decreased.get_results(&core).iter().any(|result| result.addr == 0x0300 && result.value == 0xff));
+}
+
+
+#[test]
+fn test_soft_reset_and_power_on_ram()
+{
+	let prg =
+	[
+		0xa2, 0x40,       // ldx #$40
+		0x9a,             // txs
+		0xa9, 0x04,       // lda #$04
+		0x8d, 0x00, 0x20, // sta $2000
+		0x58,             // cli
+		0xe6, 0x10,       // inc $10
+		0x4c, 0x09, 0x80, // jmp $8009
+	];
+	
+	let ines = RomINES::new(&make_test_ines(&prg));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	
+	// A power cycle fills memory according to the policy and leaves S at $FD
+	core.power_on_ram = PowerOnRam::Pattern;
+	core.reset();
+	assert!(core.ram[0..8] == [0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff]);
+	assert!(core.vram[0x7fc] == 0xff);
+	
+	core.run();
+	while core.cpu.reg_pc != 0x8001
+		{ core.run(); }
+		
+	assert!(core.cpu.reg_s == 0xfd && core.cpu.reg_p == 0x24);
+	
+	core.power_on_ram = PowerOnRam::Ones;
+	core.reset();
+	assert!(core.ram.iter().all(|&byte| byte == 0xff));
+	
+	core.power_on_ram = PowerOnRam::Random(1234);
+	core.reset();
+	let ram = core.ram;
+	core.reset();
+	assert!(core.ram[..] == ram[..] && ram.iter().any(|&byte| byte != ram[0]));
+	
+	core.power_on_ram = PowerOnRam::Random(4321);
+	core.reset();
+	assert!(core.ram[..] != ram[..]);
+	
+	// Pressing reset keeps memory and registers, takes S down by 3 and sets I
+	core.power_on_ram = PowerOnRam::Zero;
+	core.reset();
+	
+	for _ in 0..10
+		{ core.run_frame(); }
+		
+	let counter = core.ram[0x10];
+	let frame = core.ppu.get_frame();
+	assert!(counter > 0 && core.cpu.reg_s == 0x40 && (core.cpu.reg_p & 0x04) == 0 && core.ppu.get_reg_ctrl() == 0x04);
+	
+	core.ram[0x20] = 0x55;
+	core.soft_reset();
+	
+	core.run();
+	while core.cpu.reg_pc != 0x8001
+		{ core.run(); }
+		
+	assert!(core.cpu.reg_s == 0x3d && (core.cpu.reg_p & 0x04) != 0);
+	assert!(core.ram[0x10] == counter && core.ram[0x20] == 0x55);
+	assert!(core.ppu.get_frame() == frame && core.ppu.get_reg_ctrl() == 0);
+	
+	// The PPU ignores the game's $2000 write, and scroll writes, until vblank is over
+	while core.cpu.reg_pc != 0x8009
+		{ core.run(); }
+		
+	core.ppu.write_reg_scroll(0xff);
+	assert!(core.ppu.get_reg_ctrl() == 0 && core.ppu.get_scroll_t() == 0 && core.ppu.get_scroll_x() == 0);
+	
+	core.run_frame();
+	core.ppu.write_reg_ctrl(0x04);
+	core.ppu.write_reg_scroll(0xff);
+	assert!(core.ppu.get_reg_ctrl() == 0x04 && core.ppu.get_scroll_x() == 0x07);
+}
+
+
+#[cfg(test)]
+fn make_test_cpu(program: &[u8]) -> (Cpu, std::rc::Rc<std::cell::RefCell<Vec<u8>>>)
+{
+	use std::rc::Rc;
+	use std::cell::RefCell;
+	
+	// A CPU on flat memory running `program` from $0200, with the IRQ handler at $0300
+	// and the NMI handler at $0400
+	let mut memory = vec![0xea_u8; 0x10000];
+	memory[0x0200 .. 0x0200 + program.len()].copy_from_slice(program);
+	memory[0xfffa .. 0x10000].copy_from_slice(&[0x00, 0x04, 0x00, 0x02, 0x00, 0x03]);
+	
+	let memory = Rc::new(RefCell::new(memory));
+	let memory_read = memory.clone();
+	let memory_write = memory.clone();
+	
+	let mut cpu = Cpu::new();
+	cpu.hook_read = Box::new(move |addr| memory_read.borrow()[addr as usize]);
+	cpu.hook_write = Box::new(move |addr, val| memory_write.borrow_mut()[addr as usize] = val);
+	cpu.reset();
+	
+	(cpu, memory)
+}
+
+
+#[cfg(test)]
+fn run_cpu_until(cpu: &mut Cpu, event: CpuEvent) -> bool
+{
+	for _ in 0..100
+	{
+		cpu.clock();
+		if cpu.last_event == event
+			{ return true; }
+	}
+	
+	false
+}
+
+
+#[test]
+fn test_cpu_interrupts()
+{
+	// CLI takes effect after the following instruction
+	let (mut cpu, memory) = make_test_cpu(&[0x58, 0xea, 0xea]);
+	cpu.drive_irq(true);
+	assert!(run_cpu_until(&mut cpu, CpuEvent::Irq));
+	assert!(run_cpu_until(&mut cpu, CpuEvent::FetchOpcode(0x0300)));
+	assert!(memory.borrow()[0x1fc ..= 0x1fd] == [0x02, 0x02]);
+	assert!((memory.borrow()[0x1fb] & 0x14) == 0 && (cpu.reg_p & 0x04) != 0);
+	
+	// An IRQ seen during SEI is still taken, with I already set in the pushed flags
+	let (mut cpu, memory) = make_test_cpu(&[0x58, 0xea, 0x78, 0xea]);
+	assert!(run_cpu_until(&mut cpu, CpuEvent::FetchOpcode(0x0202)));
+	cpu.drive_irq(true);
+	assert!(run_cpu_until(&mut cpu, CpuEvent::Irq));
+	assert!(run_cpu_until(&mut cpu, CpuEvent::FetchOpcode(0x0300)));
+	assert!(memory.borrow()[0x1fc ..= 0x1fd] == [0x03, 0x02]);
+	assert!((memory.borrow()[0x1fb] & 0x04) != 0);
+	
+	// IRQ is level-sensitive, so a pulse while I is set is forgotten
+	let (mut cpu, _) = make_test_cpu(&[0xea, 0xea, 0x58, 0xea, 0xea]);
+	cpu.drive_irq(true);
+	assert!(run_cpu_until(&mut cpu, CpuEvent::FetchOpcode(0x0201)));
+	cpu.drive_irq(false);
+	assert!(!run_cpu_until(&mut cpu, CpuEvent::Irq));
+	
+	// A taken branch that doesn't cross a page delays an IRQ that shows up during its operand fetch
+	let (mut cpu, memory) = make_test_cpu(&[0x58, 0xea, 0xd0, 0x00, 0xea, 0xea]);
+	assert!(run_cpu_until(&mut cpu, CpuEvent::FetchOpcode(0x0202)));
+	cpu.clock();
+	cpu.drive_irq(true);
+	assert!(run_cpu_until(&mut cpu, CpuEvent::Irq));
+	assert!(run_cpu_until(&mut cpu, CpuEvent::FetchOpcode(0x0300)));
+	assert!(memory.borrow()[0x1fc ..= 0x1fd] == [0x05, 0x02]);
+	
+	// Without the branch the IRQ is taken one instruction earlier
+	let (mut cpu, memory) = make_test_cpu(&[0x58, 0xea, 0xa5, 0x00, 0xea, 0xea]);
+	assert!(run_cpu_until(&mut cpu, CpuEvent::FetchOpcode(0x0202)));
+	cpu.clock();
+	cpu.drive_irq(true);
+	assert!(run_cpu_until(&mut cpu, CpuEvent::Irq));
+	assert!(run_cpu_until(&mut cpu, CpuEvent::FetchOpcode(0x0300)));
+	assert!(memory.borrow()[0x1fc ..= 0x1fd] == [0x04, 0x02]);
+	
+	// An NMI during BRK takes over its vector, but the pushed flags still have B set
+	let (mut cpu, memory) = make_test_cpu(&[0x00, 0x00, 0xea]);
+	assert!(run_cpu_until(&mut cpu, CpuEvent::FetchOpcode(0x0200)));
+	cpu.drive_nmi(true);
+	assert!(run_cpu_until(&mut cpu, CpuEvent::FetchOpcode(0x0400)));
+	assert!(memory.borrow()[0x1fc ..= 0x1fd] == [0x02, 0x02]);
+	assert!((memory.borrow()[0x1fb] & 0x10) != 0);
+	assert!(!run_cpu_until(&mut cpu, CpuEvent::Nmi));
+	
+	// A plain NMI pushes B clear
+	let (mut cpu, memory) = make_test_cpu(&[0xea, 0xea, 0xea]);
+	assert!(run_cpu_until(&mut cpu, CpuEvent::FetchOpcode(0x0200)));
+	cpu.drive_nmi(true);
+	assert!(run_cpu_until(&mut cpu, CpuEvent::Nmi));
+	assert!(run_cpu_until(&mut cpu, CpuEvent::FetchOpcode(0x0400)));
+	assert!((memory.borrow()[0x1fb] & 0x10) == 0 && memory.borrow()[0x1fc ..= 0x1fd] == [0x01, 0x02]);
+}
+
+
+#[test]
+fn test_step_instruction()
+{
+	let program =
+	[
+		0xa9, 0x42, // lda #$42
+		0xa2, 0x10, // ldx #$10
+		0x95, 0x10, // sta $10,x
+		0xe8,       // inx
+	];
+	
+	// The reset sequence ends with the first fetch
+	let (mut cpu, memory) = make_test_cpu(&program);
+	assert!(cpu.step_instruction() == 7);
+	
+	let read = memory.clone();
+	let state = cpu.get_state(&move |addr| read.borrow()[addr as usize]);
+	assert!(state == CpuState { pc: 0x0200, a: 0, x: 0, y: 0, s: 0xfd, p: 0x24, cycles: 7, instruction: [0xa9, 0x42, 0xa2], instruction_len: 2 });
+	
+	assert!(cpu.step_instruction() == 2);
+	assert!(cpu.step_instruction() == 2);
+	assert!(cpu.step_instruction() == 4);
+	assert!(memory.borrow()[0x20] == 0x42);
+	
+	let state_before = cpu.get_state(&|addr| memory.borrow()[addr as usize]);
+	assert!(state_before.pc == 0x0206 && state_before.instruction_len == 1);
+	assert!(cpu.step_instruction() == 2);
+	
+	let state_after = cpu.get_state(&|addr| memory.borrow()[addr as usize]);
+	assert!(state_after != state_before);
+	assert!(state_after.pc == 0x0207 && state_after.x == 0x11 && state_after.cycles == 17);
+	
+	// The core version stops at the same boundaries
+	let prg =
+	[
+		0xa9, 0x42,       // lda #$42
+		0x8d, 0x00, 0x03, // sta $0300
+		0x4c, 0x05, 0x80, // jmp *
+	];
+	
+	let ines = RomINES::new(&make_test_ines(&prg));
+	let mut core = Core::new(Box::new(ines.make_cartridge().unwrap()));
+	core.reset();
+	
+	assert!(core.step_instruction().is_none());
+	assert!(core.get_cpu_state().pc == 0x8000);
+	
+	core.step_instruction();
+	core.step_instruction();
+	
+	let state = core.get_cpu_state();
+	assert!(state.pc == 0x8005 && state.a == 0x42 && state.instruction == [0x4c, 0x05, 0x80] && state.instruction_len == 3);
+	assert!(core.ram[0x300] == 0x42);
+}
+
+
+#[test]
+fn test_processor_tests_harness()
+{
+	let json = r#"[
+		{
+			"name": "bd ff 10",
+			"initial": { "pc": 512, "s": 253, "a": 0, "x": 1, "y": 0, "p": 36, "ram": [[512, 189], [513, 255], [514, 16], [4352, 133]] },
+			"final": { "pc": 515, "s": 253, "a": 133, "x": 1, "y": 0, "p": 164, "ram": [[4352, 133]] },
+			"cycles": [[512, 189, "read"], [513, 255, "read"], [514, 16, "read"], [4096, 0, "read"], [4352, 133, "read"]]
+		},
+		{
+			"name": "d0 20",
+			"initial": { "pc": 752, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[752, 208], [753, 32]] },
+			"final": { "pc": 786, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [] },
+			"cycles": [[752, 208, "read"], [753, 32, "read"], [754, 0, "read"], [530, 0, "read"]]
+		},
+		{
+			"name": "48 (missing dummy read)",
+			"initial": { "pc": 512, "s": 253, "a": 66, "x": 0, "y": 0, "p": 36, "ram": [[512, 72]] },
+			"final": { "pc": 513, "s": 252, "a": 66, "x": 0, "y": 0, "p": 36, "ram": [[509, 66]] },
+			"cycles": [[512, 72, "read"], [509, 66, "write"]]
+		}
+	]"#;
+	
+	let results = run_processor_tests(json).unwrap();
+	assert!(results.passed == 2);
+	assert!(results.failures.len() == 1 && results.failures[0].starts_with("48 (missing dummy read): cycle 2:"));
+	
+	assert!(run_processor_tests("[{\"name\": 1").is_err());
+}
+
+
+#[test]
+fn test_processor_tests()
+{
+	use std::fs;
+	use std::path::Path;
+	
+	let dir = Path::new("../examples/ProcessorTests/nes6502/v1");
+	if !dir.is_dir()
+	{
+		println!("skipping test_processor_tests: {} not found", dir.display());
+		return;
+	}
+	
+	// Unofficial opcodes aren't implemented, and missing files are skipped
+	let mut failures = Vec::new();
+	for opcode in 0..=0xff_u8
+	{
+		let path = dir.join(format!("{:02x}.json", opcode));
+		if cpu_dis::decode_opcode(opcode).is_none() || !path.is_file()
+			{ continue; }
+			
+		let results = run_processor_tests(&fs::read_to_string(&path).unwrap()).unwrap();
+		println!("{:<60} {} passed, {} failed", path.display(), results.passed, results.failures.len());
+		
+		failures.extend(results.failures.into_iter().take(3));
+	}
+	
+	assert!(failures.is_empty(), "failures: {:#?}", failures);
+}
+
+
+#[test]
+fn test_cpu_variants()
+{
+	let program =
+	[
+		0xf8,       // sed
+		0x18,       // clc
+		0xa9, 0x99, // lda #$99
+		0x69, 0x01, // adc #$01
+		0x38,       // sec
+		0xa9, 0x00, // lda #$00
+		0xe9, 0x01, // sbc #$01
+	];
+	
+	// Flags NVZC after ADC and SBC, and how long the ADC took
+	let run_decimal = |variant|
+	{
+		let (mut cpu, _memory) = make_test_cpu(&program);
+		cpu.variant = variant;
+		
+		for _ in 0..4
+			{ cpu.step_instruction(); }
+			
+		let adc_cycles = cpu.step_instruction();
+		let adc = (cpu.reg_a, cpu.reg_p & 0xc3);
+		
+		for _ in 0..3
+			{ cpu.step_instruction(); }
+			
+		(adc, (cpu.reg_a, cpu.reg_p & 0xc3), adc_cycles)
+	};
+	
+	assert!(run_decimal(CpuVariant::Ricoh2A03) == ((0x9a, 0x80), (0xff, 0x80), 2));
+	assert!(run_decimal(CpuVariant::Nmos6502) == ((0x00, 0x81), (0x99, 0x80), 2));
+	assert!(run_decimal(CpuVariant::Wdc65C02) == ((0x00, 0x03), (0x99, 0x80), 3));
+	
+	let program =
+	[
+		0xb2, 0x10,       // lda ($10)
+		0x1a,             // inc a
+		0x92, 0x12,       // sta ($12)
+		0x64, 0x20,       // stz $20
+		0xf7, 0x20,       // smb7 $20
+		0x5a,             // phy
+		0xfa,             // plx
+		0xff, 0x20, 0x02, // bbs7 $20, +2
+		0x02, 0x00,
+		0x80, 0x02,       // bra +2
+		0x02, 0x00,
+		0x7c, 0x00, 0x04, // jmp ($0400,x)
+	];
+	
+	let (mut cpu, memory) = make_test_cpu(&program);
+	cpu.variant = CpuVariant::Wdc65C02;
+	cpu.reg_x = 0x55;
+	
+	for &(addr, val) in &[(0x10, 0x00), (0x11, 0x03), (0x12, 0x01), (0x13, 0x03), (0x20, 0x33), (0x300, 0x5a), (0x400, 0x00), (0x401, 0x05)]
+		{ memory.borrow_mut()[addr] = val; }
+		
+	cpu.step_instruction();
+	let cycles: Vec<usize> = (0..10).map(|_| cpu.step_instruction()).collect();
+	
+	assert!(cycles == [5, 2, 5, 3, 5, 3, 4, 6, 3, 6]);
+	assert!(cpu.get_instruction_addr() == 0x0500);
+	assert!(cpu.reg_a == 0x5b && cpu.reg_x == 0 && (cpu.reg_p & 0x02) != 0);
+	assert!(memory.borrow()[0x301] == 0x5b && memory.borrow()[0x20] == 0x80);
+	
+	// Only the 65C02 carries into the pointer's high byte, taking a cycle more
+	let run_jmp_indirect = |variant|
+	{
+		let (mut cpu, memory) = make_test_cpu(&[0x6c, 0xff, 0x02]);
+		cpu.variant = variant;
+		memory.borrow_mut()[0x2ff] = 0x00;
+		memory.borrow_mut()[0x300] = 0x04;
+		
+		cpu.step_instruction();
+		let cycles = cpu.step_instruction();
+		(cpu.get_instruction_addr(), cycles)
+	};
+	
+	assert!(run_jmp_indirect(CpuVariant::Nmos6502) == (0x6c00, 5));
+	assert!(run_jmp_indirect(CpuVariant::Wdc65C02) == (0x0400, 6));
+	
+	assert!(cpu_dis::disassemble_instruction_variant(0x020b, 0xff, 0x20, 0x02, CpuVariant::Wdc65C02) == "bbs7 $20, $0210");
+	assert!(cpu_dis::disassemble_instruction_variant(0x0200, 0xb2, 0x10, 0x00, CpuVariant::Wdc65C02) == "lda ($10)");
+	assert!(cpu_dis::disassemble_instruction_variant(0x0214, 0x7c, 0x00, 0x04, CpuVariant::Wdc65C02) == "jmp ($0400,x)");
+	assert!(cpu_dis::disassemble_instruction_variant(0x0200, 0x1a, 0x00, 0x00, CpuVariant::Wdc65C02) == "inc a");
+	assert!(cpu_dis::disassemble_instruction_variant(0x0200, 0x1a, 0x00, 0x00, CpuVariant::Ricoh2A03) == "nop");
+	assert!(cpu_dis::disassemble_instruction_variant(0x0200, 0xb2, 0x10, 0x00, CpuVariant::Nmos6502) == ".byte $b2");
+}
@@ -0,0 +1,277 @@
+use Core;
+use std::mem;
+
+
+// FCEUX .fm2 per-frame commands
+pub const MOVIE_SOFT_RESET: u8 = 0x01;
+pub const MOVIE_POWER: u8 = 0x02;
+
+// Gamepad characters in FCEUX order, from bit 7 down to bit 0
+const BUTTON_CHARS: &[u8; 8] = b"RLDUTSBA";
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MovieFrame
+{
+	pub commands: u8,
+	pub controller1: u8,
+	pub controller2: u8
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MovieMode
+{
+	Recording,
+	Playing,
+	Finished
+}
+
+
+pub struct Movie
+{
+	pub frames: Vec<MovieFrame>,
+	pub rerecord_count: u32,
+	pub controller2: bool,
+	
+	// Other header lines, kept in order so an imported movie exports unchanged
+	pub header: Vec<(String, String)>,
+	
+	pub mode: MovieMode,
+	pub frame: usize,
+	
+	pending_commands: u8
+}
+
+
+impl Movie
+{
+	pub fn new() -> Movie
+	{
+		Movie
+		{
+			frames: Vec::new(),
+			rerecord_count: 0,
+			controller2: false,
+			
+			header: vec![
+				("emuVersion".to_string(), "0".to_string()),
+				("palFlag".to_string(), "0".to_string()),
+				("romFilename".to_string(), String::new()),
+				("guid".to_string(), String::new()),
+				("fourscore".to_string(), "0".to_string()),
+				("microphone".to_string(), "0".to_string()),
+				("FDS".to_string(), "0".to_string()),
+				("NewPPU".to_string(), "0".to_string())],
+				
+			mode: MovieMode::Finished,
+			frame: 0,
+			
+			pending_commands: 0
+		}
+	}
+	
+	
+	pub fn parse_fm2(text: &str) -> Result<Movie, String>
+	{
+		let mut movie = Movie::new();
+		movie.header.clear();
+		
+		for (line_index, line) in text.lines().enumerate()
+		{
+			let line = line.trim_end_matches('\r');
+			
+			if line.starts_with('|')
+			{
+				let frame = Movie::parse_fm2_frame(line).ok_or(format!("line {}: invalid input record", line_index + 1))?;
+				movie.frames.push(frame);
+				continue;
+			}
+			
+			if line.trim().is_empty()
+				{ continue; }
+				
+			let mut parts = line.splitn(2, ' ');
+			let key = parts.next().unwrap_or("");
+			let value = parts.next().unwrap_or("").to_string();
+			
+			match key
+			{
+				"version" if value != "3" => return Err(format!("unsupported fm2 version {}", value)),
+				"binary" if value != "0" => return Err("binary fm2 movies are not supported".to_string()),
+				"version" | "binary" | "port0" | "port2" => { }
+				"rerecordCount" => movie.rerecord_count = value.parse().map_err(|_| format!("line {}: invalid rerecord count", line_index + 1))?,
+				"port1" => movie.controller2 = value == "1",
+				_ => movie.header.push((key.to_string(), value))
+			}
+		}
+		
+		Ok(movie)
+	}
+	
+	
+	fn parse_fm2_frame(line: &str) -> Option<MovieFrame>
+	{
+		let fields: Vec<&str> = line.split('|').collect();
+		if fields.len() < 4
+			{ return None; }
+			
+		Some(MovieFrame
+		{
+			commands: fields[1].trim().parse().ok()?,
+			controller1: parse_fm2_buttons(fields[2])?,
+			controller2: parse_fm2_buttons(fields[3])?
+		})
+	}
+	
+	
+	pub fn to_fm2(&self) -> String
+	{
+		let mut text = "version 3\n".to_string();
+		
+		for (key, value) in &self.header
+			{ text.push_str(&format!("{} {}\n", key, value)); }
+			
+		text.push_str(&format!("rerecordCount {}\n", self.rerecord_count));
+		text.push_str(&format!("port0 1\nport1 {}\nport2 0\n", if self.controller2 { 1 } else { 0 }));
+		
+		for frame in &self.frames
+		{
+			let controller2 = if self.controller2 { format_fm2_buttons(frame.controller2) } else { String::new() };
+			text.push_str(&format!("|{}|{}|{}||\n", frame.commands, format_fm2_buttons(frame.controller1), controller2));
+		}
+		
+		text
+	}
+	
+	
+	pub fn add_comment(&mut self, comment: &str)
+	{
+		self.header.push(("comment".to_string(), comment.to_string()));
+	}
+	
+	
+	pub fn is_active(&self) -> bool
+	{
+		self.mode != MovieMode::Finished
+	}
+	
+	
+	// The input the game sees when it strobes the controller during this frame
+	pub fn get_input(&self) -> Option<u8>
+	{
+		match self.mode
+		{
+			MovieMode::Playing => self.frames.get(self.frame).map(|frame| frame.controller1),
+			_ => None
+		}
+	}
+	
+	
+	pub fn record_command(&mut self, command: u8)
+	{
+		if self.mode == MovieMode::Recording
+			{ self.pending_commands |= command; }
+	}
+	
+	
+	// Commands due at the start of the current frame, handed out only once
+	pub fn take_commands(&mut self) -> u8
+	{
+		match self.mode
+		{
+			MovieMode::Playing => mem::replace(&mut self.pending_commands, 0),
+			_ => 0
+		}
+	}
+	
+	
+	pub fn end_frame(&mut self, controller1: u8)
+	{
+		match self.mode
+		{
+			MovieMode::Recording =>
+			{
+				self.frames.push(MovieFrame { commands: self.pending_commands, controller1, controller2: 0 });
+				self.pending_commands = 0;
+				self.frame += 1;
+			}
+			
+			MovieMode::Playing =>
+			{
+				self.frame += 1;
+				
+				match self.frames.get(self.frame)
+				{
+					Some(frame) => self.pending_commands = frame.commands,
+					None => self.mode = MovieMode::Finished
+				}
+			}
+			
+			MovieMode::Finished => { }
+		}
+	}
+}
+
+
+impl Core
+{
+	// Movies always start from power-on
+	pub fn record_movie(&mut self, mut movie: Movie)
+	{
+		movie.frames.clear();
+		movie.frame = 0;
+		movie.mode = MovieMode::Recording;
+		movie.pending_commands = 0;
+		
+		self.movie = None;
+		self.reset();
+		self.movie = Some(Box::new(movie));
+	}
+	
+	
+	pub fn play_movie(&mut self, mut movie: Movie)
+	{
+		movie.frame = 0;
+		movie.mode = if movie.frames.is_empty() { MovieMode::Finished } else { MovieMode::Playing };
+		
+		// Power at frame 0 is implied by starting from power-on
+		movie.pending_commands = movie.frames.first().map_or(0, |frame| frame.commands & !MOVIE_POWER);
+		
+		self.movie = None;
+		self.reset();
+		self.movie = Some(Box::new(movie));
+	}
+	
+	
+	pub fn stop_movie(&mut self) -> Option<Box<Movie>>
+	{
+		let mut movie = self.movie.take();
+		
+		if let Some(ref mut movie) = movie
+			{ movie.mode = MovieMode::Finished; }
+			
+		movie
+	}
+}
+
+
+fn parse_fm2_buttons(field: &str) -> Option<u8>
+{
+	if field.is_empty()
+		{ return Some(0); }
+		
+	if field.len() != BUTTON_CHARS.len()
+		{ return None; }
+		
+	Some(field.bytes().enumerate().fold(0, |buttons, (i, c)|
+		if c == b'.' || c == b' ' { buttons } else { buttons | (0x80 >> i) }))
+}
+
+
+fn format_fm2_buttons(buttons: u8) -> String
+{
+	BUTTON_CHARS.iter().enumerate()
+		.map(|(i, &c)| if (buttons & (0x80 >> i)) != 0 { c as char } else { '.' })
+		.collect()
+}
\ No newline at end of file
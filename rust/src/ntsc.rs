@@ -0,0 +1,164 @@
+use std::f32::consts::PI;
+use Palette;
+
+
+pub const NTSC_SCREEN_WIDTH: usize = 602;
+
+const SAMPLES_PER_DOT: usize = 8;
+const SAMPLES_PER_LINE: usize = 256 * SAMPLES_PER_DOT;
+const SAMPLES_PER_LINE_DOT_CRAWL: usize = 4;
+
+
+pub struct NtscFilter
+{
+	pub screen: Vec<u8>,
+	pub sharpness: f32,
+	
+	line: [u16; 256],
+	frame_phase: usize,
+	
+	phase_cos: [f32; 12],
+	phase_sin: [f32; 12]
+}
+
+
+impl NtscFilter
+{
+	pub fn new() -> NtscFilter
+	{
+		let mut phase_cos = [0.0; 12];
+		let mut phase_sin = [0.0; 12];
+		
+		for phase in 0..12
+		{
+			phase_cos[phase] = (PI * phase as f32 / 6.0).cos();
+			phase_sin[phase] = (PI * phase as f32 / 6.0).sin();
+		}
+		
+		NtscFilter
+		{
+			screen: vec![0; NTSC_SCREEN_WIDTH * 240 * 4],
+			sharpness: 0.0,
+			
+			line: [0; 256],
+			frame_phase: 0,
+			
+			phase_cos,
+			phase_sin
+		}
+	}
+	
+	
+	pub fn output_dot(&mut self, scanline: usize, dot: usize, color: u8, mask: u8)
+	{
+		self.line[dot] = Palette::get_index(color, mask) as u16;
+		
+		if dot == 255
+		{
+			self.render_scanline(scanline);
+			
+			// The PPU skips a dot every other frame while rendering, which
+			// shifts the subcarrier phase and makes the artifacts crawl.
+			if scanline == 239 && (mask & 0x18) != 0
+				{ self.frame_phase = if self.frame_phase == 0 { 4 } else { 0 }; }
+		}
+	}
+	
+	
+	fn render_scanline(&mut self, scanline: usize)
+	{
+		let line_phase = (self.frame_phase + scanline * SAMPLES_PER_LINE_DOT_CRAWL) % 12;
+		
+		let mut sum_y = [0.0_f32; SAMPLES_PER_LINE + 1];
+		let mut sum_i = [0.0_f32; SAMPLES_PER_LINE + 1];
+		let mut sum_q = [0.0_f32; SAMPLES_PER_LINE + 1];
+		
+		for sample in 0..SAMPLES_PER_LINE
+		{
+			let phase = (line_phase + sample) % 12;
+			let v = composite_level(self.line[sample / SAMPLES_PER_DOT] as usize, phase);
+			
+			sum_y[sample + 1] = sum_y[sample] + v;
+			sum_i[sample + 1] = sum_i[sample] + v * self.phase_cos[phase];
+			sum_q[sample + 1] = sum_q[sample] + v * self.phase_sin[phase];
+		}
+		
+		// Narrower luma windows keep more detail, but also let more of
+		// the subcarrier through as fringes.
+		let luma_width = (12.0 - self.sharpness * 8.0).clamp(4.0, 20.0) as usize;
+		let chroma_width = 24;
+		
+		for x in 0..NTSC_SCREEN_WIDTH
+		{
+			let center = (x * 2 + 1) * SAMPLES_PER_LINE / (NTSC_SCREEN_WIDTH * 2);
+			
+			let y = NtscFilter::average_window(&sum_y, center, luma_width);
+			let i = NtscFilter::average_window(&sum_i, center, chroma_width);
+			let q = NtscFilter::average_window(&sum_q, center, chroma_width);
+			
+			let (r, g, b) = yiq_to_rgb(y, i, q);
+			
+			let screen_addr = (scanline * NTSC_SCREEN_WIDTH + x) * 4;
+			self.screen[screen_addr + 0] = r;
+			self.screen[screen_addr + 1] = g;
+			self.screen[screen_addr + 2] = b;
+			self.screen[screen_addr + 3] = 0xff;
+		}
+	}
+	
+	
+	fn average_window(sum: &[f32], center: usize, width: usize) -> f32
+	{
+		let start = center.saturating_sub(width / 2);
+		let end = (start + width).min(sum.len() - 1);
+		
+		(sum[end] - sum[start]) / (end - start) as f32
+	}
+}
+
+
+// Returns the normalized composite voltage the PPU outputs for a
+// 9-bit color+emphasis index at one of the 12 subcarrier phases.
+pub fn composite_level(index: usize, phase: usize) -> f32
+{
+	// Voltage levels, relative to sync, for the low and high
+	// halves of the square wave the 2C02 outputs for each hue.
+	const BLACK: f32 = 0.518;
+	const WHITE: f32 = 1.962;
+	const ATTENUATION: f32 = 0.746;
+	const LEVELS: [f32; 8] = [0.350, 0.518, 0.962, 1.550, 1.094, 1.506, 1.962, 1.962];
+	
+	let hue = index & 0xf;
+	let level = if hue > 13 { 1 } else { (index >> 4) & 0x3 };
+	let emphasis = index >> 6;
+	
+	let in_phase = |hue: usize| (hue + phase + 8) % 12 < 6;
+	
+	let high = hue == 0 || (hue <= 12 && in_phase(hue));
+	let mut spot = LEVELS[level + if high { 4 } else { 0 }];
+	
+	if ((emphasis & 1) != 0 && in_phase(0)) ||
+		((emphasis & 2) != 0 && in_phase(4)) ||
+		((emphasis & 4) != 0 && in_phase(8))
+		{ spot *= ATTENUATION; }
+	
+	(spot - BLACK) / (WHITE - BLACK)
+}
+
+
+pub fn yiq_to_rgb(y: f32, i: f32, q: f32) -> (u8, u8, u8)
+{
+	const GAMMA: f32 = 1.8;
+	
+	let to_u8 = |f: f32|
+	{
+		let f = if f <= 0.0 { 0.0 } else { f.powf(2.2 / GAMMA) };
+		(f * 255.95).min(255.0) as u8
+	};
+	
+	(
+		to_u8(y + 0.946882 * i + 0.623557 * q),
+		to_u8(y - 0.274788 * i - 0.635691 * q),
+		to_u8(y - 1.108545 * i + 1.709007 * q)
+	)
+}
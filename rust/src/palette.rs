@@ -0,0 +1,239 @@
+use std::f32::consts::PI;
+use ntsc;
+
+
+pub struct Palette
+{
+	pub colors: [u8; 512 * 4]
+}
+
+
+#[derive(Clone, Copy)]
+pub struct NtscPaletteParams
+{
+	pub hue: f32,
+	pub saturation: f32,
+	pub contrast: f32,
+	pub brightness: f32
+}
+
+
+// Attenuation factor applied to the non-emphasized channels
+// when expanding a 64-entry palette to all emphasis combinations.
+const EMPHASIS_ATTENUATION: f32 = 0.816328;
+
+
+impl Palette
+{
+	pub fn new() -> Palette
+	{
+		Palette::from_64_colors(&PALETTE_DEFAULT)
+	}
+	
+	
+	pub fn from_pal(buffer: &[u8]) -> Option<Palette>
+	{
+		match buffer.len()
+		{
+			0xc0 => Some(Palette::from_64_colors(&Palette::rgb_to_rgba(buffer))),
+			0x600 => Some(Palette::from_512_colors(&Palette::rgb_to_rgba(buffer))),
+			_ => None
+		}
+	}
+	
+	
+	pub fn from_ntsc(params: &NtscPaletteParams) -> Palette
+	{
+		let mut colors = [0; 512 * 4];
+		
+		for index in 0..512
+		{
+			let (r, g, b) = Palette::generate_ntsc_color(index, params);
+			
+			colors[index * 4 + 0] = r;
+			colors[index * 4 + 1] = g;
+			colors[index * 4 + 2] = b;
+			colors[index * 4 + 3] = 0xff;
+		}
+		
+		Palette { colors }
+	}
+	
+	
+	pub fn get_rgba(&self, color: u8, mask: u8) -> &[u8]
+	{
+		let index = Palette::get_index(color, mask);
+		&self.colors[index * 4 .. index * 4 + 4]
+	}
+	
+	
+	pub fn get_index(color: u8, mask: u8) -> usize
+	{
+		(color as usize & 0x3f) | ((mask as usize & 0xe0) << 1)
+	}
+	
+	
+	fn rgb_to_rgba(buffer: &[u8]) -> Vec<u8>
+	{
+		let mut rgba = Vec::with_capacity(buffer.len() / 3 * 4);
+		
+		for rgb in buffer.chunks(3)
+		{
+			rgba.extend_from_slice(rgb);
+			rgba.push(0xff);
+		}
+		
+		rgba
+	}
+	
+	
+	fn from_512_colors(rgba: &[u8]) -> Palette
+	{
+		let mut colors = [0; 512 * 4];
+		colors.copy_from_slice(&rgba[0..512 * 4]);
+		
+		Palette { colors }
+	}
+	
+	
+	fn from_64_colors(rgba: &[u8]) -> Palette
+	{
+		let mut colors = [0; 512 * 4];
+		
+		for emphasis in 0..8
+		{
+			for color in 0..64
+			{
+				let src = color * 4;
+				let dest = ((emphasis << 6) | color) * 4;
+				
+				for channel in 0..3
+				{
+					// Bit 0 emphasizes red, bit 1 green, and bit 2 blue,
+					// which darkens the other two channels.
+					let attenuated = (emphasis & !(1 << channel)) != 0;
+					
+					colors[dest + channel] = if attenuated
+						{ (rgba[src + channel] as f32 * EMPHASIS_ATTENUATION) as u8 }
+					else
+						{ rgba[src + channel] };
+				}
+				
+				colors[dest + 3] = rgba[src + 3];
+			}
+		}
+		
+		Palette { colors }
+	}
+	
+	
+	fn generate_ntsc_color(index: usize, params: &NtscPaletteParams) -> (u8, u8, u8)
+	{
+		let mut y = 0.0;
+		let mut i = 0.0;
+		let mut q = 0.0;
+		
+		for phase in 0..12
+		{
+			let v = ntsc::composite_level(index, phase) / 12.0;
+			let angle = PI * (phase as f32 + params.hue / 30.0) / 6.0;
+			
+			y += v;
+			i += v * angle.cos();
+			q += v * angle.sin();
+		}
+		
+		i *= params.saturation;
+		q *= params.saturation;
+		y = y * params.contrast + params.brightness;
+		
+		ntsc::yiq_to_rgb(y, i, q)
+	}
+}
+
+
+impl NtscPaletteParams
+{
+	pub fn new() -> NtscPaletteParams
+	{
+		NtscPaletteParams
+		{
+			hue: 0.0,
+			saturation: 1.0,
+			contrast: 1.0,
+			brightness: 0.0
+		}
+	}
+}
+
+
+static PALETTE_DEFAULT: [u8; 64 * 4] =
+[
+	0x75, 0x75, 0x75, 0xff,
+	0x27, 0x1b, 0x8f, 0xff,
+	0x00, 0x00, 0xab, 0xff,
+	0x47, 0x00, 0x9f, 0xff,
+	0x8f, 0x00, 0x77, 0xff,
+	0xab, 0x00, 0x13, 0xff,
+	0xa7, 0x00, 0x00, 0xff,
+	0x7f, 0x0b, 0x00, 0xff,
+	0x43, 0x2f, 0x00, 0xff,
+	0x00, 0x47, 0x00, 0xff,
+	0x00, 0x51, 0x00, 0xff,
+	0x00, 0x3f, 0x17, 0xff,
+	0x1b, 0x3f, 0x5f, 0xff,
+	0x00, 0x00, 0x00, 0xff,
+	0x00, 0x00, 0x00, 0xff,
+	0x00, 0x00, 0x00, 0xff,
+
+	0xbc, 0xbc, 0xbc, 0xff,
+	0x00, 0x73, 0xef, 0xff,
+	0x23, 0x3b, 0xef, 0xff,
+	0x83, 0x00, 0xf3, 0xff,
+	0xbf, 0x00, 0xbf, 0xff,
+	0xe7, 0x00, 0x5b, 0xff,
+	0xdb, 0x2b, 0x00, 0xff,
+	0xcb, 0x4f, 0x0f, 0xff,
+	0x8b, 0x73, 0x00, 0xff,
+	0x00, 0x97, 0x00, 0xff,
+	0x00, 0xab, 0x00, 0xff,
+	0x00, 0x93, 0x3b, 0xff,
+	0x00, 0x83, 0x8b, 0xff,
+	0x00, 0x00, 0x00, 0xff,
+	0x00, 0x00, 0x00, 0xff,
+	0x00, 0x00, 0x00, 0xff,
+
+	0xff, 0xff, 0xff, 0xff,
+	0x3f, 0xbf, 0xff, 0xff,
+	0x5f, 0x97, 0xff, 0xff,
+	0xa7, 0x8b, 0xfd, 0xff,
+	0xf7, 0x7b, 0xff, 0xff,
+	0xff, 0x77, 0xb7, 0xff,
+	0xff, 0x77, 0x63, 0xff,
+	0xff, 0x9b, 0x3b, 0xff,
+	0xf3, 0xbf, 0x3f, 0xff,
+	0x83, 0xd3, 0x13, 0xff,
+	0x4f, 0xdf, 0x4b, 0xff,
+	0x58, 0xf8, 0x98, 0xff,
+	0x00, 0xeb, 0xdb, 0xff,
+	0x00, 0x00, 0x00, 0xff,
+	0x00, 0x00, 0x00, 0xff,
+	0x00, 0x00, 0x00, 0xff,
+
+	0xff, 0xff, 0xff, 0xff,
+	0xab, 0xe7, 0xff, 0xff,
+	0xc7, 0xd7, 0xff, 0xff,
+	0xd7, 0xcb, 0xff, 0xff,
+	0xff, 0xc7, 0xff, 0xff,
+	0xff, 0xc7, 0xdb, 0xff,
+	0xff, 0xbf, 0xb3, 0xff,
+	0xff, 0xdb, 0xab, 0xff,
+	0xff, 0xe7, 0xa3, 0xff,
+	0xe3, 0xff, 0xa3, 0xff,
+	0xab, 0xf3, 0xbf, 0xff,
+	0xb3, 0xff, 0xcf, 0xff,
+	0x9f, 0xff, 0xf3, 0xff,
+	0x00, 0x00, 0x00, 0xff,
+	0x00, 0x00, 0x00, 0xff,
+	0x00, 0x00, 0x00, 0xff,
+];
\ No newline at end of file
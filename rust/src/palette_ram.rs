@@ -0,0 +1,47 @@
+pub struct PaletteRam
+{
+	data: [u8; 0x20]
+}
+
+
+impl PaletteRam
+{
+	pub fn new() -> PaletteRam
+	{
+		PaletteRam
+		{
+			data: [0; 0x20]
+		}
+	}
+	
+	
+	pub fn reset(&mut self)
+	{
+		self.data = [0; 0x20];
+	}
+	
+	
+	pub fn read(&self, addr: u16) -> u8
+	{
+		self.data[PaletteRam::get_index(addr)]
+	}
+	
+	
+	pub fn write(&mut self, addr: u16, val: u8)
+	{
+		self.data[PaletteRam::get_index(addr)] = val & 0x3f;
+	}
+	
+	
+	fn get_index(addr: u16) -> usize
+	{
+		// $3f10, $3f14, $3f18 and $3f1c are mirrors of the
+		// background entries at $3f00, $3f04, $3f08 and $3f0c
+		let index = (addr & 0x1f) as usize;
+		
+		if (index & 0x13) == 0x10
+			{ index & 0x0f }
+		else
+			{ index }
+	}
+}
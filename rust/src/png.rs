@@ -0,0 +1,78 @@
+use std::io;
+
+
+// Uncompressed (stored) deflate blocks keep the encoder tiny; the files are only debug output
+pub fn write_png(output: &mut io::Write, width: usize, height: usize, rgba: &[u8]) -> io::Result<()>
+{
+	let mut raw = Vec::with_capacity((width * 4 + 1) * height);
+	for row in 0..height
+	{
+		raw.push(0);
+		raw.extend_from_slice(&rgba[row * width * 4 .. (row + 1) * width * 4]);
+	}
+	
+	let mut zlib = vec![0x78, 0x01];
+	let mut chunks = raw.chunks(0xffff).peekable();
+	
+	while let Some(chunk) = chunks.next()
+	{
+		zlib.push(if chunks.peek().is_none() { 1 } else { 0 });
+		zlib.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+		zlib.extend_from_slice(&(!(chunk.len() as u16)).to_le_bytes());
+		zlib.extend_from_slice(chunk);
+	}
+	
+	zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+	
+	let mut header = Vec::with_capacity(13);
+	header.extend_from_slice(&(width as u32).to_be_bytes());
+	header.extend_from_slice(&(height as u32).to_be_bytes());
+	header.extend_from_slice(&[8, 6, 0, 0, 0]);
+	
+	output.write_all(b"\x89PNG\r\n\x1a\n")?;
+	write_chunk(output, b"IHDR", &header)?;
+	write_chunk(output, b"IDAT", &zlib)?;
+	write_chunk(output, b"IEND", &[])
+}
+
+
+fn write_chunk(output: &mut io::Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()>
+{
+	let mut crc_data = Vec::with_capacity(4 + data.len());
+	crc_data.extend_from_slice(kind);
+	crc_data.extend_from_slice(data);
+	
+	output.write_all(&(data.len() as u32).to_be_bytes())?;
+	output.write_all(&crc_data)?;
+	output.write_all(&crc32(&crc_data).to_be_bytes())
+}
+
+
+pub fn crc32(data: &[u8]) -> u32
+{
+	let mut crc = 0xffffffff_u32;
+	
+	for &byte in data
+	{
+		crc ^= byte as u32;
+		for _ in 0..8
+			{ crc = if (crc & 1) != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 }; }
+	}
+	
+	!crc
+}
+
+
+fn adler32(data: &[u8]) -> u32
+{
+	let mut a = 1_u32;
+	let mut b = 0_u32;
+	
+	for &byte in data
+	{
+		a = (a + byte as u32) % 65521;
+		b = (b + a) % 65521;
+	}
+	
+	(b << 16) | a
+}
\ No newline at end of file
@@ -6,6 +6,9 @@ type PpuDriveNmiFn = Fn(bool);
 
 static FLAG_VBLANK: u8 = 0b10000000;
 
+// Roughly 600ms before an unrefreshed I/O latch bit fades to 0
+static IO_LATCH_DECAY_FRAMES: usize = 36;
+
 
 pub struct Ppu
 {
@@ -24,6 +27,12 @@ pub struct Ppu
 	address_nibble: bool,
 	internal_latch: u8,
 	
+	// Set by the reset button until the end of the next vblank
+	ignore_reg_writes: bool,
+	
+	io_latch: u8,
+	io_latch_refresh_frame: [usize; 8],
+	
 	pub oam: [u8; 0x100],
 	oam_address: u8,
 	
@@ -75,6 +84,11 @@ impl Ppu
 			address_nibble: false,
 			internal_latch: 0,
 			
+			ignore_reg_writes: false,
+			
+			io_latch: 0,
+			io_latch_refresh_frame: [0; 8],
+			
 			oam: [0; 0x100],
 			oam_address: 0,
 			
@@ -108,6 +122,11 @@ impl Ppu
 		self.address_nibble = false;
 		self.internal_latch = 0;
 		
+		self.ignore_reg_writes = false;
+		
+		self.io_latch = 0;
+		self.io_latch_refresh_frame = [0; 8];
+		
 		self.oam = [0; 0x100];
 		self.oam_address = 0;
 		
@@ -117,8 +136,79 @@ impl Ppu
 	}
 	
 	
+	// The reset button clears the write registers and latches, but not the
+	// status, OAM, the VRAM address or where the beam is. $2000, $2001, $2005
+	// and $2006 then ignore writes until the end of the next vblank.
+	pub fn soft_reset(&mut self)
+	{
+		self.reg_ctrl = 0;
+		self.reg_mask = 0;
+		
+		self.scroll_t = 0;
+		self.scroll_x = 0;
+		
+		self.address_nibble = false;
+		self.internal_latch = 0;
+		
+		self.ignore_reg_writes = true;
+	}
+	
+	
+	pub fn get_scanline(&self) -> usize
+	{
+		self.scanline
+	}
+	
+	
+	pub fn get_dot(&self) -> usize
+	{
+		self.dot
+	}
+	
+	
+	pub fn get_frame(&self) -> usize
+	{
+		self.frame
+	}
+	
+	
+	pub fn get_reg_ctrl(&self) -> u8
+	{
+		self.reg_ctrl
+	}
+	
+	
+	pub fn get_scroll_t(&self) -> u16
+	{
+		self.scroll_t
+	}
+	
+	
+	pub fn get_scroll_x(&self) -> u8
+	{
+		self.scroll_x
+	}
+	
+	
+	pub fn get_scroll_v(&self) -> u16
+	{
+		self.scroll_v
+	}
+	
+	
+	pub fn write_reg_status(&mut self, val: u8)
+	{
+		self.refresh_io_latch(val, 0xff);
+	}
+	
+	
 	pub fn write_reg_ctrl(&mut self, val: u8)
 	{
+		self.refresh_io_latch(val, 0xff);
+		
+		if self.ignore_reg_writes
+			{ return; }
+			
 		self.reg_ctrl = val;
 		self.scroll_t &= !(0x3 << 10);
 		self.scroll_t |= (val as u16 & 0x3) << 10;
@@ -127,12 +217,22 @@ impl Ppu
 	
 	pub fn write_reg_mask(&mut self, val: u8)
 	{
+		self.refresh_io_latch(val, 0xff);
+		
+		if self.ignore_reg_writes
+			{ return; }
+			
 		self.reg_mask = val;
 	}
 	
 	
 	pub fn write_reg_scroll(&mut self, val: u8)
 	{
+		self.refresh_io_latch(val, 0xff);
+		
+		if self.ignore_reg_writes
+			{ return; }
+			
 		if !self.address_nibble
 		{
 			self.scroll_x = val & 0x7;
@@ -155,6 +255,11 @@ impl Ppu
 	
 	pub fn write_reg_addr(&mut self, val: u8)
 	{
+		self.refresh_io_latch(val, 0xff);
+		
+		if self.ignore_reg_writes
+			{ return; }
+			
 		if !self.address_nibble
 		{
 			self.scroll_t &= !(0x1 << 14);
@@ -174,20 +279,25 @@ impl Ppu
 	
 	pub fn write_reg_data(&mut self, val: u8)
 	{
-		(self.hook_write)(self.scroll_v, val);
-		self.scroll_v += if (self.reg_ctrl & 0x04) == 0 { 1 } else { 32 };
-		self.scroll_v &= 0xffff;
+		self.refresh_io_latch(val, 0xff);
+		
+		(self.hook_write)(self.scroll_v & 0x3fff, val);
+		self.increment_data_addr();
 	}
 	
 	
 	pub fn write_reg_oamaddr(&mut self, val: u8)
 	{
+		self.refresh_io_latch(val, 0xff);
+		
 		self.oam_address = val;
 	}
 	
 	
 	pub fn write_reg_oamdata(&mut self, val: u8)
 	{
+		self.refresh_io_latch(val, 0xff);
+		
 		self.oam[self.oam_address as usize] = val;
 		self.oam_address = self.oam_address.wrapping_add(1);
 	}
@@ -195,7 +305,10 @@ impl Ppu
 	
 	pub fn read_reg_status(&mut self) -> u8
 	{
-		let val = self.reg_status;
+		self.decay_io_latch();
+		
+		let val = (self.reg_status & 0xe0) | (self.io_latch & 0x1f);
+		self.refresh_io_latch(val, 0xe0);
 		
 		self.address_nibble = false;
 		self.reg_status &= 0x7f;
@@ -206,29 +319,144 @@ impl Ppu
 	
 	pub fn read_reg_data(&mut self) -> u8
 	{
+		self.decay_io_latch();
+		
 		let mut val = self.internal_latch;
+		let addr = self.scroll_v & 0x3fff;
 		
-		if self.scroll_v >= 0x3f00 && self.scroll_v < 0x4000
+		if addr >= 0x3f00
 		{
-			self.internal_latch = (self.hook_read)(self.scroll_v - 0x1000);
+			// The read buffer is filled from the nametable underneath
+			self.internal_latch = (self.hook_read)(addr - 0x1000);
 			
-			val = (self.hook_read)(self.scroll_v);
+			val = (self.hook_read)(addr);
 			if (self.reg_mask & 1) != 0
 				{ val &= 0x30; }
+				
+			// Palette RAM only drives the lower 6 bits
+			val = (self.io_latch & 0xc0) | (val & 0x3f);
+			self.refresh_io_latch(val, 0x3f);
 		}
 		else
 		{
-			self.internal_latch = (self.hook_read)(self.scroll_v);
+			self.internal_latch = (self.hook_read)(addr);
+			self.refresh_io_latch(val, 0xff);
 		}
 		
-		self.scroll_v += if (self.reg_ctrl & 0x04) == 0 { 1 } else { 32 };
+		self.increment_data_addr();
 		val
 	}
 	
 	
 	pub fn read_reg_oamdata(&mut self) -> u8
 	{
-		self.oam[self.oam_address as usize] & (if (self.oam_address & 0x3) == 0x2 { 0xe3 } else { 0xff })
+		let val = self.oam[self.oam_address as usize] & (if (self.oam_address & 0x3) == 0x2 { 0xe3 } else { 0xff });
+		self.refresh_io_latch(val, 0xff);
+		val
+	}
+	
+	
+	pub fn read_reg_open_bus(&mut self) -> u8
+	{
+		self.decay_io_latch();
+		self.io_latch
+	}
+	
+	
+	// What a CPU read of the register would return, without the read's side effects
+	pub fn peek_reg(&self, addr: u16) -> u8
+	{
+		match addr % 8
+		{
+			2 => (self.reg_status & 0xe0) | (self.io_latch & 0x1f),
+			4 => self.oam[self.oam_address as usize] & (if (self.oam_address & 0x3) == 0x2 { 0xe3 } else { 0xff }),
+			7 => self.internal_latch,
+			_ => self.io_latch
+		}
+	}
+	
+	
+	pub fn is_rendering(&self) -> bool
+	{
+		let rendering_enabled = (self.reg_mask & 0x18) != 0;
+		let rendering_scanline = self.scanline < 240 || self.scanline == 261;
+		
+		rendering_enabled && rendering_scanline
+	}
+	
+	
+	fn increment_data_addr(&mut self)
+	{
+		// While rendering, $2007 accesses bump the scroll counters
+		// the same way the background fetches do
+		if self.is_rendering()
+		{
+			self.increment_scroll_coarse_x();
+			self.increment_scroll_y();
+		}
+		else
+		{
+			self.scroll_v += if (self.reg_ctrl & 0x04) == 0 { 1 } else { 32 };
+			self.scroll_v &= 0x7fff;
+		}
+	}
+	
+	
+	fn increment_scroll_coarse_x(&mut self)
+	{
+		if (self.scroll_v & 0x1f) == 0x1f
+		{
+			self.scroll_v &= !0x1f;
+			self.scroll_v ^= 0x400;
+		}
+		else
+			{ self.scroll_v = (self.scroll_v + 1) & 0x7fff; }
+	}
+	
+	
+	fn increment_scroll_y(&mut self)
+	{
+		if (self.scroll_v & 0x7000) != 0x7000
+			{ self.scroll_v = (self.scroll_v + 0x1000) & 0x7fff; }
+		else
+		{
+			self.scroll_v &= !0x7000;
+			
+			let mut y = (self.scroll_v & 0x3e0) >> 5;
+			if y == 29
+			{
+				y = 0;
+				self.scroll_v ^= 0x800;
+			}
+			else if y == 31
+				{ y = 0; }
+			else
+				{ y += 1; }
+
+			self.scroll_v = (self.scroll_v & !0x3e0) | (y << 5);
+		}
+	}
+	
+	
+	fn refresh_io_latch(&mut self, val: u8, mask: u8)
+	{
+		self.io_latch = (self.io_latch & !mask) | (val & mask);
+		
+		for bit in 0..8
+		{
+			if (mask & (1 << bit)) != 0
+				{ self.io_latch_refresh_frame[bit] = self.frame; }
+		}
+	}
+	
+	
+	fn decay_io_latch(&mut self)
+	{
+		for bit in 0..8
+		{
+			if self.frame.wrapping_sub(self.io_latch_refresh_frame[bit]) >= IO_LATCH_DECAY_FRAMES
+				{ self.io_latch &= !(1 << bit); }
+		}
 	}
 	
 	
@@ -249,6 +477,7 @@ impl Ppu
 			{
 				self.reg_status &= !FLAG_VBLANK;
 				self.reg_status &= 0x1f;
+				self.ignore_reg_writes = false;
 			}
 			
 			else if self.dot >= 280 && self.dot < 305
@@ -283,8 +512,8 @@ impl Ppu
 		{
 			if (self.reg_mask & 0x18) == 0
 			{
-				let bkg_pixel_color = if self.scroll_v >= 0x3f00 && self.scroll_v < 0x4000
-					{ 0x3f & (self.hook_read)(self.scroll_v) }
+				let bkg_pixel_color = if (self.scroll_v & 0x3fff) >= 0x3f00
+					{ 0x3f & (self.hook_read)(self.scroll_v & 0x3fff) }
 				else
 					{ 0x3f & (self.hook_read)(0x3f00) };
 					
@@ -325,15 +554,7 @@ impl Ppu
 				self.blend_bkg_with_spr_and_output(bitplane_dot, color);
 				
 				if dot_into_tile == 7
-				{
-					if (self.scroll_v & 0x1f) == 0x1f
-					{
-						self.scroll_v &= !0x1f;
-						self.scroll_v ^= 0x400;
-					}
-					else
-						{ self.scroll_v = (self.scroll_v + 1) & 0xffff; }
-				}
+					{ self.increment_scroll_coarse_x(); }
 			}
 		}
 		
@@ -346,27 +567,7 @@ impl Ppu
 		else if self.dot == 257
 		{
 			if (self.reg_mask & 0x18) != 0
-			{
-				if (self.scroll_v & 0x7000) != 0x7000
-					{ self.scroll_v = (self.scroll_v + 0x1000) & 0xffff; }
-				else
-				{
-					self.scroll_v &= !0x7000;
-					
-					let mut y = (self.scroll_v & 0x3e0) >> 5;
-					if y == 29
-					{
-						y = 0;
-						self.scroll_v ^= 0x800;
-					}
-					else if y == 31
-						{ y = 0; }
-					else
-						{ y += 1; }
-
-					self.scroll_v = (self.scroll_v & !0x3e0) | (y << 5);
-				}
-			}
+				{ self.increment_scroll_y(); }
 		}
 		
 		else if self.dot == 258
@@ -473,9 +674,9 @@ impl Ppu
 			let flip_h = attr & 0x40 != 0;
 			let flip_v = attr & 0x80 != 0;
 			
-			let mut pattern_table;
+			let pattern_table;
 			let mut pattern_index;
-			let mut pattern_row;
+			let pattern_row;
 			
 			if spr_height == 16
 			{
@@ -0,0 +1,454 @@
+use Cpu;
+use std::cell::RefCell;
+use std::panic;
+use std::rc::Rc;
+
+
+// Runs single-instruction test vectors in the ProcessorTests JSON format, where each test
+// gives the initial and final registers and memory plus every bus access in between
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcessorTestResults
+{
+	pub passed: usize,
+	pub failures: Vec<String>
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BusAccess
+{
+	addr: u16,
+	value: u8,
+	write: bool
+}
+
+
+struct RecordingBus
+{
+	memory: Vec<u8>,
+	accesses: Vec<BusAccess>
+}
+
+
+pub fn run_processor_tests(json: &str) -> Result<ProcessorTestResults, String>
+{
+	let tests = match Json::parse(json)?
+	{
+		Json::Array(tests) => tests,
+		_ => return Err("expected an array of tests".to_string())
+	};
+	
+	let mut results = ProcessorTestResults
+	{
+		passed: 0,
+		failures: Vec::new()
+	};
+	
+	for test in &tests
+	{
+		let name = test.get("name").and_then(Json::as_str).unwrap_or("?").to_string();
+		
+		let result = panic::catch_unwind(panic::AssertUnwindSafe(|| run_processor_test(test)));
+		let result = match result
+		{
+			Ok(result) => result,
+			Err(_) => Err("panicked".to_string())
+		};
+		
+		match result
+		{
+			Ok(()) => results.passed += 1,
+			Err(message) => results.failures.push(format!("{}: {}", name, message))
+		}
+	}
+	
+	Ok(results)
+}
+
+
+fn run_processor_test(test: &Json) -> Result<(), String>
+{
+	let initial = test.get("initial").ok_or("missing initial state")?;
+	let expected = test.get("final").ok_or("missing final state")?;
+	let expected_cycles = test.get("cycles").and_then(Json::as_array).ok_or("missing cycles")?;
+	
+	let bus = Rc::new(RefCell::new(RecordingBus
+	{
+		memory: vec![0; 0x10000],
+		accesses: Vec::new()
+	}));
+	
+	for &(addr, value) in &read_ram(initial)?
+		{ bus.borrow_mut().memory[addr as usize] = value; }
+		
+	let bus_read = bus.clone();
+	let bus_write = bus.clone();
+	
+	let mut cpu = Cpu::new();
+	cpu.hook_read = Box::new(move |addr|
+	{
+		let mut bus = bus_read.borrow_mut();
+		let value = bus.memory[addr as usize];
+		bus.accesses.push(BusAccess { addr, value, write: false });
+		value
+	});
+	
+	cpu.hook_write = Box::new(move |addr, value|
+	{
+		let mut bus = bus_write.borrow_mut();
+		bus.memory[addr as usize] = value;
+		bus.accesses.push(BusAccess { addr, value, write: true });
+	});
+	
+	// Skip the reset sequence, then set the registers it would have touched
+	cpu.reset();
+	cpu.set_pc(read_field(initial, "pc")?);
+	cpu.reg_s = read_field(initial, "s")? as u8;
+	cpu.reg_a = read_field(initial, "a")? as u8;
+	cpu.reg_x = read_field(initial, "x")? as u8;
+	cpu.reg_y = read_field(initial, "y")? as u8;
+	cpu.reg_p = read_field(initial, "p")? as u8;
+	
+	// Counting from this fetch up to the next one gives the length of the instruction, but
+	// that next fetch isn't part of the test
+	cpu.clock();
+	let cycles = cpu.step_instruction();
+	
+	let mut accesses = bus.borrow().accesses.clone();
+	accesses.pop();
+	
+	let state = cpu.get_state(&|addr| bus.borrow().memory[addr as usize]);
+	let registers =
+	[
+		("pc", state.pc),
+		("s", state.s as u16),
+		("a", state.a as u16),
+		("x", state.x as u16),
+		("y", state.y as u16),
+		("p", state.p as u16)
+	];
+	
+	for &(field, value) in &registers
+	{
+		let expected_value = read_field(expected, field)?;
+		if value != expected_value
+			{ return Err(format!("{} = ${:02x}, expected ${:02x}", field, value, expected_value)); }
+	}
+	
+	for &(addr, expected_value) in &read_ram(expected)?
+	{
+		let value = bus.borrow().memory[addr as usize];
+		if value != expected_value
+			{ return Err(format!("[${:04x}] = ${:02x}, expected ${:02x}", addr, value, expected_value)); }
+	}
+	
+	for (index, expected_cycle) in expected_cycles.iter().enumerate()
+	{
+		let expected_access = read_access(expected_cycle)?;
+		
+		match accesses.get(index)
+		{
+			Some(access) if *access == expected_access => { }
+			Some(access) => return Err(format!("cycle {}: {}, expected {}", index + 1, format_access(access), format_access(&expected_access))),
+			None => return Err(format!("cycle {}: nothing, expected {}", index + 1, format_access(&expected_access)))
+		}
+	}
+	
+	if cycles != expected_cycles.len() || accesses.len() != expected_cycles.len()
+		{ return Err(format!("took {} cycles with {} accesses, expected {}", cycles, accesses.len(), expected_cycles.len())); }
+		
+	Ok(())
+}
+
+
+fn read_field(state: &Json, field: &str) -> Result<u16, String>
+{
+	state.get(field).and_then(Json::as_u16).ok_or(format!("missing or invalid `{}`", field))
+}
+
+
+fn read_ram(state: &Json) -> Result<Vec<(u16, u8)>, String>
+{
+	let entries = state.get("ram").and_then(Json::as_array).ok_or("missing ram")?;
+	
+	entries.iter().map(|entry|
+	{
+		match entry.as_array()
+		{
+			Some([addr, value]) => match (addr.as_u16(), value.as_u16())
+			{
+				(Some(addr), Some(value)) if value <= 0xff => Ok((addr, value as u8)),
+				_ => Err("invalid ram entry".to_string())
+			},
+			
+			_ => Err("invalid ram entry".to_string())
+		}
+	}).collect()
+}
+
+
+fn read_access(cycle: &Json) -> Result<BusAccess, String>
+{
+	match cycle.as_array()
+	{
+		Some([addr, value, kind]) => match (addr.as_u16(), value.as_u16(), kind.as_str())
+		{
+			(Some(addr), Some(value), Some("read")) if value <= 0xff => Ok(BusAccess { addr, value: value as u8, write: false }),
+			(Some(addr), Some(value), Some("write")) if value <= 0xff => Ok(BusAccess { addr, value: value as u8, write: true }),
+			_ => Err("invalid cycle".to_string())
+		},
+		
+		_ => Err("invalid cycle".to_string())
+	}
+}
+
+
+fn format_access(access: &BusAccess) -> String
+{
+	format!("{} ${:04x} = ${:02x}", if access.write { "write" } else { "read" }, access.addr, access.value)
+}
+
+
+// Just enough JSON for the test files
+#[derive(Clone, Debug, PartialEq)]
+enum Json
+{
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>)
+}
+
+
+impl Json
+{
+	fn parse(text: &str) -> Result<Json, String>
+	{
+		let mut parser = JsonParser { bytes: text.as_bytes(), index: 0 };
+		
+		let value = parser.parse_value()?;
+		parser.skip_whitespace();
+		
+		if parser.index < parser.bytes.len()
+			{ return Err(format!("unexpected data at offset {}", parser.index)); }
+			
+		Ok(value)
+	}
+	
+	
+	fn get(&self, key: &str) -> Option<&Json>
+	{
+		match *self
+		{
+			Json::Object(ref fields) => fields.iter().find(|field| field.0 == key).map(|field| &field.1),
+			_ => None
+		}
+	}
+	
+	
+	fn as_array(&self) -> Option<&[Json]>
+	{
+		match *self
+		{
+			Json::Array(ref values) => Some(values),
+			_ => None
+		}
+	}
+	
+	
+	fn as_str(&self) -> Option<&str>
+	{
+		match *self
+		{
+			Json::String(ref text) => Some(text),
+			_ => None
+		}
+	}
+	
+	
+	fn as_u16(&self) -> Option<u16>
+	{
+		match *self
+		{
+			Json::Number(n) if n >= 0.0 && n <= 65535.0 && n.fract() == 0.0 => Some(n as u16),
+			_ => None
+		}
+	}
+}
+
+
+struct JsonParser<'a>
+{
+	bytes: &'a [u8],
+	index: usize
+}
+
+
+impl<'a> JsonParser<'a>
+{
+	fn parse_value(&mut self) -> Result<Json, String>
+	{
+		self.skip_whitespace();
+		
+		match self.peek()
+		{
+			Some(b'{') => self.parse_object(),
+			Some(b'[') => self.parse_array(),
+			Some(b'"') => Ok(Json::String(self.parse_string()?)),
+			Some(b't') => self.parse_literal("true", Json::Bool(true)),
+			Some(b'f') => self.parse_literal("false", Json::Bool(false)),
+			Some(b'n') => self.parse_literal("null", Json::Null),
+			Some(_) => self.parse_number(),
+			None => Err("unexpected end of data".to_string())
+		}
+	}
+	
+	
+	fn parse_object(&mut self) -> Result<Json, String>
+	{
+		let mut fields = Vec::new();
+		self.index += 1;
+		
+		self.skip_whitespace();
+		if self.peek() == Some(b'}')
+		{
+			self.index += 1;
+			return Ok(Json::Object(fields));
+		}
+		
+		loop
+		{
+			self.skip_whitespace();
+			let key = self.parse_string()?;
+			
+			self.expect(b':')?;
+			fields.push((key, self.parse_value()?));
+			
+			self.skip_whitespace();
+			match self.next()
+			{
+				Some(b',') => continue,
+				Some(b'}') => return Ok(Json::Object(fields)),
+				_ => return Err(format!("expected `,` or `}}` at offset {}", self.index))
+			}
+		}
+	}
+	
+	
+	fn parse_array(&mut self) -> Result<Json, String>
+	{
+		let mut values = Vec::new();
+		self.index += 1;
+		
+		self.skip_whitespace();
+		if self.peek() == Some(b']')
+		{
+			self.index += 1;
+			return Ok(Json::Array(values));
+		}
+		
+		loop
+		{
+			values.push(self.parse_value()?);
+			
+			self.skip_whitespace();
+			match self.next()
+			{
+				Some(b',') => continue,
+				Some(b']') => return Ok(Json::Array(values)),
+				_ => return Err(format!("expected `,` or `]` at offset {}", self.index))
+			}
+		}
+	}
+	
+	
+	// Only the simple escapes; test names are plain hex bytes
+	fn parse_string(&mut self) -> Result<String, String>
+	{
+		self.expect(b'"')?;
+		let mut bytes = Vec::new();
+		
+		loop
+		{
+			match self.next()
+			{
+				Some(b'"') => break,
+				Some(b'\\') => match self.next()
+				{
+					Some(b'n') => bytes.push(b'\n'),
+					Some(b't') => bytes.push(b'\t'),
+					Some(c @ b'"') | Some(c @ b'\\') | Some(c @ b'/') => bytes.push(c),
+					_ => return Err(format!("unsupported escape at offset {}", self.index))
+				},
+				
+				Some(c) => bytes.push(c),
+				None => return Err("unterminated string".to_string())
+			}
+		}
+		
+		String::from_utf8(bytes).map_err(|_| "invalid UTF-8 in string".to_string())
+	}
+	
+	
+	fn parse_number(&mut self) -> Result<Json, String>
+	{
+		let start = self.index;
+		
+		while let Some(c) = self.peek()
+		{
+			match c
+			{
+				b'0' ..= b'9' | b'-' | b'+' | b'.' | b'e' | b'E' => self.index += 1,
+				_ => break
+			}
+		}
+		
+		let text = String::from_utf8_lossy(&self.bytes[start .. self.index]).to_string();
+		text.parse().map(Json::Number).map_err(|_| format!("invalid value at offset {}", start))
+	}
+	
+	
+	fn parse_literal(&mut self, literal: &str, value: Json) -> Result<Json, String>
+	{
+		if !self.bytes[self.index..].starts_with(literal.as_bytes())
+			{ return Err(format!("invalid value at offset {}", self.index)); }
+			
+		self.index += literal.len();
+		Ok(value)
+	}
+	
+	
+	fn expect(&mut self, c: u8) -> Result<(), String>
+	{
+		self.skip_whitespace();
+		
+		match self.next()
+		{
+			Some(next) if next == c => Ok(()),
+			_ => Err(format!("expected `{}` at offset {}", c as char, self.index))
+		}
+	}
+	
+	
+	fn skip_whitespace(&mut self)
+	{
+		while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek()
+			{ self.index += 1; }
+	}
+	
+	
+	fn peek(&self) -> Option<u8>
+	{
+		self.bytes.get(self.index).cloned()
+	}
+	
+	
+	fn next(&mut self) -> Option<u8>
+	{
+		let c = self.peek();
+		self.index += 1;
+		c
+	}
+}
\ No newline at end of file
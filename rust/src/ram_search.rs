@@ -0,0 +1,174 @@
+use Core;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchSize
+{
+	Byte,
+	Word
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchFilter
+{
+	// Comparisons against the value at the previous search step
+	Unchanged,
+	Changed,
+	IncreasedBy(i64),
+	DecreasedBy(i64),
+	Greater,
+	Less,
+	
+	Value(i64)
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchResult
+{
+	pub addr: u16,
+	pub value: i64,
+	pub previous: i64
+}
+
+
+// Narrows down internal RAM and PRG RAM addresses by how their values change over time
+pub struct RamSearch
+{
+	size: SearchSize,
+	signed: bool,
+	
+	addrs: Vec<u16>,
+	snapshot: Vec<u8>,
+	candidates: Vec<usize>
+}
+
+
+impl RamSearch
+{
+	pub fn new(core: &Core, size: SearchSize, signed: bool) -> RamSearch
+	{
+		let mut addrs: Vec<u16> = (0x0000..0x0800).collect();
+		addrs.extend((0..core.cartridge.get_prg_ram_size().min(0x2000)).map(|i| 0x6000 + i as u16));
+		
+		let mut search = RamSearch
+		{
+			size,
+			signed,
+			
+			addrs,
+			snapshot: Vec::new(),
+			candidates: Vec::new()
+		};
+		
+		search.reset(core);
+		search
+	}
+	
+	
+	// Every address becomes a candidate again, compared against the current memory
+	pub fn reset(&mut self, core: &Core)
+	{
+		self.snapshot = self.read_memory(core);
+		self.candidates = (0..self.addrs.len()).filter(|&index| self.has_value_at(index)).collect();
+	}
+	
+	
+	// Keeps the candidates that pass, then takes a new snapshot to compare the next step against
+	pub fn filter(&mut self, core: &Core, filter: SearchFilter) -> usize
+	{
+		let memory = self.read_memory(core);
+		
+		let candidates = self.candidates.iter().cloned().filter(|&index|
+		{
+			let value = self.get_value(&memory, index);
+			let previous = self.get_value(&self.snapshot, index);
+			
+			match filter
+			{
+				SearchFilter::Unchanged => value == previous,
+				SearchFilter::Changed => value != previous,
+				SearchFilter::IncreasedBy(n) => self.wrap(value - previous) == self.wrap(n),
+				SearchFilter::DecreasedBy(n) => self.wrap(previous - value) == self.wrap(n),
+				SearchFilter::Greater => value > previous,
+				SearchFilter::Less => value < previous,
+				SearchFilter::Value(n) => value == n
+			}
+		}).collect();
+		
+		self.candidates = candidates;
+		self.snapshot = memory;
+		self.candidates.len()
+	}
+	
+	
+	// Reinterprets the remaining candidates without losing them
+	pub fn set_view(&mut self, size: SearchSize, signed: bool)
+	{
+		self.size = size;
+		self.signed = signed;
+		
+		let candidates = self.candidates.iter().cloned().filter(|&index| self.has_value_at(index)).collect();
+		self.candidates = candidates;
+	}
+	
+	
+	pub fn get_candidate_count(&self) -> usize
+	{
+		self.candidates.len()
+	}
+	
+	
+	pub fn get_results(&self, core: &Core) -> Vec<SearchResult>
+	{
+		let memory = self.read_memory(core);
+		
+		self.candidates.iter().map(|&index| SearchResult
+		{
+			addr: self.addrs[index],
+			value: self.get_value(&memory, index),
+			previous: self.get_value(&self.snapshot, index)
+		}).collect()
+	}
+	
+	
+	fn read_memory(&self, core: &Core) -> Vec<u8>
+	{
+		self.addrs.iter().map(|&addr| core.peek_cpu(addr)).collect()
+	}
+	
+	
+	// A word can't straddle the gap between internal RAM and PRG RAM
+	fn has_value_at(&self, index: usize) -> bool
+	{
+		match self.size
+		{
+			SearchSize::Byte => true,
+			SearchSize::Word => index + 1 < self.addrs.len() && self.addrs[index + 1] == self.addrs[index].wrapping_add(1)
+		}
+	}
+	
+	
+	// Counters wrap around, so differences are taken modulo the width of the view
+	fn wrap(&self, difference: i64) -> i64
+	{
+		match self.size
+		{
+			SearchSize::Byte => difference & 0xff,
+			SearchSize::Word => difference & 0xffff
+		}
+	}
+	
+	
+	fn get_value(&self, memory: &[u8], index: usize) -> i64
+	{
+		match (self.size, self.signed)
+		{
+			(SearchSize::Byte, false) => memory[index] as i64,
+			(SearchSize::Byte, true) => memory[index] as i8 as i64,
+			(SearchSize::Word, false) => (memory[index] as u16 | ((memory[index + 1] as u16) << 8)) as i64,
+			(SearchSize::Word, true) => (memory[index] as u16 | ((memory[index + 1] as u16) << 8)) as i16 as i64
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,398 @@
+use cdl::{CDL_PRG_CODE, CDL_PRG_DATA, CDL_PRG_INDIRECT_CODE};
+use cpu_dis;
+use cpu_dis::AddressingMode;
+use std::collections::BTreeMap;
+
+
+const VECTORS: [u16; 3] = [0xfffa, 0xfffc, 0xfffe];
+
+const BYTES_PER_LINE: usize = 16;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum XRefKind
+{
+	Call,
+	Jump,
+	Branch,
+	Pointer,
+	Read,
+	Write
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct XRef
+{
+	pub from: u16,
+	pub kind: XRefKind
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ByteKind
+{
+	Data,
+	Opcode,
+	Operand,
+	Pointer,
+	PointerHigh
+}
+
+
+// Static disassembly of one PRG bank as it appears at `base` in the CPU address space
+pub struct RomDisassembly
+{
+	pub base: u16,
+	pub prg: Vec<u8>,
+	pub xrefs: BTreeMap<u16, Vec<XRef>>,
+	
+	kinds: Vec<ByteKind>,
+	cdl: Option<Vec<u8>>
+}
+
+
+impl RomDisassembly
+{
+	pub fn new(prg: &[u8], base: u16, cdl: Option<&[u8]>) -> RomDisassembly
+	{
+		let mut dis = RomDisassembly
+		{
+			base,
+			prg: prg.to_vec(),
+			xrefs: BTreeMap::new(),
+			
+			kinds: vec![ByteKind::Data; prg.len()],
+			cdl: cdl.map(|cdl| cdl.to_vec())
+		};
+		
+		let mut entry_points = Vec::new();
+		
+		if base as usize + prg.len() == 0x10000
+		{
+			for &vector in &VECTORS
+				{ entry_points.extend(dis.add_pointer(vector)); }
+		}
+		
+		// Every run of logged code starts with an instruction, and so does every place an
+		// indirect jump was seen landing
+		if let Some(cdl) = dis.cdl.clone()
+		{
+			for offset in 0..cdl.len().min(prg.len())
+			{
+				let run_start = (cdl[offset] & CDL_PRG_CODE) != 0 && (offset == 0 || (cdl[offset - 1] & CDL_PRG_CODE) == 0);
+				if run_start || (cdl[offset] & CDL_PRG_INDIRECT_CODE) != 0
+					{ entry_points.push(base.wrapping_add(offset as u16)); }
+			}
+		}
+		
+		for addr in entry_points
+			{ dis.add_entry_point(addr); }
+			
+		dis
+	}
+	
+	
+	pub fn add_entry_point(&mut self, entry: u16)
+	{
+		let mut pending = vec![entry];
+		
+		while let Some(mut addr) = pending.pop()
+		{
+			while let Some(offset) = self.get_offset(addr)
+			{
+				let (mnemonic, mode) = match cpu_dis::decode_opcode(self.prg[offset])
+				{
+					Some(decoded) => decoded,
+					None => break
+				};
+				
+				let length = mode.get_length();
+				if !self.can_place_instruction(offset, length)
+					{ break; }
+					
+				self.kinds[offset] = ByteKind::Opcode;
+				for i in 1..length
+					{ self.kinds[offset + i] = ByteKind::Operand; }
+					
+				let byte2 = self.get_byte(offset + 1);
+				let byte3 = self.get_byte(offset + 2);
+				let target = get_operand_target(addr, mode, byte2, byte3);
+				
+				match (mnemonic, target)
+				{
+					(_, Some(target)) if mode == AddressingMode::Rel =>
+					{
+						self.add_xref(target, addr, XRefKind::Branch);
+						pending.push(target);
+					}
+					
+					("jsr", Some(target)) =>
+					{
+						self.add_xref(target, addr, XRefKind::Call);
+						pending.push(target);
+					}
+					
+					("jmp", Some(target)) if mode == AddressingMode::Abs =>
+					{
+						self.add_xref(target, addr, XRefKind::Jump);
+						pending.push(target);
+						break;
+					}
+					
+					// A pointer in the ROM itself is a one-entry jump table; the CPU doesn't
+					// carry into the high byte, so one split across a page isn't followed
+					("jmp", Some(target)) =>
+					{
+						self.add_xref(target, addr, XRefKind::Read);
+						
+						if mode == AddressingMode::Ind && (target & 0xff) != 0xff
+							{ pending.extend(self.add_pointer(target)); }
+							
+						break;
+					}
+					
+					("rts", _) | ("rti", _) | ("brk", _) => break,
+					
+					(_, Some(target)) =>
+					{
+						let writes = matches!(mnemonic, "sta" | "stx" | "sty" | "inc" | "dec" | "asl" | "lsr" | "rol" | "ror");
+						self.add_xref(target, addr, if writes { XRefKind::Write } else { XRefKind::Read });
+					}
+					
+					_ => { }
+				}
+				
+				addr = addr.wrapping_add(length as u16);
+			}
+		}
+	}
+	
+	
+	pub fn is_code(&self, addr: u16) -> bool
+	{
+		self.get_offset(addr).map_or(false, |offset| self.kinds[offset] == ByteKind::Opcode)
+	}
+	
+	
+	pub fn to_ca65(&self, labels: &Fn(u16) -> Option<String>) -> String
+	{
+		let label_for = |addr| self.get_label(addr, labels);
+		let end = self.base as usize + self.prg.len() - 1;
+		
+		let mut text = format!("; Disassembly of ${:04x}-${:04x}\n\n.setcpu \"6502\"\n\n", self.base, end);
+		
+		// Labelled addresses that can't be defined inline become equates
+		let mut equates = false;
+		for &target in self.xrefs.keys()
+		{
+			if !self.is_label_site(target)
+			{
+				if let Some(name) = labels(target)
+				{
+					text.push_str(&format!("{} = ${:04x}\n", name, target));
+					equates = true;
+				}
+			}
+		}
+		
+		if equates
+			{ text.push('\n'); }
+			
+		text.push_str(&format!(".org ${:04x}\n", self.base));
+		
+		let mut offset = 0;
+		while offset < self.prg.len()
+		{
+			let addr = self.base.wrapping_add(offset as u16);
+			
+			if let Some(name) = label_for(addr)
+				{ text.push_str(&format!("\n{}:\n", name)); }
+				
+			match self.kinds[offset]
+			{
+				ByteKind::Opcode =>
+				{
+					let opcode = self.prg[offset];
+					let (mnemonic, mode) = cpu_dis::decode_opcode(opcode).unwrap();
+					
+					// ca65 only knows the official nop
+					let line = if mnemonic == "nop" && opcode != 0xea
+						{ format!(".byte ${:02x}", opcode) }
+					else
+						{ cpu_dis::disassemble_instruction_labelled(addr, opcode, self.get_byte(offset + 1), self.get_byte(offset + 2), &label_for) };
+						
+					text.push_str(&format!("\t{:<31} ; ${:04x}\n", line, addr));
+					offset += mode.get_length();
+				}
+				
+				ByteKind::Pointer =>
+				{
+					let target = self.get_word(offset);
+					let name = label_for(target).unwrap_or_else(|| format!("${:04x}", target));
+					
+					text.push_str(&format!("\t{:<31} ; ${:04x}\n", format!(".addr {}", name), addr));
+					offset += 2;
+				}
+				
+				_ =>
+				{
+					let mut bytes = vec![format!("${:02x}", self.prg[offset])];
+					offset += 1;
+					
+					while offset < self.prg.len() && bytes.len() < BYTES_PER_LINE &&
+						self.kinds[offset] == ByteKind::Data && label_for(self.base.wrapping_add(offset as u16)).is_none()
+					{
+						bytes.push(format!("${:02x}", self.prg[offset]));
+						offset += 1;
+					}
+					
+					text.push_str(&format!("\t{:<31} ; ${:04x}\n", format!(".byte {}", bytes.join(", ")), addr));
+				}
+			}
+		}
+		
+		text
+	}
+	
+	
+	pub fn format_xrefs(&self, labels: &Fn(u16) -> Option<String>) -> String
+	{
+		let mut text = String::new();
+		
+		for (&target, xrefs) in &self.xrefs
+		{
+			let mut xrefs = xrefs.clone();
+			xrefs.sort_by_key(|xref| xref.from);
+			
+			let sources: Vec<String> = xrefs.iter().map(|xref| format!("${:04x} {}", xref.from, format_xref_kind(xref.kind))).collect();
+			
+			match self.get_label(target, labels)
+			{
+				Some(name) => text.push_str(&format!("{} (${:04x}): {}\n", name, target, sources.join(", "))),
+				None => text.push_str(&format!("${:04x}: {}\n", target, sources.join(", ")))
+			}
+		}
+		
+		text
+	}
+	
+	
+	fn add_pointer(&mut self, addr: u16) -> Option<u16>
+	{
+		let offset = self.get_offset(addr)?;
+		if offset + 1 >= self.prg.len() || self.kinds[offset] != ByteKind::Data || self.kinds[offset + 1] != ByteKind::Data
+			{ return None; }
+			
+		self.kinds[offset] = ByteKind::Pointer;
+		self.kinds[offset + 1] = ByteKind::PointerHigh;
+		
+		let target = self.get_word(offset);
+		self.add_xref(target, addr, XRefKind::Pointer);
+		
+		Some(target)
+	}
+	
+	
+	fn add_xref(&mut self, target: u16, from: u16, kind: XRefKind)
+	{
+		let xrefs = self.xrefs.entry(target).or_default();
+		let xref = XRef { from, kind };
+		
+		if !xrefs.contains(&xref)
+			{ xrefs.push(xref); }
+	}
+	
+	
+	// Bytes the code/data log saw only as data never start or continue an instruction
+	fn can_place_instruction(&self, offset: usize, length: usize) -> bool
+	{
+		if offset + length > self.prg.len()
+			{ return false; }
+			
+		(offset..offset + length).all(|i|
+		{
+			let data_only = self.cdl.as_ref().map_or(false, |cdl| cdl.get(i).map_or(false, |&flags| (flags & (CDL_PRG_CODE | CDL_PRG_DATA)) == CDL_PRG_DATA));
+			self.kinds[i] == ByteKind::Data && !data_only
+		})
+	}
+	
+	
+	// Only addresses where a line starts can carry a label
+	fn is_label_site(&self, addr: u16) -> bool
+	{
+		match self.get_offset(addr)
+		{
+			Some(offset) => matches!(self.kinds[offset], ByteKind::Data | ByteKind::Opcode | ByteKind::Pointer),
+			None => false
+		}
+	}
+	
+	
+	fn get_label(&self, addr: u16, labels: &Fn(u16) -> Option<String>) -> Option<String>
+	{
+		if !self.is_label_site(addr)
+			{ return labels(addr).filter(|_| self.xrefs.contains_key(&addr)); }
+			
+		match labels(addr)
+		{
+			Some(name) => Some(name),
+			None if self.xrefs.contains_key(&addr) => Some(format!("L{:04x}", addr)),
+			None => None
+		}
+	}
+	
+	
+	fn get_offset(&self, addr: u16) -> Option<usize>
+	{
+		let offset = addr.wrapping_sub(self.base) as usize;
+		
+		if addr >= self.base && offset < self.prg.len()
+			{ Some(offset) }
+		else
+			{ None }
+	}
+	
+	
+	fn get_byte(&self, offset: usize) -> u8
+	{
+		self.prg.get(offset).cloned().unwrap_or(0)
+	}
+	
+	
+	fn get_word(&self, offset: usize) -> u16
+	{
+		self.get_byte(offset) as u16 | ((self.get_byte(offset + 1) as u16) << 8)
+	}
+}
+
+
+fn get_operand_target(addr: u16, mode: AddressingMode, byte2: u8, byte3: u8) -> Option<u16>
+{
+	match mode
+	{
+		AddressingMode::Imp | AddressingMode::Imm => None,
+		AddressingMode::Rel => Some(cpu_dis::get_branch_target(addr, byte2)),
+		
+		AddressingMode::Zer | AddressingMode::Zrx | AddressingMode::Zry |
+		AddressingMode::Ptx | AddressingMode::Pty | AddressingMode::Ptr |
+		AddressingMode::Zrl => Some(byte2 as u16),
+		
+		AddressingMode::Abs | AddressingMode::Abx | AddressingMode::Aby |
+		AddressingMode::Ind | AddressingMode::Iax => Some(byte2 as u16 | ((byte3 as u16) << 8))
+	}
+}
+
+
+fn format_xref_kind(kind: XRefKind) -> &'static str
+{
+	match kind
+	{
+		XRefKind::Call => "call",
+		XRefKind::Jump => "jump",
+		XRefKind::Branch => "branch",
+		XRefKind::Pointer => "pointer",
+		XRefKind::Read => "read",
+		XRefKind::Write => "write"
+	}
+}
\ No newline at end of file
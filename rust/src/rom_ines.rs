@@ -28,7 +28,7 @@ impl RomINES
 {
 	pub fn new(buffer: &[u8]) -> RomINES
 	{
-		if buffer[0] != 'N' as u8 || buffer[1] != 'E' as u8 || buffer[2] != 'S' as u8 || buffer[3] != 0x1a
+		if buffer[0] != b'N' || buffer[1] != b'E' || buffer[2] != b'S' || buffer[3] != 0x1a
 			{ panic!("invalid iNES magic number"); }
 			
 		let prg_16kb_bank_num = buffer[4] as usize;
@@ -54,8 +54,6 @@ impl RomINES
 		
 		let region = buffer[10] as usize & 0x3;
 		
-		println!("chr_rom({}) = {:?}", chr_rom.len(), chr_rom);
-		
 		RomINES
 		{
 			prg_16kb_bank_num,
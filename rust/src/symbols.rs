@@ -0,0 +1,264 @@
+use Core;
+use Expression;
+use cpu_dis;
+use std::collections::HashMap;
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol
+{
+	pub name: String,
+	pub comment: String
+}
+
+
+// PRG ROM labels are keyed by ROM offset so they follow bank switching;
+// everything else (RAM, registers) is keyed by CPU address
+pub struct SymbolTable
+{
+	pub cpu_symbols: HashMap<u16, Symbol>,
+	pub prg_symbols: HashMap<usize, Symbol>
+}
+
+
+impl SymbolTable
+{
+	pub fn new() -> SymbolTable
+	{
+		SymbolTable
+		{
+			cpu_symbols: HashMap::new(),
+			prg_symbols: HashMap::new()
+		}
+	}
+	
+	
+	pub fn is_empty(&self) -> bool
+	{
+		self.cpu_symbols.is_empty() && self.prg_symbols.is_empty()
+	}
+	
+	
+	pub fn add_cpu_symbol(&mut self, addr: u16, name: &str, comment: &str)
+	{
+		self.cpu_symbols.insert(addr, Symbol { name: name.to_string(), comment: comment.to_string() });
+	}
+	
+	
+	pub fn add_prg_symbol(&mut self, offset: usize, name: &str, comment: &str)
+	{
+		self.prg_symbols.insert(offset, Symbol { name: name.to_string(), comment: comment.to_string() });
+	}
+	
+	
+	pub fn find(&self, core: &Core, addr: u16) -> Option<&Symbol>
+	{
+		let prg_symbol = core.cartridge.cpu_addr_to_prg_offset(addr).and_then(|offset| self.prg_symbols.get(&offset));
+		prg_symbol.or_else(|| self.cpu_symbols.get(&addr))
+	}
+	
+	
+	pub fn find_label(&self, core: &Core, addr: u16) -> Option<String>
+	{
+		self.find(core, addr).map(|symbol| symbol.name.clone())
+	}
+	
+	
+	// PRG labels resolve to wherever their bank is currently mapped
+	pub fn find_address(&self, core: &Core, name: &str) -> Option<u16>
+	{
+		if let Some((&addr, _)) = self.cpu_symbols.iter().find(|&(_, symbol)| symbol.name == name)
+			{ return Some(addr); }
+			
+		let offset = self.prg_symbols.iter().find(|&(_, symbol)| symbol.name == name).map(|(&offset, _)| offset)?;
+		(0x4020..=0xffff).find(|&addr| core.cartridge.cpu_addr_to_prg_offset(addr) == Some(offset))
+	}
+	
+	
+	// FCEUX .nl: "$C000#Label#Comment"; `bank` is None for the RAM file
+	pub fn load_nl(&mut self, text: &str, bank: Option<usize>)
+	{
+		for line in text.lines()
+		{
+			let mut fields = line.trim().splitn(3, '#');
+			
+			let addr_field = fields.next().unwrap_or("");
+			let name = fields.next().unwrap_or("");
+			let comment = fields.next().unwrap_or("").trim_end_matches('#');
+			
+			// Array entries carry a "/size" suffix
+			let addr = match parse_hex(addr_field.trim_start_matches('$').split('/').next().unwrap())
+			{
+				Some(addr) if !name.is_empty() => addr as u16,
+				_ => continue
+			};
+			
+			match bank
+			{
+				Some(bank) => self.add_prg_symbol(bank * 0x4000 + (addr as usize & 0x3fff), name, comment),
+				None => self.add_cpu_symbol(addr, name, comment)
+			}
+		}
+	}
+	
+	
+	// Mesen .mlb: "P:1234:Label:Comment", with either the short or the Mesen 2 memory type names
+	pub fn load_mlb(&mut self, text: &str)
+	{
+		for line in text.lines()
+		{
+			let mut fields = line.trim().splitn(4, ':');
+			
+			let kind = fields.next().unwrap_or("");
+			let addr = match fields.next().and_then(|field| parse_hex(field.split('-').next().unwrap()))
+			{
+				Some(addr) => addr,
+				None => continue
+			};
+			
+			let name = fields.next().unwrap_or("");
+			let comment = fields.next().unwrap_or("");
+			
+			if name.is_empty()
+				{ continue; }
+				
+			match kind
+			{
+				"P" | "NesPrgRom" => self.add_prg_symbol(addr, name, comment),
+				"R" | "NesInternalRam" => self.add_cpu_symbol(addr as u16, name, comment),
+				"S" | "W" | "NesSaveRam" | "NesWorkRam" => self.add_cpu_symbol(0x6000 + addr as u16, name, comment),
+				"G" | "NesMemory" | "Register" => self.add_cpu_symbol(addr as u16, name, comment),
+				_ => { }
+			}
+		}
+	}
+	
+	
+	// ld65 .dbg: only "lab" symbols are addresses; segments tell where a symbol ended up in the ROM
+	pub fn load_dbg(&mut self, text: &str)
+	{
+		let mut segments = HashMap::new();
+		let mut symbols = Vec::new();
+		
+		for line in text.lines()
+		{
+			let mut parts = line.splitn(2, '\t');
+			let kind = parts.next().unwrap_or("");
+			let attrs = parse_dbg_attributes(parts.next().unwrap_or(""));
+			
+			match kind
+			{
+				"seg" =>
+				{
+					let id = attrs.get("id").and_then(|id| id.parse::<usize>().ok());
+					let start = attrs.get("start").and_then(|start| parse_hex(start));
+					let ooffs = attrs.get("ooffs").and_then(|ooffs| ooffs.parse::<usize>().ok());
+					
+					if let (Some(id), Some(start)) = (id, start)
+						{ segments.insert(id, (start, ooffs)); }
+				}
+				
+				"sym" => symbols.push(attrs),
+				_ => { }
+			}
+		}
+		
+		for attrs in symbols
+		{
+			if attrs.get("type").map(|kind| kind.as_ref()) != Some("lab")
+				{ continue; }
+				
+			let name = match attrs.get("name")
+			{
+				Some(name) => name.clone(),
+				None => continue
+			};
+			
+			let val = match attrs.get("val").and_then(|val| parse_hex(val))
+			{
+				Some(val) => val,
+				None => continue
+			};
+			
+			let segment = attrs.get("seg").and_then(|seg| seg.parse::<usize>().ok()).and_then(|seg| segments.get(&seg));
+			
+			match segment
+			{
+				// Output offsets include the 16-byte iNES header
+				Some(&(start, Some(ooffs))) if ooffs >= 16 && val >= start =>
+					self.add_prg_symbol(ooffs - 16 + (val - start), &name, ""),
+					
+				_ => self.add_cpu_symbol(val as u16, &name, "")
+			}
+		}
+	}
+}
+
+
+impl Core
+{
+	pub fn parse_expression(&self, src: &str) -> Result<Expression, String>
+	{
+		Expression::parse_with_labels(src, &|name| self.symbols.find_address(self, name))
+	}
+	
+	
+	pub fn disassemble(&self, addr: u16) -> (String, usize)
+	{
+		let opcode = self.peek_cpu(addr);
+		let byte2 = self.peek_cpu(addr.wrapping_add(1));
+		let byte3 = self.peek_cpu(addr.wrapping_add(2));
+		
+		let labels = |target| self.symbols.find_label(self, target);
+		let text = cpu_dis::disassemble_instruction_labelled_variant(addr, opcode, byte2, byte3, self.cpu.variant, &labels);
+		
+		let (mnemonic, mode) = match cpu_dis::decode_opcode_variant(opcode, self.cpu.variant)
+		{
+			Some(decoded) => decoded,
+			None => return (text, 1)
+		};
+		
+		let peek = |target| self.peek_cpu(target);
+		let effective = cpu_dis::get_effective_addr(mode, byte2, byte3, self.cpu.reg_x, self.cpu.reg_y, self.cpu.variant, &peek);
+		
+		let annotation = match effective
+		{
+			Some(target) if mnemonic == "jmp" => format!(" ; -> {}", labels(target).unwrap_or_else(|| format!("${:04x}", target))),
+			Some(_) if mnemonic == "jsr" => String::new(),
+			Some(target) => format!(" ; ${:04x} = ${:02x}", target, self.peek_cpu(target)),
+			None => String::new()
+		};
+		
+		// Plain absolute jumps need no annotation
+		let annotation = if mnemonic == "jmp" && mode == cpu_dis::AddressingMode::Abs { String::new() } else { annotation };
+		
+		(format!("{}{}", text, annotation), mode.get_length())
+	}
+}
+
+
+fn parse_hex(text: &str) -> Option<usize>
+{
+	let text = text.trim();
+	let digits = text.trim_start_matches("0x").trim_start_matches('$');
+	
+	usize::from_str_radix(digits, 16).ok()
+}
+
+
+fn parse_dbg_attributes(text: &str) -> HashMap<String, String>
+{
+	let mut attrs = HashMap::new();
+	
+	for pair in text.split(',')
+	{
+		let mut parts = pair.splitn(2, '=');
+		let key = parts.next().unwrap_or("").trim();
+		let value = parts.next().unwrap_or("").trim().trim_matches('"');
+		
+		if !key.is_empty()
+			{ attrs.insert(key.to_string(), value.to_string()); }
+	}
+	
+	attrs
+}
\ No newline at end of file
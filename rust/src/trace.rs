@@ -0,0 +1,331 @@
+use Core;
+use cpu_dis;
+use cpu_dis::AddressingMode;
+use std::collections::VecDeque;
+use std::io;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceFormat
+{
+	pub bytes: bool,
+	pub effective_addr: bool,
+	pub prefixed_values: bool,
+	pub registers: bool,
+	pub ppu: bool,
+	pub cycles: bool
+}
+
+
+pub struct TraceLogger
+{
+	pub format: TraceFormat,
+	pub filters: Vec<(u16, u16)>,
+	
+	output: Option<Box<io::Write>>,
+	
+	ring_buffer: VecDeque<String>,
+	ring_buffer_size: usize
+}
+
+
+impl TraceFormat
+{
+	pub fn nestest() -> TraceFormat
+	{
+		TraceFormat
+		{
+			bytes: true,
+			effective_addr: true,
+			prefixed_values: false,
+			registers: true,
+			ppu: true,
+			cycles: true
+		}
+	}
+}
+
+
+impl TraceLogger
+{
+	pub fn new(format: TraceFormat) -> TraceLogger
+	{
+		TraceLogger
+		{
+			format,
+			filters: Vec::new(),
+			
+			output: None,
+			
+			ring_buffer: VecDeque::new(),
+			ring_buffer_size: 0
+		}
+	}
+	
+	
+	pub fn set_output(&mut self, output: Option<Box<io::Write>>)
+	{
+		self.output = output;
+	}
+	
+	
+	pub fn set_ring_buffer_size(&mut self, size: usize)
+	{
+		self.ring_buffer_size = size;
+		
+		while self.ring_buffer.len() > size
+			{ self.ring_buffer.pop_front(); }
+	}
+	
+	
+	pub fn add_filter(&mut self, addr_start: u16, addr_end: u16)
+	{
+		self.filters.push((addr_start, addr_end));
+	}
+	
+	
+	pub fn get_ring_buffer(&self) -> &VecDeque<String>
+	{
+		&self.ring_buffer
+	}
+	
+	
+	pub fn write_ring_buffer(&self, output: &mut io::Write) -> io::Result<()>
+	{
+		for line in &self.ring_buffer
+			{ writeln!(output, "{}", line)?; }
+			
+		Ok(())
+	}
+	
+	
+	pub fn log(&mut self, core: &Core, addr: u16)
+	{
+		if !self.filters.is_empty() && !self.filters.iter().any(|&(start, end)| addr >= start && addr <= end)
+			{ return; }
+			
+		if self.output.is_none() && self.ring_buffer_size == 0
+			{ return; }
+			
+		let line = self.format_line(core, addr);
+		
+		let failed = match self.output
+		{
+			Some(ref mut output) => writeln!(output, "{}", line).is_err(),
+			None => false
+		};
+		
+		// Stop writing to a broken output instead of failing every instruction
+		if failed
+			{ self.output = None; }
+			
+		if self.ring_buffer_size > 0
+		{
+			if self.ring_buffer.len() >= self.ring_buffer_size
+				{ self.ring_buffer.pop_front(); }
+				
+			self.ring_buffer.push_back(line);
+		}
+	}
+	
+	
+	pub fn format_line(&self, core: &Core, addr: u16) -> String
+	{
+		let opcode = core.peek_cpu(addr);
+		let byte2 = core.peek_cpu(addr.wrapping_add(1));
+		let byte3 = core.peek_cpu(addr.wrapping_add(2));
+		
+		let decoded = cpu_dis::decode_opcode_variant(opcode, core.cpu.variant);
+		let length = decoded.map_or(1, |(_, mode)| mode.get_length());
+		
+		let mut line = format!("{:04X}  ", addr);
+		
+		if self.format.bytes
+		{
+			let bytes: Vec<String> = [opcode, byte2, byte3][0..length].iter().map(|b| format!("{:02X}", b)).collect();
+			line.push_str(&format!("{:<8}  ", bytes.join(" ")));
+		}
+		
+		let disassembly = match decoded
+		{
+			Some((mnemonic, mode)) => self.format_instruction(core, addr, mnemonic, mode, byte2, byte3),
+			None => "???".to_string()
+		};
+		
+		let mut fields = Vec::new();
+		
+		if self.format.registers
+		{
+			let cpu = &core.cpu;
+			fields.push(format!("A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}", cpu.reg_a, cpu.reg_x, cpu.reg_y, cpu.reg_p, cpu.reg_s));
+		}
+		
+		if self.format.ppu
+			{ fields.push(format!("PPU:{:3},{:3}", core.ppu.get_scanline(), core.ppu.get_dot())); }
+			
+		// The opcode fetch has already been counted by the CPU
+		if self.format.cycles
+			{ fields.push(format!("CYC:{}", core.cpu.clocks.wrapping_sub(1))); }
+			
+		if fields.is_empty()
+			{ line.push_str(&disassembly); }
+		else
+		{
+			line.push_str(&format!("{:<32}", disassembly));
+			line.push_str(&fields.join(" "));
+		}
+		
+		line
+	}
+	
+	
+	fn format_instruction(&self, core: &Core, addr: u16, mnemonic: &str, mode: AddressingMode, byte2: u8, byte3: u8) -> String
+	{
+		let cpu = &core.cpu;
+		let mnemonic = mnemonic.to_uppercase();
+		let abs = (byte2 as u16) | ((byte3 as u16) << 8);
+		
+		let peek_zp_word = |zp: u8| (core.peek_cpu(zp as u16) as u16) | ((core.peek_cpu(zp.wrapping_add(1) as u16) as u16) << 8);
+		
+		let (operand, detail) = match mode
+		{
+			AddressingMode::Imp => (String::new(), String::new()),
+			AddressingMode::Imm => (format!("#${:02X}", byte2), String::new()),
+			
+			AddressingMode::Zer =>
+				(label_or(core, byte2 as u16, format!("${:02X}", byte2)), self.format_value(core.peek_cpu(byte2 as u16))),
+				
+			AddressingMode::Zrx | AddressingMode::Zry =>
+			{
+				let (index, name) = if mode == AddressingMode::Zrx { (cpu.reg_x, "X") } else { (cpu.reg_y, "Y") };
+				let effective = byte2.wrapping_add(index);
+				
+				(format!("{},{}", label_or(core, byte2 as u16, format!("${:02X}", byte2)), name),
+					format!(" @ {}{}", self.format_zp_addr(effective), self.format_value(core.peek_cpu(effective as u16))))
+			}
+			
+			AddressingMode::Abs =>
+			{
+				let detail = if mnemonic == "JMP" || mnemonic == "JSR"
+					{ String::new() }
+				else
+					{ self.format_value(core.peek_cpu(abs)) };
+					
+				(label_or(core, abs, format!("${:04X}", abs)), detail)
+			}
+			
+			AddressingMode::Abx | AddressingMode::Aby =>
+			{
+				let (index, name) = if mode == AddressingMode::Abx { (cpu.reg_x, "X") } else { (cpu.reg_y, "Y") };
+				let effective = abs.wrapping_add(index as u16);
+				
+				(format!("{},{}", label_or(core, abs, format!("${:04X}", abs)), name),
+					format!(" @ {}{}", self.format_addr(effective), self.format_value(core.peek_cpu(effective))))
+			}
+			
+			AddressingMode::Ind =>
+			{
+				let target =
+					(core.peek_cpu(abs) as u16) |
+					((core.peek_cpu(cpu_dis::get_pointer_high_addr(abs, cpu.variant)) as u16) << 8);
+					
+				(format!("({})", label_or(core, abs, format!("${:04X}", abs))), format!(" = {}", self.format_addr(target)))
+			}
+			
+			AddressingMode::Ptx =>
+			{
+				let pointer = byte2.wrapping_add(cpu.reg_x);
+				let effective = peek_zp_word(pointer);
+				
+				(format!("({},X)", label_or(core, byte2 as u16, format!("${:02X}", byte2))),
+					format!(" @ {} = {}{}", self.format_zp_addr(pointer), self.format_addr(effective), self.format_value(core.peek_cpu(effective))))
+			}
+			
+			AddressingMode::Pty =>
+			{
+				let base = peek_zp_word(byte2);
+				let effective = base.wrapping_add(cpu.reg_y as u16);
+				
+				(format!("({}),Y", label_or(core, byte2 as u16, format!("${:02X}", byte2))),
+					format!(" = {} @ {}{}", self.format_addr(base), self.format_addr(effective), self.format_value(core.peek_cpu(effective))))
+			}
+			
+			AddressingMode::Rel =>
+			{
+				let target = cpu_dis::get_branch_target(addr, byte2);
+				(label_or(core, target, format!("${:04X}", target)), String::new())
+			}
+			
+			AddressingMode::Ptr =>
+			{
+				let effective = peek_zp_word(byte2);
+				
+				(format!("({})", label_or(core, byte2 as u16, format!("${:02X}", byte2))),
+					format!(" = {}{}", self.format_addr(effective), self.format_value(core.peek_cpu(effective))))
+			}
+			
+			AddressingMode::Iax =>
+			{
+				let pointer = abs.wrapping_add(cpu.reg_x as u16);
+				let target = (core.peek_cpu(pointer) as u16) | ((core.peek_cpu(pointer.wrapping_add(1)) as u16) << 8);
+				
+				(format!("({},X)", label_or(core, abs, format!("${:04X}", abs))),
+					format!(" @ {} = {}", self.format_addr(pointer), self.format_addr(target)))
+			}
+			
+			AddressingMode::Zrl =>
+			{
+				let target = addr.wrapping_add(3).wrapping_add(byte3 as i8 as u16);
+				
+				(format!("{},{}", label_or(core, byte2 as u16, format!("${:02X}", byte2)), label_or(core, target, format!("${:04X}", target))),
+					self.format_value(core.peek_cpu(byte2 as u16)))
+			}
+		};
+		
+		let mut text = mnemonic;
+		
+		if !operand.is_empty()
+		{
+			text.push(' ');
+			text.push_str(&operand);
+		}
+		
+		if self.format.effective_addr
+			{ text.push_str(&detail); }
+			
+		text
+	}
+	
+	
+	fn format_zp_addr(&self, addr: u8) -> String
+	{
+		if self.format.prefixed_values
+			{ format!("${:02X}", addr) }
+		else
+			{ format!("{:02X}", addr) }
+	}
+	
+	
+	fn format_addr(&self, addr: u16) -> String
+	{
+		if self.format.prefixed_values
+			{ format!("${:04X}", addr) }
+		else
+			{ format!("{:04X}", addr) }
+	}
+	
+	
+	fn format_value(&self, val: u8) -> String
+	{
+		if self.format.prefixed_values
+			{ format!(" = #${:02X}", val) }
+		else
+			{ format!(" = {:02X}", val) }
+	}
+}
+
+
+fn label_or(core: &Core, addr: u16, text: String) -> String
+{
+	core.symbols.find_label(core, addr).unwrap_or(text)
+}
\ No newline at end of file
@@ -0,0 +1,271 @@
+use Core;
+
+
+pub const NAMETABLES_WIDTH: usize = 512;
+pub const NAMETABLES_HEIGHT: usize = 480;
+
+pub const PATTERN_TABLES_WIDTH: usize = 256;
+pub const PATTERN_TABLES_HEIGHT: usize = 128;
+
+pub const SPRITE_SHEET_WIDTH: usize = 64;
+pub const SPRITE_SHEET_HEIGHT: usize = 128;
+
+pub const PALETTE_WIDTH: usize = 16;
+pub const PALETTE_HEIGHT: usize = 2;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteInfo
+{
+	pub index: u8,
+	pub x: u8,
+	pub y: u8,
+	pub tile: u8,
+	pub palette_index: u8,
+	pub priority: bool,
+	pub flip_h: bool,
+	pub flip_v: bool,
+	pub pattern_addr: u16
+}
+
+
+impl Core
+{
+	pub fn render_nametables(&self) -> Vec<u8>
+	{
+		let mut buffer = vec![0; NAMETABLES_WIDTH * NAMETABLES_HEIGHT * 4];
+		let pattern_table = if (self.ppu.get_reg_ctrl() & 0x10) != 0 { 0x1000 } else { 0 };
+		
+		for nametable in 0..4
+		{
+			let nametable_addr = 0x2000 | (nametable << 10);
+			let origin_x = (nametable as usize & 1) * 256;
+			let origin_y = (nametable as usize >> 1) * 240;
+			
+			for tile_y in 0..30
+			{
+				for tile_x in 0..32
+				{
+					let tile = self.peek_ppu(nametable_addr | (tile_y << 5) | tile_x);
+					
+					let attr_addr = nametable_addr | 0x3c0 | ((tile_y >> 2) << 3) | (tile_x >> 2);
+					let attr_shift = ((tile_y & 0x2) << 1) | (tile_x & 0x2);
+					let palette_index = (self.peek_ppu(attr_addr) >> attr_shift) & 0x3;
+					
+					let pattern_addr = pattern_table | ((tile as u16) << 4);
+					
+					self.viewer_draw_tile(
+						&mut buffer, NAMETABLES_WIDTH,
+						origin_x + tile_x as usize * 8, origin_y + tile_y as usize * 8,
+						pattern_addr, palette_index);
+				}
+			}
+		}
+		
+		self.viewer_draw_scroll_rect(&mut buffer);
+		buffer
+	}
+	
+	
+	pub fn render_pattern_tables(&self, palette_index: u8) -> Vec<u8>
+	{
+		let mut buffer = vec![0; PATTERN_TABLES_WIDTH * PATTERN_TABLES_HEIGHT * 4];
+		
+		for table in 0..2
+		{
+			for tile in 0..256
+			{
+				let pattern_addr = ((table as u16) << 12) | ((tile as u16) << 4);
+				
+				self.viewer_draw_tile(
+					&mut buffer, PATTERN_TABLES_WIDTH,
+					table * 128 + (tile % 16) * 8, (tile / 16) * 8,
+					pattern_addr, palette_index & 0x7);
+			}
+		}
+		
+		buffer
+	}
+	
+	
+	pub fn get_sprite_list(&self) -> Vec<SpriteInfo>
+	{
+		let tall_sprites = (self.ppu.get_reg_ctrl() & 0x20) != 0;
+		let default_pattern_table = if (self.ppu.get_reg_ctrl() & 0x08) != 0 { 0x1000 } else { 0 };
+		
+		let mut sprites = Vec::with_capacity(64);
+		
+		for index in 0..64
+		{
+			let y    = self.ppu.oam[index * 4 + 0];
+			let tile = self.ppu.oam[index * 4 + 1];
+			let attr = self.ppu.oam[index * 4 + 2];
+			let x    = self.ppu.oam[index * 4 + 3];
+			
+			let pattern_addr = if tall_sprites
+				{ (if (tile & 1) != 0 { 0x1000 } else { 0 }) | (((tile & 0xfe) as u16) << 4) }
+			else
+				{ default_pattern_table | ((tile as u16) << 4) };
+				
+			sprites.push(SpriteInfo
+			{
+				index: index as u8,
+				x,
+				y,
+				tile,
+				palette_index: attr & 0x3,
+				priority: (attr & 0x20) != 0,
+				flip_h: (attr & 0x40) != 0,
+				flip_v: (attr & 0x80) != 0,
+				pattern_addr
+			});
+		}
+		
+		sprites
+	}
+	
+	
+	pub fn render_sprite_sheet(&self) -> Vec<u8>
+	{
+		let mut buffer = vec![0; SPRITE_SHEET_WIDTH * SPRITE_SHEET_HEIGHT * 4];
+		let tall_sprites = (self.ppu.get_reg_ctrl() & 0x20) != 0;
+		
+		for spr in self.get_sprite_list()
+		{
+			let x = (spr.index as usize % 8) * 8;
+			let y = (spr.index as usize / 8) * 16;
+			let palette_index = 4 + spr.palette_index;
+			
+			if tall_sprites
+			{
+				let (top, bottom) = if spr.flip_v
+					{ (spr.pattern_addr + 16, spr.pattern_addr) }
+				else
+					{ (spr.pattern_addr, spr.pattern_addr + 16) };
+					
+				self.viewer_draw_sprite_tile(&mut buffer, x, y, top, palette_index, &spr);
+				self.viewer_draw_sprite_tile(&mut buffer, x, y + 8, bottom, palette_index, &spr);
+			}
+			else
+				{ self.viewer_draw_sprite_tile(&mut buffer, x, y, spr.pattern_addr, palette_index, &spr); }
+		}
+		
+		buffer
+	}
+	
+	
+	pub fn render_palette(&self) -> Vec<u8>
+	{
+		let mut buffer = vec![0; PALETTE_WIDTH * PALETTE_HEIGHT * 4];
+		
+		for i in 0..0x20
+		{
+			let rgba = self.palette.get_rgba(self.palram.read(i as u16), 0);
+			buffer[i * 4 .. i * 4 + 4].copy_from_slice(rgba);
+		}
+		
+		buffer
+	}
+	
+	
+	fn viewer_get_color(&self, palette_index: u8, pixel: u8) -> &[u8]
+	{
+		let color = if pixel == 0
+			{ self.palram.read(0) }
+		else
+			{ self.palram.read(((palette_index << 2) | pixel) as u16) };
+			
+		self.palette.get_rgba(color, 0)
+	}
+	
+	
+	fn viewer_decode_tile(&self, pattern_addr: u16) -> [u8; 64]
+	{
+		let mut pixels = [0; 64];
+		
+		for row in 0..8
+		{
+			let pattern_lo = self.cartridge.ppu_peek(pattern_addr + row as u16);
+			let pattern_hi = self.cartridge.ppu_peek(pattern_addr + row as u16 + 8);
+			
+			for col in 0..8
+				{ pixels[row * 8 + col] = ((pattern_lo >> (7 - col)) & 1) | (((pattern_hi >> (7 - col)) & 1) << 1); }
+		}
+		
+		pixels
+	}
+	
+	
+	fn viewer_draw_tile(&self, buffer: &mut [u8], width: usize, x: usize, y: usize, pattern_addr: u16, palette_index: u8)
+	{
+		let pixels = self.viewer_decode_tile(pattern_addr);
+		
+		for row in 0..8
+		{
+			for col in 0..8
+			{
+				let addr = ((y + row) * width + x + col) * 4;
+				buffer[addr .. addr + 4].copy_from_slice(self.viewer_get_color(palette_index, pixels[row * 8 + col]));
+			}
+		}
+	}
+	
+	
+	fn viewer_draw_sprite_tile(&self, buffer: &mut [u8], x: usize, y: usize, pattern_addr: u16, palette_index: u8, spr: &SpriteInfo)
+	{
+		let pixels = self.viewer_decode_tile(pattern_addr);
+		
+		for row in 0..8
+		{
+			for col in 0..8
+			{
+				let src_row = if spr.flip_v { 7 - row } else { row };
+				let src_col = if spr.flip_h { 7 - col } else { col };
+				
+				let pixel = pixels[src_row * 8 + src_col];
+				if pixel == 0
+					{ continue; }
+					
+				let addr = ((y + row) * SPRITE_SHEET_WIDTH + x + col) * 4;
+				buffer[addr .. addr + 4].copy_from_slice(self.viewer_get_color(palette_index, pixel));
+			}
+		}
+	}
+	
+	
+	fn viewer_draw_scroll_rect(&self, buffer: &mut [u8])
+	{
+		let scroll_t = self.ppu.get_scroll_t() as usize;
+		
+		let scroll_x =
+			((scroll_t & 0x1f) << 3) |
+			(self.ppu.get_scroll_x() as usize) |
+			(((scroll_t >> 10) & 1) << 8);
+			
+		let scroll_y =
+			(((scroll_t >> 5) & 0x1f) << 3) |
+			((scroll_t >> 12) & 0x7);
+			
+		let scroll_y = scroll_y + ((scroll_t >> 11) & 1) * 240;
+		
+		let mut invert = |x: usize, y: usize|
+		{
+			let addr = (((y % NAMETABLES_HEIGHT) * NAMETABLES_WIDTH) + (x % NAMETABLES_WIDTH)) * 4;
+			buffer[addr + 0] ^= 0xff;
+			buffer[addr + 1] ^= 0xff;
+			buffer[addr + 2] ^= 0xff;
+		};
+		
+		for i in 0..256
+		{
+			invert(scroll_x + i, scroll_y);
+			invert(scroll_x + i, scroll_y + 239);
+		}
+		
+		for i in 1..239
+		{
+			invert(scroll_x, scroll_y + i);
+			invert(scroll_x + 255, scroll_y + i);
+		}
+	}
+}
\ No newline at end of file
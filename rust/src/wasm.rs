@@ -1,12 +1,16 @@
 use Core;
+use PowerOnRam;
 use RomINES;
+use Palette;
+use NtscPaletteParams;
+use Cheat;
 
 
 pub static mut WASM_CORE: Option<Box<Core>> = None;
 
 
 #[no_mangle]
-pub unsafe extern fn wasm_buffer_new(len: usize) -> *mut Vec<u8>
+pub unsafe extern "C" fn wasm_buffer_new(len: usize) -> *mut Vec<u8>
 {
 	let vec = Box::new(vec![0; len]);
 	Box::into_raw(vec)
@@ -14,38 +18,121 @@ pub unsafe extern fn wasm_buffer_new(len: usize) -> *mut Vec<u8>
 
 
 #[no_mangle]
-pub unsafe extern fn wasm_buffer_drop(buffer: *mut Vec<u8>)
+pub unsafe extern "C" fn wasm_buffer_drop(buffer: *mut Vec<u8>)
 {
-	Box::from_raw(buffer);
+	drop(Box::from_raw(buffer));
 }
 
 
 #[no_mangle]
-pub unsafe extern fn wasm_buffer_set(buffer: *mut Vec<u8>, index: usize, value: u8)
+pub unsafe extern "C" fn wasm_buffer_set(buffer: *mut Vec<u8>, index: usize, value: u8)
 {
-	(*buffer)[index] = value;
+	(&mut *buffer)[index] = value;
 }
 
 
 #[no_mangle]
-pub unsafe extern fn wasm_core_new(buffer: *mut Vec<u8>)
+pub unsafe extern "C" fn wasm_buffer_get_ptr(buffer: *mut Vec<u8>) -> *mut u8
 {
-	let ines = RomINES::new(&std::mem::transmute::<_, &mut Vec<u8>>(buffer));
+	(&mut *buffer).as_mut_ptr()
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_buffer_get_len(buffer: *mut Vec<u8>) -> usize
+{
+	(&*buffer).len()
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_new(buffer: *mut Vec<u8>)
+{
+	let ines = RomINES::new(&*buffer);
 	let cartridge = ines.make_cartridge().unwrap();
 	
 	WASM_CORE = Some(Core::new(Box::new(cartridge)));
 }
 
 
+// Power cycle
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_reset()
+{
+	WASM_CORE.as_mut().unwrap().reset();
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_soft_reset()
+{
+	WASM_CORE.as_mut().unwrap().soft_reset();
+}
+
+
+// 0: zero, 1: $FF, 2: hardware-like pattern, 3: random from `seed`
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_set_power_on_ram(kind: u32, seed: u32)
+{
+	WASM_CORE.as_mut().unwrap().power_on_ram = match kind
+	{
+		1 => PowerOnRam::Ones,
+		2 => PowerOnRam::Pattern,
+		3 => PowerOnRam::Random(seed),
+		_ => PowerOnRam::Zero
+	};
+}
+
+
 #[no_mangle]
-pub unsafe extern fn wasm_core_set_controller1(input: u8)
+pub unsafe extern "C" fn wasm_core_set_controller1(input: u8)
 {
 	WASM_CORE.as_mut().unwrap().controller1 = input;
 }
 
 
 #[no_mangle]
-pub unsafe extern fn wasm_core_run_frame()
+pub unsafe extern "C" fn wasm_core_set_palette(buffer: *mut Vec<u8>) -> bool
+{
+	WASM_CORE.as_mut().unwrap().set_palette(&*buffer)
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_set_palette_default()
+{
+	WASM_CORE.as_mut().unwrap().palette = Palette::new();
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_set_palette_ntsc(hue: f32, saturation: f32, contrast: f32, brightness: f32)
+{
+	let params = NtscPaletteParams
+	{
+		hue,
+		saturation,
+		contrast,
+		brightness
+	};
+	
+	WASM_CORE.as_mut().unwrap().palette = Palette::from_ntsc(&params);
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_set_ntsc_filter(enabled: bool, sharpness: f32)
+{
+	let core = WASM_CORE.as_mut().unwrap();
+	core.set_ntsc_filter(enabled);
+	
+	if let Some(ref mut ntsc_filter) = core.ntsc_filter
+		{ ntsc_filter.sharpness = sharpness; }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_run_frame()
 {
 	for _ in 0..29780
 	{
@@ -55,7 +142,200 @@ pub unsafe extern fn wasm_core_run_frame()
 
 
 #[no_mangle]
-pub unsafe extern fn wasm_core_get_screen_buffer() -> *mut u8
+pub unsafe extern "C" fn wasm_core_get_screen_buffer() -> *mut u8
 {
 	WASM_CORE.as_mut().unwrap().screen.as_mut_ptr()
-}
\ No newline at end of file
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_get_ntsc_screen_buffer() -> *mut u8
+{
+	match WASM_CORE.as_mut().unwrap().ntsc_filter
+	{
+		Some(ref mut ntsc_filter) => ntsc_filter.screen.as_mut_ptr(),
+		None => std::ptr::null_mut()
+	}
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_render_nametables() -> *mut Vec<u8>
+{
+	Box::into_raw(Box::new(WASM_CORE.as_ref().unwrap().render_nametables()))
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_render_pattern_tables(palette_index: u8) -> *mut Vec<u8>
+{
+	Box::into_raw(Box::new(WASM_CORE.as_ref().unwrap().render_pattern_tables(palette_index)))
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_render_sprite_sheet() -> *mut Vec<u8>
+{
+	Box::into_raw(Box::new(WASM_CORE.as_ref().unwrap().render_sprite_sheet()))
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_get_sprite_list() -> *mut Vec<u8>
+{
+	let mut list = Vec::with_capacity(64 * 4);
+	
+	for spr in WASM_CORE.as_ref().unwrap().get_sprite_list()
+	{
+		list.push(spr.x);
+		list.push(spr.y);
+		list.push(spr.tile);
+		list.push(
+			spr.palette_index |
+			if spr.priority { 0x20 } else { 0 } |
+			if spr.flip_h { 0x40 } else { 0 } |
+			if spr.flip_v { 0x80 } else { 0 });
+	}
+	
+	Box::into_raw(Box::new(list))
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_render_palette() -> *mut Vec<u8>
+{
+	Box::into_raw(Box::new(WASM_CORE.as_ref().unwrap().render_palette()))
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_peek_cpu(addr: u16) -> u8
+{
+	WASM_CORE.as_ref().unwrap().peek_cpu(addr)
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_peek_ppu(addr: u16) -> u8
+{
+	WASM_CORE.as_ref().unwrap().peek_ppu(addr)
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_poke_cpu(addr: u16, val: u8)
+{
+	WASM_CORE.as_mut().unwrap().poke_cpu(addr, val);
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_poke_ppu(addr: u16, val: u8)
+{
+	WASM_CORE.as_mut().unwrap().poke_ppu(addr, val);
+}
+
+
+// A whole hex-editor page per call, instead of one call per byte
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_peek_cpu_block(addr: u16, len: usize) -> *mut Vec<u8>
+{
+	let core = WASM_CORE.as_ref().unwrap();
+	let block = (0..len).map(|i| core.peek_cpu(addr.wrapping_add(i as u16))).collect::<Vec<u8>>();
+	
+	Box::into_raw(Box::new(block))
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_peek_ppu_block(addr: u16, len: usize) -> *mut Vec<u8>
+{
+	let core = WASM_CORE.as_ref().unwrap();
+	let block = (0..len).map(|i| core.peek_ppu(addr.wrapping_add(i as u16))).collect::<Vec<u8>>();
+	
+	Box::into_raw(Box::new(block))
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_is_lag_frame() -> bool
+{
+	WASM_CORE.as_ref().unwrap().is_lag_frame()
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_get_lag_count() -> usize
+{
+	WASM_CORE.as_ref().unwrap().lag_frames
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_reset_lag_count()
+{
+	WASM_CORE.as_mut().unwrap().reset_lag_count();
+}
+
+
+// Polls of $4016 (port 0) or $4017 (port 1) during the last complete frame
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_get_input_polls(port: usize) -> usize
+{
+	WASM_CORE.as_ref().unwrap().last_input_polls[port & 1]
+}
+
+
+// Returns the new cheat's index, or -1 if the code is not valid
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_add_cheat(buffer: *mut Vec<u8>) -> i32
+{
+	let code = String::from_utf8_lossy(&*buffer);
+	WASM_CORE.as_mut().unwrap().add_cheat(&code).map_or(-1, |index| index as i32)
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_load_cheats(buffer: *mut Vec<u8>) -> i32
+{
+	let text = String::from_utf8_lossy(&*buffer);
+	WASM_CORE.as_mut().unwrap().load_cheats(&text).map_or(-1, |count| count as i32)
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_get_cheat_count() -> usize
+{
+	WASM_CORE.as_ref().unwrap().cheats.len()
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_set_cheat_enabled(index: usize, enabled: bool)
+{
+	WASM_CORE.as_mut().unwrap().set_cheat_enabled(index, enabled);
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_remove_cheat(index: usize)
+{
+	let cheats = &mut WASM_CORE.as_mut().unwrap().cheats;
+	
+	if index < cheats.len()
+		{ cheats.remove(index); }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_clear_cheats()
+{
+	WASM_CORE.as_mut().unwrap().cheats.clear();
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_core_export_cheats() -> *mut Vec<u8>
+{
+	Box::into_raw(Box::new(Cheat::to_cht(&WASM_CORE.as_ref().unwrap().cheats).into_bytes()))
+}
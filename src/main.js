@@ -7,6 +7,8 @@ let g_desiredBackend = 0
 let g_isRunning = false
 let g_screenBuffer = null
 let g_requestAnimationFrame = null
+let g_paletteFile = null
+let g_wasmCoreLoaded = false
 
 let audioCtx = null
 let keyA = false
@@ -51,6 +53,34 @@ export function main()
 		reader.onload = () => (g_desiredBackend == 0 ? loadJS(reader.result) : loadWasm(reader.result))
 		reader.readAsArrayBuffer(inputFile.files[0])
 	}
+	
+	let inputPaletteFile = document.getElementById("inputPaletteFile")
+	inputPaletteFile.onchange = () =>
+	{
+		if (inputPaletteFile.files.length != 1)
+			return
+		
+		let reader = new FileReader()
+		reader.onload = () =>
+		{
+			g_paletteFile = new Uint8Array(reader.result)
+			document.getElementById("selectPalette").value = "file"
+			applyPaletteWasm()
+		}
+		reader.readAsArrayBuffer(inputPaletteFile.files[0])
+	}
+	
+	document.getElementById("selectPalette").onchange = () => applyPaletteWasm()
+	document.getElementById("inputPaletteHue").oninput = () => applyPaletteWasm()
+	document.getElementById("inputPaletteSaturation").oninput = () => applyPaletteWasm()
+	document.getElementById("inputPaletteContrast").oninput = () => applyPaletteWasm()
+	document.getElementById("inputPaletteBrightness").oninput = () => applyPaletteWasm()
+	
+	document.getElementById("checkboxViewers").onchange = () =>
+		document.getElementById("divViewers").hidden = !document.getElementById("checkboxViewers").checked
+	
+	document.getElementById("checkboxNtscFilter").onchange = () => applyNtscFilterWasm()
+	document.getElementById("inputNtscSharpness").oninput = () => applyNtscFilterWasm()
 }
 
 
@@ -133,6 +163,8 @@ function reset()
 	g_requestAnimationFrame = null
 	
 	let canvas = document.getElementById("canvasScreen")
+	canvas.width = 256
+	
 	let ctx = canvas.getContext("2d")
 	ctx.fillStyle = "black"
 	ctx.fillRect(0, 0, 256, 240)
@@ -228,6 +260,10 @@ function loadWasm(buffer)
 		
 		g_wasm.instance.exports.wasm_core_new(wasm_buffer)
 		g_wasm.instance.exports.wasm_buffer_drop(wasm_buffer)
+		
+		g_wasmCoreLoaded = true
+		applyPaletteWasm()
+		applyNtscFilterWasm()
 	}
 	catch (e)
 	{
@@ -244,6 +280,54 @@ function loadWasm(buffer)
 }
 
 
+function applyPaletteWasm()
+{
+	if (!g_wasmCoreLoaded || g_desiredBackend != 1)
+		return
+	
+	const exports = g_wasm.instance.exports
+	const selection = document.getElementById("selectPalette").value
+	
+	if (selection == "ntsc")
+	{
+		exports.wasm_core_set_palette_ntsc(
+			parseFloat(document.getElementById("inputPaletteHue").value),
+			parseFloat(document.getElementById("inputPaletteSaturation").value),
+			parseFloat(document.getElementById("inputPaletteContrast").value),
+			parseFloat(document.getElementById("inputPaletteBrightness").value))
+	}
+	
+	else if (selection == "file" && g_paletteFile != null)
+	{
+		let wasm_buffer = exports.wasm_buffer_new(g_paletteFile.length)
+		for (let i = 0; i < g_paletteFile.length; i++)
+			exports.wasm_buffer_set(wasm_buffer, i, g_paletteFile[i])
+		
+		const accepted = exports.wasm_core_set_palette(wasm_buffer)
+		exports.wasm_buffer_drop(wasm_buffer)
+		
+		if (!accepted)
+			window.alert("Invalid palette file!\n\nExpected 64 or 512 RGB entries.")
+	}
+	
+	else
+		exports.wasm_core_set_palette_default()
+}
+
+
+function applyNtscFilterWasm()
+{
+	if (!g_wasmCoreLoaded || g_desiredBackend != 1)
+		return
+	
+	const enabled = document.getElementById("checkboxNtscFilter").checked
+	const sharpness = parseFloat(document.getElementById("inputNtscSharpness").value)
+	g_wasm.instance.exports.wasm_core_set_ntsc_filter(enabled, sharpness)
+	
+	document.getElementById("canvasScreen").width = (enabled ? NTSC_SCREEN_WIDTH : 256)
+}
+
+
 function runFrameJS(emu)
 {
 	for (let i = 0; i < 29780; i++)
@@ -271,6 +355,9 @@ function runFrameWasm()
 		g_wasm.instance.exports.wasm_core_set_controller1(controller1)
 		g_wasm.instance.exports.wasm_core_run_frame()
 		outputWasm()
+		
+		if (document.getElementById("checkboxViewers").checked)
+			outputViewersWasm()
 	}
 	catch (e)
 	{
@@ -299,9 +386,12 @@ function outputJS(emu, ctx, scanline, dot, color, mask)
 
 function outputWasm()
 {
-	const ptr = g_wasm.instance.exports.wasm_core_get_screen_buffer()
-	const buffer = new Uint8ClampedArray(g_wasm.instance.exports.memory.buffer, ptr, 256 * 240 * 4)
-	const imageData = new ImageData(buffer, 256, 240)
+	const ntscPtr = g_wasm.instance.exports.wasm_core_get_ntsc_screen_buffer()
+	const ptr = (ntscPtr != 0 ? ntscPtr : g_wasm.instance.exports.wasm_core_get_screen_buffer())
+	const width = (ntscPtr != 0 ? NTSC_SCREEN_WIDTH : 256)
+	
+	const buffer = new Uint8ClampedArray(g_wasm.instance.exports.memory.buffer, ptr, width * 240 * 4)
+	const imageData = new ImageData(buffer, width, 240)
 	
 	let canvas = document.getElementById("canvasScreen")
 	let ctx = canvas.getContext("2d")
@@ -309,6 +399,34 @@ function outputWasm()
 }
 
 
+function outputViewersWasm()
+{
+	const exports = g_wasm.instance.exports
+	const paletteIndex = parseInt(document.getElementById("selectViewerPalette").value)
+	
+	outputViewerBufferWasm(exports.wasm_core_render_nametables(), "canvasNametables", 512, 480)
+	outputViewerBufferWasm(exports.wasm_core_render_pattern_tables(paletteIndex), "canvasPatternTables", 256, 128)
+	outputViewerBufferWasm(exports.wasm_core_render_sprite_sheet(), "canvasSpriteSheet", 64, 128)
+	outputViewerBufferWasm(exports.wasm_core_render_palette(), "canvasPalette", 16, 2)
+}
+
+
+function outputViewerBufferWasm(wasm_buffer, canvasId, width, height)
+{
+	const exports = g_wasm.instance.exports
+	const ptr = exports.wasm_buffer_get_ptr(wasm_buffer)
+	const buffer = new Uint8ClampedArray(exports.memory.buffer, ptr, width * height * 4)
+	const imageData = new ImageData(new Uint8ClampedArray(buffer), width, height)
+	exports.wasm_buffer_drop(wasm_buffer)
+	
+	let ctx = document.getElementById(canvasId).getContext("2d")
+	ctx.putImageData(imageData, 0, 0)
+}
+
+
+const NTSC_SCREEN_WIDTH = 602
+
+
 const palette =
 [
 	0x75, 0x75, 0x75, 0xff,